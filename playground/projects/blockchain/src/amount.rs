@@ -0,0 +1,75 @@
+// Amounts are counted in whole units of the smallest denomination, so adding them up is always exact. Floats can't do
+// that: 0.1 + 0.2 comes out as 0.30000000000000004.
+pub const DECIMALS: usize = 8;
+pub const UNITS_PER_COIN: u64 = 100_000_000;
+
+// Reads a coin amount such as "12", "0.5" or "0.00000001" into units. Anything with more decimals than a unit can
+// hold, a sign, or a value too large to count is refused rather than rounded.
+pub fn parse(text: &str) -> Option<u64>
+{
+	let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+
+	let digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+
+	if (whole.is_empty() && fraction.is_empty()) || fraction.len() > DECIMALS || !digits(whole) || !digits(fraction)
+	{
+		return None;
+	}
+
+	let whole: u64 = match whole
+	{
+		"" => 0,
+		whole => whole.parse().ok()?
+	};
+
+	let fraction: u64 = format!("{:0<width$}", fraction, width = DECIMALS).parse().ok()?;
+
+	whole.checked_mul(UNITS_PER_COIN)?.checked_add(fraction)
+}
+
+// The reverse of 'parse', keeping only as many decimals as are needed.
+pub fn format(units: u64) -> String
+{
+	let whole = units / UNITS_PER_COIN;
+	let fraction = units % UNITS_PER_COIN;
+
+	match fraction
+	{
+		0 => whole.to_string(),
+		_ =>
+		{
+			let fraction = format!("{:0width$}", fraction, width = DECIMALS);
+			format!("{}.{}", whole, fraction.trim_end_matches('0'))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn parse_reads_coins_into_units()
+	{
+		assert_eq!(parse("12"), Some(12 * UNITS_PER_COIN));
+		assert_eq!(parse("0.1"), Some(10_000_000));
+		assert_eq!(parse(".5"), Some(50_000_000));
+		assert_eq!(parse("3."), Some(3 * UNITS_PER_COIN));
+		assert_eq!(parse("0.00000001"), Some(1));
+
+		for invalid in ["", ".", "-1", "+1", "1e3", "0.000000001", "1.2.3", "184467440738"]
+		{
+			assert_eq!(parse(invalid), None, "{:?} was accepted", invalid);
+		}
+	}
+
+	#[test]
+	fn format_round_trips_through_parse()
+	{
+		for text in ["0", "1", "0.1", "2.5", "0.00000001", "184467440737.09551615"]
+		{
+			assert_eq!(format(parse(text).unwrap()), text);
+		}
+	}
+}
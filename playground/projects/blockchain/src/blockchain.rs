@@ -1,27 +1,85 @@
+use crate::{amount::UNITS_PER_COIN, crypto, mempool::Mempool};
 use chrono::prelude::*;
-use serde_derive::Serialize;
-use sha2::{Digest, Sha256};
-use std::fmt::Write;
+use serde_derive::{Deserialize, Serialize};
+use std::{
+	error, fmt, fs, io,
+	path::Path,
+	time::{Duration, Instant}
+};
 
-#[derive(Serialize, Clone, Debug)]
-struct Transaction
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Transaction
 {
 	sender:   String,
 	receiver: String,
-	amount:   f32
+	// In units, see 'amount'.
+	amount:   u64
 }
 
-#[derive(Serialize, Debug)]
+impl Transaction
+{
+	pub fn new(sender: String, receiver: String, amount: u64) -> Transaction
+	{
+		Transaction {
+			sender,
+			receiver,
+			amount
+		}
+	}
+
+	pub fn get_sender(&self) -> &str { &self.sender }
+
+	pub fn get_receiver(&self) -> &str { &self.receiver }
+
+	pub fn get_amount(&self) -> u64 { self.amount }
+}
+
+// How a header's difficulty is measured. Hex characters multiply the work by 16 per step, while bits double it, which
+// gives much finer control over how long mining takes.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum DifficultyUnit
+{
+	HexChars,
+	Bits
+}
+
+impl DifficultyUnit
+{
+	// The most a SHA-256 hash can have of the unit: 64 hex characters, or 256 bits. Anything more could never be mined.
+	pub fn max_difficulty(&self) -> u32
+	{
+		match self
+		{
+			DifficultyUnit::HexChars => 64,
+			DifficultyUnit::Bits => 256
+		}
+	}
+}
+
+impl fmt::Display for DifficultyUnit
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		match self
+		{
+			DifficultyUnit::HexChars => write!(f, "hex characters"),
+			DifficultyUnit::Bits => write!(f, "bits")
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct BlockHeader
 {
-	timestamp:  i64,
-	nonce:      u32,
-	prev_hash:  String,
-	merkle:     String,
-	difficulty: u32
+	timestamp:       i64,
+	nonce:           u64,
+	prev_hash:       String,
+	merkle:          String,
+	difficulty:      u32,
+	difficulty_unit: DifficultyUnit
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Block
 {
 	header:       BlockHeader,
@@ -29,182 +87,1681 @@ pub struct Block
 	transactions: Vec<Transaction>
 }
 
+impl BlockHeader
+{
+	pub fn get_timestamp(&self) -> i64 { self.timestamp }
+
+	pub fn get_nonce(&self) -> u64 { self.nonce }
+
+	pub fn get_prev_hash(&self) -> &str { &self.prev_hash }
+
+	pub fn get_merkle(&self) -> &str { &self.merkle }
+
+	pub fn get_difficulty(&self) -> u32 { self.difficulty }
+
+	pub fn get_difficulty_unit(&self) -> DifficultyUnit { self.difficulty_unit }
+}
+
+impl Block
+{
+	pub fn get_header(&self) -> &BlockHeader { &self.header }
+
+	// The reward comes first, followed by the transactions that were pending when the block was mined.
+	pub fn get_transactions(&self) -> &[Transaction] { &self.transactions }
+}
+
+// How much work went into a block this node mined itself.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct MiningStat
+{
+	block:      usize,
+	difficulty: u32,
+	attempts:   u64,
+	duration:   Duration
+}
+
+impl MiningStat
+{
+	pub fn get_block(&self) -> usize { self.block }
+
+	pub fn get_difficulty(&self) -> u32 { self.difficulty }
+
+	pub fn get_attempts(&self) -> u64 { self.attempts }
+
+	pub fn get_duration(&self) -> Duration { self.duration }
+}
+
+// Difficulty 0 would let any hash through, so there would be no work to prove.
+pub const MIN_DIFFICULTY: u32 = 1;
+
+// What the genesis block has for prev_hash, since there is no block before it: as long as a hash, but all zeros.
+pub const GENESIS_PREV_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// How far ahead of this node's clock a block's timestamp may be before it is rejected, allowing for clocks that are
+// slightly off between nodes.
+pub const DEFAULT_MAX_FUTURE_DRIFT_MS: i64 = 2 * 60 * 1000;
+
+// How many of the latest block intervals are averaged when retargeting. A single interval is too noisy to go by.
+pub const DEFAULT_RETARGET_WINDOW: usize = 5;
+
+// How many hashes are tried for a block before giving up on it. On average that is enough for about 6 hex characters or
+// 26 bits of difficulty, so anything much harder fails after a minute or two instead of mining for ever.
+pub const DEFAULT_MAX_MINING_ATTEMPTS: u64 = 100_000_000;
+
+// How many pending transactions go into one block, not counting the reward. Whatever is left over waits for the next.
+pub const DEFAULT_MAX_TRANSACTIONS_PER_BLOCK: usize = 100;
+
+#[derive(Debug)]
+pub enum ChainError
+{
+	Io(io::Error),
+	Malformed(serde_json::Error),
+	// The blocks don't hold together, see 'Chain::is_valid', whether they were loaded from a file or changed after
+	// they were mined.
+	Invalid,
+	// Either address of a transaction is empty.
+	InvalidAddress,
+	// Amounts are in units, so anything below one is nothing at all.
+	InvalidAmount,
+	// Outside of MIN_DIFFICULTY up to what the unit allows, see 'DifficultyUnit::max_difficulty'.
+	InvalidDifficulty(DifficultyUnit),
+	// Any other setting out of range, with what it has to be.
+	InvalidSetting(&'static str),
+	// Empty blocks are turned off and nothing is waiting to be mined.
+	NoPendingTransactions,
+	// A block from elsewhere that is older than the last block, or newer than the allowed drift.
+	BlockOutOfTime,
+	// A block from elsewhere with more transactions than 'max_transactions_per_block', besides the reward.
+	BlockTooLarge,
+	// A block from elsewhere whose prev_hash isn't the hash of this chain's last block.
+	BlockUnlinked,
+	// A block from elsewhere that isn't as it was mined, or was mined below this chain's difficulty.
+	BlockInvalid,
+	// No nonce met the difficulty within this many attempts.
+	MiningGaveUp(u64),
+	// The very same transaction is already waiting to be mined, see 'Mempool'.
+	DuplicateTransaction
+}
+
+impl fmt::Display for ChainError
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		match self
+		{
+			ChainError::Io(error) => write!(f, "{}", error),
+			ChainError::Malformed(error) => write!(f, "Malformed chain file: {}", error),
+			ChainError::Invalid => write!(f, "The chain failed validation, it may have been tampered with"),
+			ChainError::InvalidAddress => write!(f, "Addresses can't be empty"),
+			ChainError::InvalidAmount => write!(f, "The amount must be positive"),
+			ChainError::InvalidDifficulty(unit) =>
+			{
+				write!(
+					f,
+					"Difficulty must be from {} to {} {}",
+					MIN_DIFFICULTY,
+					unit.max_difficulty(),
+					unit
+				)
+			}
+			ChainError::InvalidSetting(requirement) => write!(f, "{}", requirement),
+			ChainError::NoPendingTransactions =>
+			{
+				write!(f, "There are no pending transactions and empty blocks are turned off")
+			}
+			ChainError::BlockOutOfTime =>
+			{
+				write!(f, "The block is older than the last block or too far in the future")
+			}
+			ChainError::BlockTooLarge => write!(f, "The block has more transactions than allowed"),
+			ChainError::BlockUnlinked => write!(f, "The block doesn't follow on from the last block"),
+			ChainError::BlockInvalid =>
+			{
+				write!(f, "The block's merkle root or proof of work doesn't hold up")
+			}
+			ChainError::MiningGaveUp(attempts) =>
+			{
+				write!(
+					f,
+					"Gave up mining after {} attempts, the difficulty may be too high",
+					attempts
+				)
+			}
+			ChainError::DuplicateTransaction => write!(f, "The same transaction is already pending")
+		}
+	}
+}
+
+impl error::Error for ChainError
+{
+	fn source(&self) -> Option<&(dyn error::Error + 'static)>
+	{
+		match self
+		{
+			ChainError::Io(error) => Some(error),
+			ChainError::Malformed(error) => Some(error),
+			_ => None
+		}
+	}
+}
+
+// Whether a transaction hash is part of the merkle root, following a proof from 'Chain::merkle_proof'. Each step
+// combines the hash so far with the proof's sibling hash, on the side the proof says.
+pub fn verify_merkle_proof(tx_hash: &str, proof: &[(String, bool)], root: &str) -> bool
+{
+	let computed = proof
+		.iter()
+		.fold(tx_hash.to_string(), |hash, (sibling, sibling_is_left)| {
+			match sibling_is_left
+			{
+				true => Chain::hash(&format!("{}{}", sibling, hash)),
+				false => Chain::hash(&format!("{}{}", hash, sibling))
+			}
+		});
+
+	computed == root
+}
+
+// Saved as a whole, settings and pending transactions included, so a reloaded chain carries on exactly where it was.
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Chain
 {
-	blocks:               Vec<Block>,
-	current_transactions: Vec<Transaction>,
-	difficulty:           u32,
-	miner_address:        String,
-	reward:               f32
+	blocks:                     Vec<Block>,
+	mempool:                    Mempool,
+	difficulty:                 u32,
+	difficulty_unit:            DifficultyUnit,
+	miner_address:              String,
+	// In units, like every amount.
+	reward:                     u64,
+	max_future_drift_ms:        i64,
+	// Difficulty is only adjusted automatically while a target block time is set.
+	target_block_time_ms:       Option<i64>,
+	retarget_window:            usize,
+	// Whether a block may be mined with nothing in it but the reward.
+	allow_empty_blocks:         bool,
+	max_transactions_per_block: usize,
+	max_mining_attempts:        u64,
+	// One entry per block mined here, in order. Blocks added with 'add_block' were mined elsewhere and have none.
+	mining_stats:               Vec<MiningStat>
 }
 
 impl Chain
 {
-	pub fn new(miner_address: String, difficulty: u32) -> Chain
+	pub fn new(miner_address: String, difficulty: u32) -> Result<Chain, ChainError>
 	{
+		if difficulty < MIN_DIFFICULTY || difficulty > DifficultyUnit::HexChars.max_difficulty()
+		{
+			return Err(ChainError::InvalidDifficulty(DifficultyUnit::HexChars));
+		}
+
 		let mut chain = Chain {
 			blocks: Vec::new(),
-			current_transactions: Vec::new(),
+			mempool: Mempool::new(),
 			difficulty,
-			miner_address: miner_address,
-			reward: 100.0
+			difficulty_unit: DifficultyUnit::HexChars,
+			miner_address,
+			reward: 100 * UNITS_PER_COIN,
+			max_future_drift_ms: DEFAULT_MAX_FUTURE_DRIFT_MS,
+			target_block_time_ms: None,
+			retarget_window: DEFAULT_RETARGET_WINDOW,
+			allow_empty_blocks: true,
+			max_transactions_per_block: DEFAULT_MAX_TRANSACTIONS_PER_BLOCK,
+			max_mining_attempts: DEFAULT_MAX_MINING_ATTEMPTS,
+			mining_stats: Vec::new()
 		};
 
-		chain.generate_new_block();
-		chain
+		chain.generate_new_block()?;
+		Ok(chain)
 	}
 
-	pub fn new_transaction(&mut self, sender: String, receiver: String, amount: f32) -> bool
+	pub fn save_to_file(&self, path: &Path) -> io::Result<()>
 	{
-		self.current_transactions.push(Transaction {
-			sender,
-			receiver,
-			amount
-		});
+		let json = serde_json::to_string_pretty(self).map_err(io::Error::from)?;
 
-		true
+		fs::write(path, json)
+	}
+
+	// Refuses a file whose blocks were edited since they were mined, as well as one that can't be read.
+	pub fn load_from_file(path: &Path) -> Result<Chain, ChainError>
+	{
+		let json = fs::read_to_string(path).map_err(ChainError::Io)?;
+		let chain: Chain = serde_json::from_str(&json).map_err(ChainError::Malformed)?;
+
+		if !chain.is_valid()
+		{
+			return Err(ChainError::Invalid);
+		}
+
+		Ok(chain)
+	}
+
+	pub fn new_transaction(&mut self, sender: String, receiver: String, amount: u64) -> Result<(), ChainError>
+	{
+		let transaction = Transaction::new(sender, receiver, amount);
+
+		Chain::check_transaction(&transaction)?;
+
+		self.mempool.add(transaction)
+	}
+
+	// Adds each (sender, receiver, amount) and reports why each one was rejected, if it was, in the same order. With
+	// 'all_or_nothing' none are added unless every one is valid, though the results still point out the rejected ones.
+	pub fn new_transactions(
+		&mut self,
+		transactions: Vec<(String, String, u64)>,
+		all_or_nothing: bool
+	) -> Vec<Result<(), ChainError>>
+	{
+		let transactions: Vec<Transaction> = transactions
+			.into_iter()
+			.map(|(sender, receiver, amount)| Transaction::new(sender, receiver, amount))
+			.collect();
+
+		let results: Vec<Result<(), ChainError>> = transactions
+			.iter()
+			.enumerate()
+			.map(|(index, transaction)| {
+				Chain::check_transaction(transaction)?;
+
+				// A copy earlier in the batch is added first, so this one would be refused by then.
+				match self.mempool.contains(transaction) || transactions[..index].contains(transaction)
+				{
+					true => Err(ChainError::DuplicateTransaction),
+					false => Ok(())
+				}
+			})
+			.collect();
+
+		if all_or_nothing && results.iter().any(Result::is_err)
+		{
+			return results;
+		}
+
+		for (transaction, result) in transactions.into_iter().zip(&results)
+		{
+			if result.is_ok()
+			{
+				// Already checked against the mempool above, so this goes through.
+				let _ = self.mempool.add(transaction);
+			}
+		}
+
+		results
+	}
+
+	// Only the shape of a transaction is checked, since balances aren't tracked.
+	fn check_transaction(transaction: &Transaction) -> Result<(), ChainError>
+	{
+		if transaction.sender.is_empty() || transaction.receiver.is_empty()
+		{
+			return Err(ChainError::InvalidAddress);
+		}
+
+		if transaction.amount == 0
+		{
+			return Err(ChainError::InvalidAmount);
+		}
+
+		Ok(())
 	}
 
 	pub fn last_hash(&self) -> String
 	{
-		let block = match self.blocks.last()
+		match self.blocks.last()
 		{
-			Some(block) => block,
-			None => return String::from_utf8(vec![48; 64]).unwrap()
-		};
-		Chain::hash(&block.header)
+			Some(block) => Chain::hash(&block.header),
+			None => GENESIS_PREV_HASH.to_string()
+		}
 	}
 
-	pub fn update_difficulty(&mut self, difficulty: u32) -> bool
+	pub fn update_difficulty(&mut self, difficulty: u32) -> Result<(), ChainError>
 	{
+		if difficulty < MIN_DIFFICULTY || difficulty > self.difficulty_unit.max_difficulty()
+		{
+			return Err(ChainError::InvalidDifficulty(self.difficulty_unit));
+		}
+
 		self.difficulty = difficulty;
-		true
+		Ok(())
 	}
 
 	pub fn get_difficulty(&self) -> u32 { self.difficulty }
 
-	pub fn update_reward(&mut self, reward: f32) -> bool
+	// The difficulty is kept as it is, so it has to fit the new unit, e.g. 100 bits can't become 100 hex characters.
+	pub fn update_difficulty_unit(&mut self, difficulty_unit: DifficultyUnit) -> Result<(), ChainError>
+	{
+		if self.difficulty > difficulty_unit.max_difficulty()
+		{
+			return Err(ChainError::InvalidDifficulty(difficulty_unit));
+		}
+
+		self.difficulty_unit = difficulty_unit;
+		Ok(())
+	}
+
+	pub fn get_difficulty_unit(&self) -> DifficultyUnit { self.difficulty_unit }
+
+	// Any reward goes, nothing included.
+	pub fn update_reward(&mut self, reward: u64) -> Result<(), ChainError>
 	{
 		self.reward = reward;
-		true
+		Ok(())
 	}
 
-	pub fn get_reward(&self) -> f32 { self.reward }
+	pub fn get_reward(&self) -> u64 { self.reward }
 
-	pub fn generate_new_block(&mut self) -> bool
+	pub fn update_max_future_drift(&mut self, drift_ms: i64) -> Result<(), ChainError>
 	{
-		let block_header = BlockHeader {
-			timestamp:  Utc::now().timestamp_millis(),
-			nonce:      0,
-			prev_hash:  self.last_hash(),
-			merkle:     String::new(),
-			difficulty: self.difficulty
-		};
+		if drift_ms < 0
+		{
+			return Err(ChainError::InvalidSetting("The future drift can't be negative"));
+		}
 
-		let reward_transaction = Transaction {
-			sender:   String::from("Root"),
-			receiver: self.miner_address.clone(),
-			amount:   self.reward
-		};
+		self.max_future_drift_ms = drift_ms;
+		Ok(())
+	}
 
-		let mut block = Block {
-			header:       block_header,
-			count:        0,
-			transactions: vec![]
-		};
+	pub fn get_max_future_drift(&self) -> i64 { self.max_future_drift_ms }
 
-		block.transactions.push(reward_transaction);
-		block.transactions.append(&mut self.current_transactions);
+	// None turns automatic difficulty adjustment off.
+	pub fn update_target_block_time(&mut self, target_ms: Option<i64>) -> Result<(), ChainError>
+	{
+		if target_ms.is_some_and(|target_ms| target_ms <= 0)
+		{
+			return Err(ChainError::InvalidSetting("The target block time must be positive"));
+		}
 
-		block.count = block.transactions.len() as u32;
-		block.header.merkle = Chain::get_merkle(block.transactions.clone());
+		self.target_block_time_ms = target_ms;
+		Ok(())
+	}
 
-		Chain::proof_of_work(&mut block.header);
+	pub fn get_target_block_time(&self) -> Option<i64> { self.target_block_time_ms }
 
-		println!("{:#?}", &block);
-		self.blocks.push(block);
+	pub fn update_retarget_window(&mut self, window: usize) -> Result<(), ChainError>
+	{
+		if window == 0
+		{
+			return Err(ChainError::InvalidSetting("The retarget window must be at least 1"));
+		}
 
-		true
+		self.retarget_window = window;
+		Ok(())
 	}
 
-	fn get_merkle(current_transactions: Vec<Transaction>) -> String
+	pub fn get_retarget_window(&self) -> usize { self.retarget_window }
+
+	pub fn update_allow_empty_blocks(&mut self, allow_empty_blocks: bool) -> Result<(), ChainError>
 	{
-		let mut merkle = Vec::new();
+		self.allow_empty_blocks = allow_empty_blocks;
+		Ok(())
+	}
 
-		for transaction in &current_transactions
+	pub fn get_allow_empty_blocks(&self) -> bool { self.allow_empty_blocks }
+
+	pub fn update_max_transactions_per_block(&mut self, max_transactions: usize) -> Result<(), ChainError>
+	{
+		if max_transactions == 0
 		{
-			let hash = Chain::hash(transaction);
-			merkle.push(hash);
+			return Err(ChainError::InvalidSetting(
+				"The max transactions per block must be at least 1"
+			));
 		}
 
-		if merkle.len() % 2 == 1
+		self.max_transactions_per_block = max_transactions;
+		Ok(())
+	}
+
+	pub fn get_max_transactions_per_block(&self) -> usize { self.max_transactions_per_block }
+
+	pub fn update_max_mining_attempts(&mut self, max_attempts: u64) -> Result<(), ChainError>
+	{
+		if max_attempts == 0
 		{
-			let last_hash = merkle.last().cloned().unwrap();
-			merkle.push(last_hash);
+			return Err(ChainError::InvalidSetting("The max mining attempts must be at least 1"));
 		}
 
-		while merkle.len() > 1
-		{
-			let mut hash_one = merkle.remove(0);
-			let mut hash_two = merkle.remove(0);
+		self.max_mining_attempts = max_attempts;
+		Ok(())
+	}
 
-			hash_one.push_str(&mut hash_two);
+	pub fn get_max_mining_attempts(&self) -> u64 { self.max_mining_attempts }
 
-			let new_hash = Chain::hash(&hash_one);
-			merkle.push(new_hash)
+	// Transactions waiting for a block to be mined.
+	pub fn get_pending_count(&self) -> usize { self.mempool.len() }
+
+	// Blocks may share a timestamp but never go back in time, and none may claim to be from further in the future than
+	// the allowed drift. Each block also has to be as it was mined: its merkle root and count have to match its
+	// transactions, its hash has to meet its difficulty, and the next block's prev_hash has to be that hash. Changing
+	// anything in a block afterwards breaks at least one of these.
+	pub fn is_valid(&self) -> bool
+	{
+		let latest_allowed = Utc::now().timestamp_millis() + self.max_future_drift_ms;
+
+		let in_order = self
+			.blocks
+			.windows(2)
+			.all(|pair| pair[1].header.timestamp >= pair[0].header.timestamp);
+
+		let intact = self
+			.blocks
+			.iter()
+			.all(|block| block.header.timestamp <= latest_allowed && Chain::is_intact(block));
+
+		in_order && self.is_linked() && intact
+	}
+
+	// Whether the block's merkle root and count match its transactions and its hash meets its own difficulty.
+	fn is_intact(block: &Block) -> bool
+	{
+		block.count as usize == block.transactions.len()
+			&& block.header.merkle == Chain::compute_merkle_root(&block.transactions)
+			&& Chain::meets_difficulty(&block.header)
+	}
+
+	// Whether every block's prev_hash is still the hash of the block before it, starting from GENESIS_PREV_HASH.
+	fn is_linked(&self) -> bool
+	{
+		let mut prev_hash = GENESIS_PREV_HASH.to_string();
+
+		for block in &self.blocks
+		{
+			if block.header.prev_hash != prev_hash
+			{
+				return false;
+			}
+
+			prev_hash = Chain::hash(&block.header);
 		}
 
-		merkle.pop().unwrap()
+		true
 	}
 
-	pub fn proof_of_work(header: &mut BlockHeader)
+	// Appends a block produced elsewhere, held to everything 'is_valid' would check it for once it is in: its timestamp
+	// has to fit after the current last block, it has to point at that block's hash, and it has to be intact. On top of
+	// that it may be no bigger than a block mined here could be, and has to be mined at least at this chain's
+	// difficulty, so a block that was made cheaply can't pass. Anything taken in has to leave the chain valid, or
+	// mining here would refuse to go on from it.
+	pub fn add_block(&mut self, block: Block) -> Result<(), ChainError>
 	{
-		loop
+		if !self.fits_in_time(block.header.timestamp)
 		{
-			let hash = Chain::hash(header);
-			let slice = &hash[..header.difficulty as usize];
+			return Err(ChainError::BlockOutOfTime);
+		}
 
-			match slice.parse::<u32>()
-			{
-				Ok(value) =>
-				{
-					if value == 0
-					{
-						println!("Block hash: {}", hash);
-						break;
-					}
+		if block.transactions.len() > self.max_transactions_per_block + 1
+		{
+			return Err(ChainError::BlockTooLarge);
+		}
 
-					header.nonce += 1;
-				}
-				Err(_) =>
+		if block.header.prev_hash != self.last_hash()
+		{
+			return Err(ChainError::BlockUnlinked);
+		}
+
+		let hard_enough =
+			block.header.difficulty_unit == self.difficulty_unit && block.header.difficulty >= self.difficulty;
+
+		if !hard_enough || !Chain::is_intact(&block)
+		{
+			return Err(ChainError::BlockInvalid);
+		}
+
+		self.blocks.push(block);
+		Ok(())
+	}
+
+	fn fits_in_time(&self, timestamp: i64) -> bool
+	{
+		let not_before_last = self.blocks.last().is_none_or(|last| timestamp >= last.header.timestamp);
+
+		not_before_last && timestamp <= Utc::now().timestamp_millis() + self.max_future_drift_ms
+	}
+
+	pub fn mining_stats(&self) -> &[MiningStat] { &self.mining_stats }
+
+	pub fn last_block(&self) -> Option<&Block> { self.blocks.last() }
+
+	// The genesis block is at index 0.
+	pub fn get_block(&self, index: usize) -> Option<&Block> { self.blocks.get(index) }
+
+	pub fn get_block_count(&self) -> usize { self.blocks.len() }
+
+	// Every mined transaction the address sent or received, paired with the index of its block, oldest first.
+	pub fn history_of(&self, address: &str) -> Vec<(usize, &Transaction)>
+	{
+		let mut history = Vec::new();
+
+		for (index, block) in self.blocks.iter().enumerate()
+		{
+			for transaction in &block.transactions
+			{
+				if transaction.sender == address || transaction.receiver == address
 				{
-					header.nonce += 1;
-					continue;
+					history.push((index, transaction));
 				}
 			}
 		}
+
+		history
 	}
 
-	pub fn hash<T: serde::Serialize>(item: &T) -> String
+	// Like 'history_of', without the block indexes.
+	pub fn transactions_for(&self, address: &str) -> Vec<&Transaction>
 	{
-		let input = serde_json::to_string(&item).unwrap();
-		let mut hasher = Sha256::new();
-		hasher.update(input.as_bytes());
-		let res = hasher.finalize();
-		let vec_result = res.to_vec();
+		self.history_of(address)
+			.into_iter()
+			.map(|(_, transaction)| transaction)
+			.collect()
+	}
+
+	// What the address has received minus what it has sent, over mined blocks only. Senders aren't checked for funds,
+	// so this can go below zero.
+	pub fn balance_of(&self, address: &str) -> i128
+	{
+		self.history_of(address)
+			.into_iter()
+			.map(|(_, transaction)| {
+				let amount = transaction.amount as i128;
 
-		Chain::bytes_to_hex_string(vec_result.as_slice())
+				match (transaction.sender == address, transaction.receiver == address)
+				{
+					(true, true) => 0,
+					(true, false) => -amount,
+					(false, _) => amount
+				}
+			})
+			.sum()
 	}
 
-	pub fn bytes_to_hex_string(hex_vec: &[u8]) -> String
+	// Returns how many pending transactions went into the block, oldest first and at most
+	// 'max_transactions_per_block' of them.
+	pub fn generate_new_block(&mut self) -> Result<usize, ChainError>
 	{
-		let mut string = String::new();
+		// The genesis block has no transactions to wait for, so it is always mined.
+		if !self.allow_empty_blocks && self.mempool.is_empty() && !self.blocks.is_empty()
+		{
+			return Err(ChainError::NoPendingTransactions);
+		}
 
-		for bytes in hex_vec
+		// A block that was changed after the next one was mined on top of it no longer has the hash that block points
+		// at. Mining on regardless would bury the break under more work rather than expose it.
+		if !self.is_linked()
 		{
-			write!(&mut string, "{:x}", bytes).expect("unable to write");
+			return Err(ChainError::Invalid);
 		}
 
-		string
+		self.retarget_difficulty();
+
+		// The clock can be turned back between blocks, so the new one is never stamped before its predecessor.
+		let timestamp = self
+			.blocks
+			.last()
+			.map_or(0, |last| last.header.timestamp)
+			.max(Utc::now().timestamp_millis());
+
+		let block_header = BlockHeader {
+			timestamp,
+			nonce: 0,
+			prev_hash: self.last_hash(),
+			merkle: String::new(),
+			difficulty: self.difficulty,
+			difficulty_unit: self.difficulty_unit
+		};
+
+		let reward_transaction = Transaction {
+			sender:   String::from("Root"),
+			receiver: self.miner_address.clone(),
+			amount:   self.reward
+		};
+
+		let mut block = Block {
+			header:       block_header,
+			count:        0,
+			transactions: vec![]
+		};
+
+		let pending = self.mempool.drain(self.max_transactions_per_block);
+		let included = pending.len();
+
+		block.transactions.push(reward_transaction);
+		block.transactions.extend(pending);
+
+		block.count = block.transactions.len() as u32;
+		block.header.merkle = Chain::compute_merkle_root(&block.transactions);
+
+		let started = Instant::now();
+
+		let attempts = match Chain::proof_of_work(&mut block.header, self.max_mining_attempts)
+		{
+			Ok(attempts) => attempts,
+			Err(error) =>
+			{
+				// The transactions go back to the front of the queue, as if mining had never started.
+				self.mempool.requeue(block.transactions.drain(1..).collect());
+				return Err(error);
+			}
+		};
+
+		self.mining_stats.push(MiningStat {
+			block: self.blocks.len(),
+			difficulty: block.header.difficulty,
+			attempts,
+			duration: started.elapsed()
+		});
+
+		println!("{:#?}", &block);
+		self.blocks.push(block);
+
+		Ok(included)
+	}
+
+	// Adjusts the difficulty for the next block when a target block time is set, see 'retarget'.
+	fn retarget_difficulty(&mut self)
+	{
+		if let Some(target_ms) = self.target_block_time_ms
+		{
+			let timestamps: Vec<i64> = self.blocks.iter().map(|block| block.header.timestamp).collect();
+
+			self.difficulty = Chain::retarget(
+				self.difficulty,
+				self.difficulty_unit,
+				&timestamps,
+				target_ms,
+				self.retarget_window
+			);
+		}
+	}
+
+	// The difficulty for the next block, given the timestamps of the blocks so far. The average of the last 'window'
+	// intervals is compared against the target and the difficulty moves by at most one step at a time. It is left alone
+	// while the average is within one step's worth of work of the target, since a single step changes the mining time
+	// by that much and would otherwise keep overshooting back and forth.
+	pub fn retarget(difficulty: u32, unit: DifficultyUnit, timestamps: &[i64], target_ms: i64, window: usize) -> u32
+	{
+		let intervals = window.min(timestamps.len().saturating_sub(1));
+
+		if intervals == 0
+		{
+			return difficulty;
+		}
+
+		let last = timestamps[timestamps.len() - 1];
+		let first = timestamps[timestamps.len() - 1 - intervals];
+		let average_ms = (last - first) / intervals as i64;
+
+		let step_factor = match unit
+		{
+			DifficultyUnit::HexChars => 16,
+			DifficultyUnit::Bits => 2
+		};
+
+		if average_ms * step_factor < target_ms
+		{
+			(difficulty + 1).min(unit.max_difficulty())
+		}
+		else if average_ms > target_ms * step_factor
+		{
+			difficulty.saturating_sub(1).max(MIN_DIFFICULTY)
+		}
+		else
+		{
+			difficulty
+		}
+	}
+
+	// Public so a node can recompute a block's merkle field from its transactions and check the two match. With an odd
+	// number of transactions the last hash is paired with itself. From there the hashes are paired off in a queue, so
+	// one left over at a level is paired with the first hash of the next rather than with itself.
+	pub fn compute_merkle_root(transactions: &[Transaction]) -> String
+	{
+		let mut merkle = Vec::new();
+
+		for transaction in transactions
+		{
+			let hash = Chain::hash(transaction);
+			merkle.push(hash);
+		}
+
+		if merkle.len() % 2 == 1
+		{
+			let last_hash = merkle.last().cloned().unwrap();
+			merkle.push(last_hash);
+		}
+
+		while merkle.len() > 1
+		{
+			let mut hash_one = merkle.remove(0);
+			let hash_two = merkle.remove(0);
+
+			hash_one.push_str(&hash_two);
+
+			let new_hash = Chain::hash(&hash_one);
+			merkle.push(new_hash)
+		}
+
+		// Every mined block carries at least the reward transaction, so only a caller's empty list ends up here.
+		merkle.pop().unwrap_or_default()
+	}
+
+	// The sibling hashes that lead from a transaction's hash up to its block's merkle root, each with whether it goes
+	// on the left, for 'verify_merkle_proof'. It pairs the hashes exactly like 'compute_merkle_root' does, padding
+	// included, since any other pairing leads to a different root. None if the block or transaction doesn't exist.
+	pub fn merkle_proof(&self, block_index: usize, tx_index: usize) -> Option<Vec<(String, bool)>>
+	{
+		let transactions = &self.blocks.get(block_index)?.transactions;
+
+		if tx_index >= transactions.len()
+		{
+			return None;
+		}
+
+		let mut merkle: Vec<String> = transactions.iter().map(Chain::hash).collect();
+
+		if merkle.len() % 2 == 1
+		{
+			let last_hash = merkle.last().cloned().unwrap();
+			merkle.push(last_hash);
+		}
+
+		// Where the hash being proven, or the one it went into, is in the queue.
+		let mut position = tx_index;
+		let mut proof = Vec::new();
+
+		while merkle.len() > 1
+		{
+			let hash_one = merkle.remove(0);
+			let hash_two = merkle.remove(0);
+
+			position = match position
+			{
+				0 =>
+				{
+					proof.push((hash_two.clone(), false));
+					merkle.len()
+				}
+				1 =>
+				{
+					proof.push((hash_one.clone(), true));
+					merkle.len()
+				}
+				position => position - 2
+			};
+
+			merkle.push(Chain::hash(&format!("{}{}", hash_one, hash_two)));
+		}
+
+		Some(proof)
+	}
+
+	// Returns how many hashes it took, the successful one included, or an error once 'max_attempts' of them have all
+	// missed.
+	pub fn proof_of_work(header: &mut BlockHeader, max_attempts: u64) -> Result<u64, ChainError>
+	{
+		let mut attempts = 1;
+
+		while !Chain::meets_difficulty(header)
+		{
+			if attempts >= max_attempts
+			{
+				return Err(ChainError::MiningGaveUp(attempts));
+			}
+
+			header.nonce += 1;
+			attempts += 1;
+		}
+
+		println!("Block hash: {}", Chain::hash(header));
+		Ok(attempts)
+	}
+
+	// Whether the header's hash starts with as many zeros as its difficulty asks for, counted in its own unit. A
+	// difficulty longer than the hash can never be met.
+	fn meets_difficulty(header: &BlockHeader) -> bool
+	{
+		match header.difficulty_unit
+		{
+			DifficultyUnit::Bits => Chain::leading_zero_bits(&Chain::hash_bytes(header)) >= header.difficulty,
+			DifficultyUnit::HexChars => Chain::leading_zero_chars(&Chain::hash(header)) >= header.difficulty
+		}
+	}
+
+	fn leading_zero_chars(hash: &str) -> u32 { hash.chars().take_while(|character| *character == '0').count() as u32 }
+
+	fn leading_zero_bits(bytes: &[u8]) -> u32
+	{
+		let mut bits = 0;
+
+		for byte in bytes
+		{
+			bits += byte.leading_zeros();
+
+			if *byte != 0
+			{
+				break;
+			}
+		}
+
+		bits
+	}
+
+	pub fn hash<T: serde::Serialize>(item: &T) -> String { crypto::bytes_to_hex(&Chain::hash_bytes(item)) }
+
+	pub fn hash_bytes<T: serde::Serialize>(item: &T) -> Vec<u8>
+	{
+		let input = serde_json::to_string(&item).unwrap();
+
+		crypto::sha256(input.as_bytes())
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::amount;
+
+	// An empty block following the chain's last block, stamped with the timestamp and mined at the chain's difficulty.
+	fn block_on(chain: &Chain, timestamp: i64) -> Block
+	{
+		let mut header = BlockHeader {
+			timestamp,
+			nonce: 0,
+			prev_hash: chain.last_hash(),
+			merkle: Chain::compute_merkle_root(&[]),
+			difficulty: chain.difficulty,
+			difficulty_unit: chain.difficulty_unit
+		};
+
+		Chain::proof_of_work(&mut header, u64::MAX).unwrap();
+
+		Block {
+			header,
+			count: 0,
+			transactions: vec![]
+		}
+	}
+
+	#[test]
+	fn difficulty_zero_is_rejected()
+	{
+		assert!(matches!(
+			Chain::new(String::from("miner"), 0),
+			Err(ChainError::InvalidDifficulty(DifficultyUnit::HexChars))
+		));
+
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		assert!(chain.update_difficulty(0).is_err());
+		assert_eq!(chain.get_difficulty(), MIN_DIFFICULTY);
+
+		assert!(chain.update_difficulty(65).is_err());
+		assert!(chain.update_difficulty_unit(DifficultyUnit::Bits).is_ok());
+		assert!(chain.update_difficulty(65).is_ok());
+
+		// 65 bits fit, but 65 hex characters would be longer than the hash.
+		assert!(chain.update_difficulty_unit(DifficultyUnit::HexChars).is_err());
+		assert_eq!(chain.get_difficulty_unit(), DifficultyUnit::Bits);
+	}
+
+	#[test]
+	fn merkle_root_duplicates_the_odd_transaction()
+	{
+		let transactions: Vec<Transaction> = ["alice", "bob", "carol"]
+			.iter()
+			.map(|sender| {
+				Transaction {
+					sender:   sender.to_string(),
+					receiver: String::from("dave"),
+					amount:   1
+				}
+			})
+			.collect();
+
+		let hashes: Vec<String> = transactions.iter().map(Chain::hash).collect();
+
+		let left = Chain::hash(&format!("{}{}", hashes[0], hashes[1]));
+		let right = Chain::hash(&format!("{}{}", hashes[2], hashes[2]));
+
+		assert_eq!(
+			Chain::compute_merkle_root(&transactions),
+			Chain::hash(&format!("{}{}", left, right))
+		);
+
+		let chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+		let block = chain.blocks.last().unwrap();
+
+		assert_eq!(Chain::compute_merkle_root(&block.transactions), block.header.merkle);
+	}
+
+	#[test]
+	fn merkle_proofs_verify_only_the_transactions_in_the_block()
+	{
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		chain
+			.new_transaction(String::from("alice"), String::from("bob"), 5)
+			.unwrap();
+		chain
+			.new_transaction(String::from("bob"), String::from("carol"), 2)
+			.unwrap();
+		chain.generate_new_block().unwrap();
+
+		// The reward and the two transactions, so the last one is paired with itself.
+		let block = &chain.blocks[1];
+		assert_eq!(block.transactions.len(), 3);
+
+		for (index, transaction) in block.transactions.iter().enumerate()
+		{
+			let proof = chain.merkle_proof(1, index).unwrap();
+
+			assert!(verify_merkle_proof(
+				&Chain::hash(transaction),
+				&proof,
+				&block.header.merkle
+			));
+		}
+
+		let proof = chain.merkle_proof(1, 1).unwrap();
+		let hash = Chain::hash(&block.transactions[1]);
+
+		// Another transaction's hash, a changed sibling or a flipped side all lead somewhere else.
+		assert!(!verify_merkle_proof(
+			&Chain::hash(&block.transactions[0]),
+			&proof,
+			&block.header.merkle
+		));
+
+		let mut tampered = proof.clone();
+		tampered[0].0 = Chain::hash(&block.transactions[2]);
+		assert!(!verify_merkle_proof(&hash, &tampered, &block.header.merkle));
+
+		let mut tampered = proof.clone();
+		tampered[1].1 = !tampered[1].1;
+		assert!(!verify_merkle_proof(&hash, &tampered, &block.header.merkle));
+
+		assert!(!verify_merkle_proof(&hash, &proof, &chain.blocks[0].header.merkle));
+
+		assert!(chain.merkle_proof(1, 3).is_none());
+		assert!(chain.merkle_proof(2, 0).is_none());
+
+		// Larger blocks carry hashes over between levels, which the proof has to follow as well.
+		for amount in 1..=6
+		{
+			chain
+				.new_transaction(String::from("alice"), String::from("bob"), amount)
+				.unwrap();
+		}
+
+		chain.generate_new_block().unwrap();
+
+		let block = &chain.blocks[2];
+
+		assert!(block.transactions.iter().enumerate().all(|(index, transaction)| {
+			let proof = chain.merkle_proof(2, index).unwrap();
+
+			verify_merkle_proof(&Chain::hash(transaction), &proof, &block.header.merkle)
+		}));
+	}
+
+	#[test]
+	fn blocks_must_not_go_back_in_time()
+	{
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		chain.generate_new_block().unwrap();
+		assert!(chain.is_valid());
+
+		chain.blocks[1].header.timestamp = chain.blocks[0].header.timestamp - 1;
+		assert!(!chain.is_valid());
+
+		// Both blocks follow on from the genesis block, so their timestamps are all that sets them apart.
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+		let genesis_time = chain.blocks[0].header.timestamp;
+
+		let block = block_on(&chain, genesis_time - 1);
+		assert!(matches!(chain.add_block(block), Err(ChainError::BlockOutOfTime)));
+		assert_eq!(chain.blocks.len(), 1);
+
+		let block = block_on(&chain, genesis_time);
+		assert!(chain.add_block(block).is_ok());
+		assert_eq!(chain.blocks.len(), 2);
+		assert!(chain.is_valid());
+	}
+
+	#[test]
+	fn blocks_from_too_far_in_the_future_are_rejected()
+	{
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+		let drift = chain.get_max_future_drift();
+
+		let mut block = Chain::new(String::from("miner"), MIN_DIFFICULTY)
+			.unwrap()
+			.blocks
+			.remove(0);
+
+		block.header.timestamp = Utc::now().timestamp_millis() + drift + 60 * 60 * 1000;
+		assert!(matches!(chain.add_block(block), Err(ChainError::BlockOutOfTime)));
+
+		// Mined again, so the timestamp is the only thing wrong with the block.
+		chain.blocks[0].header.timestamp = Utc::now().timestamp_millis() + drift + 60 * 60 * 1000;
+		Chain::proof_of_work(&mut chain.blocks[0].header, u64::MAX).unwrap();
+		assert!(!chain.is_valid());
+
+		// Allowing more drift lets the same chain through.
+		assert!(chain.update_max_future_drift(drift + 2 * 60 * 60 * 1000).is_ok());
+		assert!(chain.is_valid());
+		assert!(chain.update_max_future_drift(-1).is_err());
+	}
+
+	#[test]
+	fn tampering_with_a_mined_block_is_detected()
+	{
+		let mined = || {
+			let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+			chain
+				.new_transaction(String::from("alice"), String::from("bob"), 5)
+				.unwrap();
+			chain
+				.new_transaction(String::from("bob"), String::from("carol"), 2)
+				.unwrap();
+			chain.generate_new_block().unwrap();
+			chain.generate_new_block().unwrap();
+			chain
+		};
+
+		assert!(mined().is_valid());
+
+		// A changed amount no longer matches the merkle root.
+		let mut chain = mined();
+		chain.blocks[1].transactions[1].amount = 500;
+		assert!(!chain.is_valid());
+
+		// Fixing the merkle root up changes the header, so its hash no longer matches the next block's prev_hash.
+		chain.blocks[1].header.merkle = Chain::compute_merkle_root(&chain.blocks[1].transactions);
+		assert!(!chain.is_valid());
+
+		// Even mined again to meet the difficulty, the block is no longer the one the next block points at.
+		Chain::proof_of_work(&mut chain.blocks[1].header, u64::MAX).unwrap();
+		assert!(!chain.is_valid());
+
+		// The tip has no block after it, so once mined again it is valid, as anyone could mine a block like it.
+		let mut chain = mined();
+		chain.blocks[2].transactions[0].amount = 500;
+		chain.blocks[2].header.merkle = Chain::compute_merkle_root(&chain.blocks[2].transactions);
+		Chain::proof_of_work(&mut chain.blocks[2].header, u64::MAX).unwrap();
+		assert!(chain.is_valid());
+
+		// Unless a transaction goes missing without the count being updated.
+		chain.blocks[2].transactions.pop();
+		chain.blocks[2].header.merkle = Chain::compute_merkle_root(&chain.blocks[2].transactions);
+		Chain::proof_of_work(&mut chain.blocks[2].header, u64::MAX).unwrap();
+		assert!(!chain.is_valid());
+	}
+
+	#[test]
+	fn chains_survive_a_save_and_reload_but_tampered_files_do_not()
+	{
+		let path = std::env::temp_dir().join(format!("blockchain_chain_{}.json", std::process::id()));
+
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		chain
+			.new_transaction(String::from("alice"), String::from("bob"), 5)
+			.unwrap();
+		chain.generate_new_block().unwrap();
+		chain
+			.new_transaction(String::from("bob"), String::from("carol"), 2)
+			.unwrap();
+		chain.save_to_file(&path).unwrap();
+
+		let loaded = Chain::load_from_file(&path).unwrap();
+
+		assert_eq!(loaded.get_block_count(), 2);
+		assert_eq!(loaded.last_hash(), chain.last_hash());
+		assert_eq!(loaded.get_pending_count(), 1);
+		assert_eq!(loaded.mining_stats(), chain.mining_stats());
+		assert_eq!(loaded.balance_of("bob"), chain.balance_of("bob"));
+
+		let json = std::fs::read_to_string(&path).unwrap();
+		std::fs::write(&path, json.replacen("\"amount\": 5", "\"amount\": 50", 1)).unwrap();
+
+		assert!(matches!(Chain::load_from_file(&path), Err(ChainError::Invalid)));
+
+		std::fs::write(&path, "{").unwrap();
+		assert!(matches!(Chain::load_from_file(&path), Err(ChainError::Malformed(_))));
+
+		std::fs::remove_file(&path).unwrap();
+		assert!(matches!(Chain::load_from_file(&path), Err(ChainError::Io(_))));
+	}
+
+	#[test]
+	fn mining_refuses_to_extend_a_broken_chain()
+	{
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		assert_eq!(chain.blocks[0].header.prev_hash, GENESIS_PREV_HASH);
+
+		chain
+			.new_transaction(String::from("alice"), String::from("bob"), 5)
+			.unwrap();
+		assert!(chain.generate_new_block().is_ok());
+		assert!(chain.generate_new_block().is_ok());
+
+		chain
+			.new_transaction(String::from("alice"), String::from("bob"), 1)
+			.unwrap();
+		chain.blocks[1].transactions[1].amount = 500;
+		chain.blocks[1].header.merkle = Chain::compute_merkle_root(&chain.blocks[1].transactions);
+
+		assert!(matches!(chain.generate_new_block(), Err(ChainError::Invalid)));
+		assert_eq!(chain.blocks.len(), 3);
+
+		// Nothing was taken from the pending transactions either.
+		assert_eq!(chain.get_pending_count(), 1);
+	}
+
+	#[test]
+	fn leading_zero_bits_counts_across_bytes()
+	{
+		assert_eq!(Chain::leading_zero_bits(&[0xff, 0x00]), 0);
+		assert_eq!(Chain::leading_zero_bits(&[0x01, 0xff]), 7);
+		assert_eq!(Chain::leading_zero_bits(&[0x00, 0x40, 0x00]), 9);
+		assert_eq!(Chain::leading_zero_bits(&[0x00, 0x00, 0x0f]), 20);
+		assert_eq!(Chain::leading_zero_bits(&[0x00, 0x00]), 16);
+	}
+
+	#[test]
+	fn mining_in_hex_characters_leads_with_that_many_zeros()
+	{
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		assert!(chain.update_difficulty(2).is_ok());
+		assert!(chain.generate_new_block().is_ok());
+
+		let block = chain.blocks.last().unwrap();
+
+		assert_eq!(block.header.difficulty_unit, DifficultyUnit::HexChars);
+		assert!(Chain::hash(&block.header).starts_with("00"));
+
+		// Letters are hex digits too, so a hash starting with "0a" has one leading zero, not a parse failure.
+		assert_eq!(Chain::leading_zero_chars("0a00"), 1);
+		assert_eq!(Chain::leading_zero_chars("000f"), 3);
+		assert_eq!(Chain::leading_zero_chars("f000"), 0);
+	}
+
+	#[test]
+	fn mining_in_bits_meets_the_target()
+	{
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		assert!(chain.update_difficulty_unit(DifficultyUnit::Bits).is_ok());
+		assert!(chain.update_difficulty(9).is_ok());
+		assert!(chain.generate_new_block().is_ok());
+
+		let header = &chain.blocks.last().unwrap().header;
+
+		assert_eq!(header.difficulty_unit, DifficultyUnit::Bits);
+		assert!(Chain::leading_zero_bits(&Chain::hash_bytes(header)) >= 9);
+	}
+
+	#[test]
+	fn retargeting_converges_after_fast_then_slow_blocks()
+	{
+		let target_ms = 1000;
+		let mut timestamps = vec![0];
+		let mut difficulty = MIN_DIFFICULTY;
+		let mut history = Vec::new();
+
+		// Each extra bit of difficulty doubles the mining time. The second half simulates slower hardware.
+		for step in 0..80
+		{
+			let cost_ms = if step < 40 { 1 } else { 8 };
+
+			difficulty = Chain::retarget(
+				difficulty,
+				DifficultyUnit::Bits,
+				&timestamps,
+				target_ms,
+				DEFAULT_RETARGET_WINDOW
+			);
+			history.push(difficulty);
+			timestamps.push(timestamps.last().unwrap() + (cost_ms << difficulty));
+		}
+
+		// Fast blocks push the difficulty up to where a block takes about the target time, and it then stays there.
+		assert!(history[25..40].iter().all(|&settled| settled == history[39]));
+		assert!((500..=2000).contains(&(1 << history[39])));
+
+		// Slower blocks bring it back down by three bits, again without bouncing around afterwards.
+		assert!(history[65..].iter().all(|&settled| settled == history[79]));
+		assert!((500..=2000).contains(&(8 << history[79])));
+		assert!(history[79] < history[39]);
+
+		// Each step moves by at most one.
+		assert!(history.windows(2).all(|pair| pair[0].abs_diff(pair[1]) <= 1));
+	}
+
+	#[test]
+	fn retarget_settings_are_validated()
+	{
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		assert_eq!(chain.get_target_block_time(), None);
+		assert!(chain.update_target_block_time(Some(0)).is_err());
+		assert!(chain.update_target_block_time(Some(1000)).is_ok());
+		assert!(chain.update_retarget_window(0).is_err());
+		assert!(chain.update_retarget_window(10).is_ok());
+
+		// However fast blocks come, the difficulty stops where no hash could meet it any more.
+		assert_eq!(Chain::retarget(64, DifficultyUnit::HexChars, &[0, 0, 0], 1000, 5), 64);
+		assert_eq!(Chain::retarget(63, DifficultyUnit::HexChars, &[0, 0, 0], 1000, 5), 64);
+		assert_eq!(Chain::retarget(256, DifficultyUnit::Bits, &[0, 0, 0], 1000, 5), 256);
+
+		// Too few blocks to average anything leaves the difficulty as it is.
+		assert_eq!(Chain::retarget(3, DifficultyUnit::Bits, &[0], 1000, 5), 3);
+		assert_eq!(
+			Chain::retarget(MIN_DIFFICULTY, DifficultyUnit::Bits, &[0, 60_000], 1000, 5),
+			MIN_DIFFICULTY
+		);
+	}
+
+	#[test]
+	fn empty_blocks_can_be_refused()
+	{
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		assert!(chain.get_allow_empty_blocks());
+		assert!(chain.generate_new_block().is_ok());
+		assert_eq!(chain.blocks.len(), 2);
+
+		assert!(chain.update_allow_empty_blocks(false).is_ok());
+		assert!(matches!(
+			chain.generate_new_block(),
+			Err(ChainError::NoPendingTransactions)
+		));
+		assert_eq!(chain.blocks.len(), 2);
+
+		chain
+			.new_transaction(String::from("alice"), String::from("bob"), 1)
+			.unwrap();
+		assert!(chain.generate_new_block().is_ok());
+		assert_eq!(chain.blocks.len(), 3);
+		assert_eq!(chain.blocks[2].transactions.len(), 2);
+
+		// Mining took the pending transaction, so the next block would be empty again.
+		assert!(matches!(
+			chain.generate_new_block(),
+			Err(ChainError::NoPendingTransactions)
+		));
+	}
+
+	#[test]
+	fn transactions_over_the_block_limit_wait_for_the_next_block()
+	{
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		assert!(chain.update_max_transactions_per_block(0).is_err());
+		assert!(chain.update_max_transactions_per_block(2).is_ok());
+
+		for amount in 1..=5
+		{
+			chain
+				.new_transaction(String::from("alice"), String::from("bob"), amount)
+				.unwrap();
+		}
+
+		assert_eq!(chain.generate_new_block().unwrap(), 2);
+		assert_eq!(chain.get_pending_count(), 3);
+
+		// The reward comes on top of the limit, and the oldest transactions go first.
+		let amounts: Vec<u64> = chain.blocks[1]
+			.transactions
+			.iter()
+			.map(Transaction::get_amount)
+			.collect();
+		assert_eq!(amounts, vec![chain.get_reward(), 1, 2]);
+
+		assert_eq!(chain.generate_new_block().unwrap(), 2);
+		assert_eq!(chain.generate_new_block().unwrap(), 1);
+		assert_eq!(chain.get_pending_count(), 0);
+		assert!(chain.is_valid());
+
+		// A block mined elsewhere with a higher limit is too big to take in.
+		let mut other = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		for amount in 1..=3
+		{
+			other
+				.new_transaction(String::from("alice"), String::from("bob"), amount)
+				.unwrap();
+		}
+
+		other.generate_new_block().unwrap();
+
+		let block = other.blocks.pop().unwrap();
+		assert!(matches!(chain.add_block(block), Err(ChainError::BlockTooLarge)));
+	}
+
+	#[test]
+	fn blocks_from_elsewhere_have_to_link_and_be_mined()
+	{
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+		let now = Utc::now().timestamp_millis();
+
+		let mut unlinked = block_on(&chain, now);
+		unlinked.header.prev_hash = Chain::hash(&"elsewhere");
+		Chain::proof_of_work(&mut unlinked.header, u64::MAX).unwrap();
+		assert!(matches!(chain.add_block(unlinked), Err(ChainError::BlockUnlinked)));
+
+		let mut unmined = block_on(&chain, now);
+		while Chain::meets_difficulty(&unmined.header)
+		{
+			unmined.header.nonce += 1;
+		}
+		assert!(matches!(chain.add_block(unmined), Err(ChainError::BlockInvalid)));
+
+		let mut tampered = block_on(&chain, now);
+		tampered.header.merkle = Chain::hash(&"something else");
+		Chain::proof_of_work(&mut tampered.header, u64::MAX).unwrap();
+		assert!(matches!(chain.add_block(tampered), Err(ChainError::BlockInvalid)));
+
+		// Mined properly, but below the difficulty this chain asks for.
+		let easy = block_on(&chain, now);
+		chain.update_difficulty(MIN_DIFFICULTY + 1).unwrap();
+		assert!(matches!(chain.add_block(easy), Err(ChainError::BlockInvalid)));
+		chain.update_difficulty(MIN_DIFFICULTY).unwrap();
+
+		assert_eq!(chain.blocks.len(), 1);
+		assert!(chain.is_valid());
+
+		// None of it got in the way of mining here.
+		assert!(chain.generate_new_block().is_ok());
+		assert!(chain.add_block(block_on(&chain, Utc::now().timestamp_millis())).is_ok());
+		assert!(chain.is_valid());
+	}
+
+	#[test]
+	fn batches_report_which_transactions_were_rejected()
+	{
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		let batch = || {
+			vec![
+				(String::from("alice"), String::from("bob"), 5),
+				(String::from("bob"), String::from("carol"), 0),
+				(String::new(), String::from("carol"), 1),
+				(String::from("carol"), String::from("alice"), 2),
+				(String::from("dave"), String::new(), 1),
+			]
+		};
+
+		let accepted = |results: Vec<Result<(), ChainError>>| results.iter().map(Result::is_ok).collect::<Vec<bool>>();
+		let expected = vec![true, false, false, true, false];
+
+		// All or nothing: one bad transaction keeps the whole batch out.
+		assert_eq!(accepted(chain.new_transactions(batch(), true)), expected);
+		assert!(chain.mempool.is_empty());
+
+		let results = chain.new_transactions(batch(), false);
+
+		assert!(matches!(results[1], Err(ChainError::InvalidAmount)));
+		assert!(matches!(results[2], Err(ChainError::InvalidAddress)));
+		assert_eq!(accepted(results), expected);
+
+		let added: Vec<(&str, &str)> = chain
+			.mempool
+			.get_transactions()
+			.iter()
+			.map(|transaction| (transaction.get_sender(), transaction.get_receiver()))
+			.collect();
+
+		assert_eq!(added, vec![("alice", "bob"), ("carol", "alice")]);
+
+		let valid = vec![(String::from("alice"), String::from("bob"), 1)];
+
+		assert_eq!(accepted(chain.new_transactions(valid, true)), vec![true]);
+		assert_eq!(chain.get_pending_count(), 3);
+		assert!(matches!(
+			chain.new_transaction(String::from("alice"), String::from("bob"), 0),
+			Err(ChainError::InvalidAmount)
+		));
+	}
+
+	#[test]
+	fn only_positive_amounts_make_it_into_a_transaction()
+	{
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		// Amounts are whole units, so a negative or NaN amount can't even be written down: parsing refuses them.
+		for invalid in ["-5.0", "NaN", "inf"]
+		{
+			assert_eq!(amount::parse(invalid), None, "{:?} was accepted", invalid);
+		}
+
+		// Zero parses, but isn't worth a transaction.
+		let zero = amount::parse("0.0").unwrap();
+
+		assert!(matches!(
+			chain.new_transaction(String::from("alice"), String::from("bob"), zero),
+			Err(ChainError::InvalidAmount)
+		));
+		assert!(chain
+			.new_transaction(
+				String::from("alice"),
+				String::from("bob"),
+				amount::parse("0.00000001").unwrap()
+			)
+			.is_ok());
+		assert_eq!(chain.get_pending_count(), 1);
+	}
+
+	#[test]
+	fn mining_gives_up_instead_of_spinning_at_an_absurd_difficulty()
+	{
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		assert!(chain.update_max_mining_attempts(0).is_err());
+		assert!(chain.update_max_mining_attempts(1000).is_ok());
+		assert!(chain
+			.update_difficulty(DifficultyUnit::HexChars.max_difficulty())
+			.is_ok());
+
+		chain
+			.new_transaction(String::from("alice"), String::from("bob"), 5)
+			.unwrap();
+
+		assert!(matches!(
+			chain.generate_new_block(),
+			Err(ChainError::MiningGaveUp(1000))
+		));
+		assert_eq!(chain.blocks.len(), 1);
+		assert_eq!(chain.mining_stats().len(), 1);
+
+		// The transaction is still pending, ready for when the difficulty comes down again.
+		assert_eq!(chain.get_pending_count(), 1);
+
+		assert!(chain.update_difficulty(MIN_DIFFICULTY).is_ok());
+		assert_eq!(chain.generate_new_block().unwrap(), 1);
+	}
+
+	#[test]
+	fn a_duplicated_transaction_is_only_mined_once()
+	{
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		chain
+			.new_transaction(String::from("alice"), String::from("bob"), 5)
+			.unwrap();
+
+		assert!(matches!(
+			chain.new_transaction(String::from("alice"), String::from("bob"), 5),
+			Err(ChainError::DuplicateTransaction)
+		));
+
+		let batch = vec![
+			(String::from("alice"), String::from("bob"), 5),
+			(String::from("bob"), String::from("carol"), 2),
+			(String::from("bob"), String::from("carol"), 2),
+		];
+
+		let results = chain.new_transactions(batch, false);
+
+		assert!(matches!(results[0], Err(ChainError::DuplicateTransaction)));
+		assert!(results[1].is_ok());
+		assert!(matches!(results[2], Err(ChainError::DuplicateTransaction)));
+
+		assert_eq!(chain.generate_new_block().unwrap(), 2);
+		assert_eq!(chain.balance_of("bob"), 3);
+	}
+
+	#[test]
+	fn higher_difficulty_takes_more_attempts()
+	{
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		chain.update_difficulty_unit(DifficultyUnit::Bits).unwrap();
+
+		let mut average_attempts = |difficulty| {
+			chain.update_difficulty(difficulty).unwrap();
+
+			for _ in 0..10
+			{
+				chain.generate_new_block().unwrap();
+			}
+
+			let stats = &chain.mining_stats()[chain.mining_stats().len() - 10..];
+
+			assert!(stats.iter().all(|stat| stat.get_difficulty() == difficulty));
+			stats.iter().map(MiningStat::get_attempts).sum::<u64>() / 10
+		};
+
+		// About 2 attempts are needed per block at 1 bit, and about 256 at 8.
+		let easy = average_attempts(1);
+		let hard = average_attempts(8);
+
+		assert!(hard > easy * 4, "{} attempts at 8 bits against {} at 1 bit", hard, easy);
+
+		// The genesis block is counted too, and each entry points at the block it describes.
+		assert_eq!(chain.mining_stats().len(), 21);
+		assert!(chain
+			.mining_stats()
+			.iter()
+			.enumerate()
+			.all(|(index, stat)| stat.get_block() == index));
+	}
+
+	#[test]
+	fn history_of_lists_sent_and_received_transactions_in_order()
+	{
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		chain
+			.new_transaction(String::from("alice"), String::from("bob"), 5)
+			.unwrap();
+		chain
+			.new_transaction(String::from("carol"), String::from("dave"), 1)
+			.unwrap();
+		chain.generate_new_block().unwrap();
+
+		chain
+			.new_transaction(String::from("bob"), String::from("alice"), 2)
+			.unwrap();
+		chain.generate_new_block().unwrap();
+
+		// Pending transactions haven't been mined yet, so they aren't part of the history.
+		chain
+			.new_transaction(String::from("alice"), String::from("carol"), 3)
+			.unwrap();
+
+		let history: Vec<(usize, &str, &str, u64)> = chain
+			.history_of("alice")
+			.into_iter()
+			.map(|(index, transaction)| {
+				(
+					index,
+					transaction.get_sender(),
+					transaction.get_receiver(),
+					transaction.get_amount()
+				)
+			})
+			.collect();
+
+		assert_eq!(history, vec![(1, "alice", "bob", 5), (2, "bob", "alice", 2)]);
+		assert!(chain.history_of("nobody").is_empty());
+
+		let amounts: Vec<u64> = chain
+			.transactions_for("alice")
+			.into_iter()
+			.map(Transaction::get_amount)
+			.collect();
+
+		assert_eq!(amounts, vec![5, 2]);
+	}
+
+	#[test]
+	fn blocks_can_be_looked_up_by_index()
+	{
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		chain
+			.new_transaction(String::from("alice"), String::from("bob"), 5)
+			.unwrap();
+		chain.generate_new_block().unwrap();
+
+		assert_eq!(chain.get_block_count(), 2);
+		assert!(chain.get_block(2).is_none());
+
+		let genesis = chain.get_block(0).unwrap();
+		let block = chain.get_block(1).unwrap();
+
+		assert_eq!(block.get_header().get_prev_hash(), Chain::hash(genesis.get_header()));
+		assert_eq!(
+			block.get_header().get_merkle(),
+			Chain::compute_merkle_root(block.get_transactions())
+		);
+
+		let receivers: Vec<&str> = block.get_transactions().iter().map(Transaction::get_receiver).collect();
+
+		assert_eq!(receivers, vec!["miner", "bob"]);
+	}
+
+	#[test]
+	fn balances_add_up_exactly_over_many_small_transactions()
+	{
+		let mut chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+
+		let tenth = amount::parse("0.1").unwrap();
+		let fifth = amount::parse("0.2").unwrap();
+
+		assert_eq!(tenth + fifth, amount::parse("0.3").unwrap());
+
+		// All of them go into the one block. Identical payments would only be pending once, so each comes from or goes
+		// to an address of its own.
+		assert!(chain.update_max_transactions_per_block(2000).is_ok());
+
+		for index in 0..1000
+		{
+			chain
+				.new_transaction(format!("alice{}", index), String::from("bob"), tenth)
+				.unwrap();
+			chain
+				.new_transaction(String::from("bob"), format!("carol{}", index), fifth)
+				.unwrap();
+		}
+
+		chain.generate_new_block().unwrap();
+
+		let coins = |text| amount::parse(text).unwrap() as i128;
+		let total = |name: &str| -> i128 {
+			(0..1000)
+				.map(|index| chain.balance_of(&format!("{}{}", name, index)))
+				.sum()
+		};
+
+		assert_eq!(total("alice"), -coins("100"));
+		assert_eq!(chain.balance_of("bob"), -coins("100"));
+		assert_eq!(total("carol"), coins("200"));
+		assert_eq!(chain.balance_of("miner"), coins("200"));
 	}
 }
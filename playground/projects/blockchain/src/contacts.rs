@@ -0,0 +1,53 @@
+use std::{
+	collections::BTreeMap,
+	fs, io,
+	path::{Path, PathBuf}
+};
+
+// A name -> address book persisted as a JSON object, so long addresses don't have to be typed at every prompt.
+pub struct Contacts
+{
+	path:    PathBuf,
+	entries: BTreeMap<String, String>
+}
+
+impl Contacts
+{
+	pub fn load(path: &Path) -> io::Result<Contacts>
+	{
+		let entries = match fs::read_to_string(path)
+		{
+			Ok(contents) =>
+			{
+				serde_json::from_str(&contents).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?
+			}
+			Err(ref error) if error.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+			Err(error) => return Err(error)
+		};
+
+		Ok(Contacts {
+			path: path.to_path_buf(),
+			entries
+		})
+	}
+
+	pub fn add(&mut self, name: String, address: String) -> io::Result<()>
+	{
+		self.entries.insert(name, address);
+
+		let contents = serde_json::to_string_pretty(&self.entries).map_err(io::Error::from)?;
+		fs::write(&self.path, contents)
+	}
+
+	// Names that aren't in the book are assumed to already be addresses.
+	pub fn resolve(&self, name_or_address: &str) -> String
+	{
+		match self.entries.get(name_or_address)
+		{
+			Some(address) => address.clone(),
+			None => name_or_address.to_string()
+		}
+	}
+
+	pub fn entries(&self) -> &BTreeMap<String, String> { &self.entries }
+}
@@ -0,0 +1,46 @@
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+pub fn sha256(bytes: &[u8]) -> Vec<u8> { Sha256::digest(bytes).to_vec() }
+
+pub fn sha256_hex(bytes: &[u8]) -> String { bytes_to_hex(&sha256(bytes)) }
+
+// Two characters per byte, so a byte below 0x10 keeps its leading zero and every digest comes out the same length.
+pub fn bytes_to_hex(bytes: &[u8]) -> String
+{
+	let mut string = String::with_capacity(bytes.len() * 2);
+
+	for byte in bytes
+	{
+		write!(&mut string, "{:02x}", byte).expect("unable to write");
+	}
+
+	string
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn sha256_matches_the_published_test_vectors()
+	{
+		assert_eq!(
+			sha256_hex(b""),
+			"e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+		);
+		assert_eq!(
+			sha256_hex(b"abc"),
+			"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+		);
+		assert_eq!(sha256(b"abc").len(), 32);
+	}
+
+	#[test]
+	fn bytes_to_hex_keeps_leading_zeros()
+	{
+		assert_eq!(bytes_to_hex(&[0x00, 0x0f, 0xab, 0x10]), "000fab10");
+		assert_eq!(bytes_to_hex(&[]), "");
+	}
+}
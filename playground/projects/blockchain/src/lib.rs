@@ -0,0 +1,6 @@
+pub mod amount;
+pub mod blockchain;
+pub mod contacts;
+pub mod crypto;
+pub mod mempool;
+pub mod network;
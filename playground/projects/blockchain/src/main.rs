@@ -1,77 +1,108 @@
 extern crate serde_derive;
 
-use std::{io, io::Write, process};
+use blockchain::{
+	amount,
+	blockchain::{Chain, DifficultyUnit},
+	contacts::Contacts
+};
+use std::{
+	io::{self, BufRead, Write},
+	path::Path,
+	process,
+	sync::{
+		mpsc::{self, RecvTimeoutError},
+		Mutex
+	},
+	thread,
+	time::Duration
+};
+
+const CONTACTS_FILE: &str = "contacts.json";
 
-mod blockchain;
 fn main()
 {
-	let mut miner_address = String::new();
-	let mut difficulty = String::new();
-	let mut choice = String::new();
-
-	print!("input a miner address: ");
-	receive_input_from_user(&mut miner_address);
+	let mut contacts = Contacts::load(Path::new(CONTACTS_FILE)).unwrap_or_else(|error| {
+		eprintln!("Failed to load contacts from {}: {}", CONTACTS_FILE, error);
+		process::exit(1);
+	});
 
-	print!("Difficulty: ");
-	receive_input_from_user(&mut difficulty);
+	let miner_address = prompt("input a miner address: ");
 
-	let difficulty = difficulty.trim().parse::<u32>().expect("Difficulty must be an integer");
+	// Asked again until it is a number, like any other invalid input.
+	let difficulty = loop
+	{
+		match prompt("Difficulty: ").parse::<u32>()
+		{
+			Ok(difficulty) => break difficulty,
+			Err(_) => println!("Difficulty must be an integer")
+		}
+	};
 
 	println!("Generating genesis block! ");
 
-	let mut chain = blockchain::Chain::new(miner_address.trim().to_string(), difficulty);
+	let mut chain = Chain::new(contacts.resolve(&miner_address), difficulty).unwrap_or_else(|error| {
+		eprintln!("{}", error);
+		process::exit(1);
+	});
 
 	loop
 	{
-		choice.clear();
-
 		println!("Menu");
 		println!("1) New Transaction");
 		println!("2) Mine block");
 		println!("3) Change Difficulty");
 		println!("4) Change Reward");
+		println!("5) Change Difficulty Unit");
+		println!("6) Contacts");
+		println!("7) Address Statement");
+		println!("8) Auto-mine");
+		println!("9) Difficulty Retargeting");
+		println!("10) Toggle Empty Blocks");
+		println!("11) Mining Stats");
+		println!("12) Save Chain");
+		println!("13) Load Chain");
+		println!("14) Max Transactions Per Block");
+		println!("15) Max Mining Attempts");
 		println!("0) Exit");
-		print!("Enter your choice: ");
 
-		receive_input_from_user(&mut choice);
+		let choice = prompt("Enter your choice: ");
 
-		println!("");
+		println!();
 
-		match choice.trim().parse().unwrap()
+		// Anything that isn't a number, an empty line included, falls through to the invalid option below.
+		match choice.parse::<u32>().ok()
 		{
-			0 =>
+			Some(0) =>
 			{
 				println!("Existing!");
 				process::exit(0);
 			}
-			1 =>
+			Some(1) =>
 			{
-				let mut sender = String::new();
-				let mut receiver = String::new();
-				let mut amount = String::new();
-
-				print!("Enter sender address:");
-				receive_input_from_user(&mut sender);
+				let sender = prompt("Enter sender address:");
+				let receiver = prompt("Enter receiver address: ");
+				let amount = prompt("Enter amount: ");
 
-				print!("Enter receiver address: ");
-				receive_input_from_user(&mut receiver);
-
-				print!("Enter amount: ");
-				receive_input_from_user(&mut amount);
+				let amount = match amount::parse(&amount)
+				{
+					Some(amount) => amount,
+					None =>
+					{
+						println!("Invalid amount: use up to {} decimals, e.g. 0.5", amount::DECIMALS);
+						continue;
+					}
+				};
 
-				let new_transaction = chain.new_transaction(
-					sender.trim().to_string(),
-					receiver.trim().to_string(),
-					amount.trim().parse().unwrap()
-				);
+				let new_transaction =
+					chain.new_transaction(contacts.resolve(&sender), contacts.resolve(&receiver), amount);
 
 				match new_transaction
 				{
-					true => println!("Transaction added successfully"),
-					false => println!("Failed to add new transaction")
+					Ok(_) => println!("Transaction added successfully"),
+					Err(error) => println!("Failed to add new transaction: {}", error)
 				}
 			}
-			2 =>
+			Some(2) =>
 			{
 				println!("Generating new block");
 
@@ -79,42 +110,333 @@ fn main()
 
 				match res
 				{
-					true => println!("Block generated successfully"),
-					false => println!("Failed to generate new block")
+					Ok(included) =>
+					{
+						println!(
+							"Block generated successfully with {} transactions, {} still pending",
+							included,
+							chain.get_pending_count()
+						)
+					}
+					Err(error) => println!("Failed to generate new block: {}", error)
+				}
+			}
+			Some(3) =>
+			{
+				println!(
+					"Current difficulty: {} {}",
+					chain.get_difficulty(),
+					chain.get_difficulty_unit()
+				);
+
+				let new_difficulty = prompt("Enter new difficulty: ");
+
+				let new_difficulty = match new_difficulty.parse()
+				{
+					Ok(new_difficulty) => new_difficulty,
+					Err(_) =>
+					{
+						println!("Invalid difficulty: it must be a whole number");
+						continue;
+					}
+				};
+
+				match chain.update_difficulty(new_difficulty)
+				{
+					Ok(_) => println!("Updated Difficulty"),
+					Err(error) => println!("Failed Update Difficulty: {}", error)
 				}
 			}
-			3 =>
+			Some(4) =>
 			{
-				println!("Current difficulty: {}", chain.get_difficulty());
+				println!("Current reward: {}", amount::format(chain.get_reward()));
 
-				let mut new_difficulty = String::new();
+				let new_reward = prompt("Enter new reward: ");
 
-				println!("Enter new difficulty: ");
-				receive_input_from_user(&mut new_difficulty);
+				let new_reward = match amount::parse(&new_reward)
+				{
+					Some(new_reward) => new_reward,
+					None =>
+					{
+						println!("Invalid reward: use up to {} decimals, e.g. 0.5", amount::DECIMALS);
+						continue;
+					}
+				};
 
-				let res = chain.update_difficulty(new_difficulty.trim().parse().unwrap());
+				let res = chain.update_reward(new_reward);
 
 				match res
 				{
-					true => println!("Updated Difficulty"),
-					false => println!("Failed Update Difficulty")
+					Ok(_) => println!("Updated reward"),
+					Err(error) => println!("Failed Update reward: {}", error)
 				}
 			}
-			4 =>
+			Some(5) =>
 			{
-				println!("Current reward: {}", chain.get_reward());
+				println!("Current difficulty unit: {}", chain.get_difficulty_unit());
 
-				let mut new_reward = String::new();
+				let new_unit = prompt("Enter new difficulty unit (1 = hex characters, 2 = bits): ");
 
-				print!("Enter new reward: ");
-				receive_input_from_user(&mut new_reward);
+				let difficulty_unit = match new_unit.as_str()
+				{
+					"1" => DifficultyUnit::HexChars,
+					"2" => DifficultyUnit::Bits,
+					_ =>
+					{
+						println!("Invalid difficulty unit");
+						continue;
+					}
+				};
 
-				let res = chain.update_reward(new_reward.trim().parse().unwrap());
+				let res = chain.update_difficulty_unit(difficulty_unit);
 
 				match res
 				{
-					true => println!("Updated reward"),
-					false => println!("Failed Update reward")
+					Ok(_) => println!("Updated difficulty unit"),
+					Err(error) => println!("Failed Update difficulty unit: {}", error)
+				}
+			}
+			Some(6) =>
+			{
+				for (name, address) in contacts.entries()
+				{
+					println!("{}: {}", name, address);
+				}
+
+				let name = prompt("Enter a name to add (leave empty to go back): ");
+
+				if name.is_empty()
+				{
+					continue;
+				}
+
+				let address = prompt("Enter address: ");
+
+				match contacts.add(name, address)
+				{
+					Ok(_) => println!("Contact saved"),
+					Err(error) => println!("Failed to save contact: {}", error)
+				}
+			}
+			Some(7) =>
+			{
+				let address = prompt("Enter address: ");
+
+				let address = contacts.resolve(&address);
+				let history = chain.history_of(&address);
+
+				if history.is_empty()
+				{
+					println!("No transactions found for {}", address);
+				}
+
+				for (block_index, transaction) in history
+				{
+					println!(
+						"Block {}: {} -> {}: {}",
+						block_index,
+						transaction.get_sender(),
+						transaction.get_receiver(),
+						amount::format(transaction.get_amount())
+					);
+				}
+
+				let balance = chain.balance_of(&address);
+				let sign = if balance < 0 { "-" } else { "" };
+
+				println!("Balance: {}{}", sign, amount::format(balance.unsigned_abs() as u64));
+			}
+			Some(8) =>
+			{
+				let interval = prompt("Enter seconds between blocks: ");
+
+				let interval = match interval.parse::<u64>()
+				{
+					Ok(seconds) => Duration::from_secs(seconds),
+					Err(_) =>
+					{
+						println!("Invalid interval");
+						continue;
+					}
+				};
+
+				println!("Auto-mining, press Enter to stop");
+
+				auto_mine(&mut chain, interval);
+
+				println!("Stopped auto-mining");
+			}
+			Some(9) =>
+			{
+				match chain.get_target_block_time()
+				{
+					Some(target_ms) =>
+					{
+						println!(
+							"Current target: {} ms per block, averaged over {} blocks",
+							target_ms,
+							chain.get_retarget_window()
+						)
+					}
+					None => println!("Retargeting is off")
+				}
+
+				let target = prompt("Enter target block time in ms (leave empty to turn off): ");
+
+				let target_ms = match target.as_str()
+				{
+					"" => None,
+					target =>
+					{
+						match target.parse::<i64>()
+						{
+							Ok(target_ms) => Some(target_ms),
+							Err(_) =>
+							{
+								println!("Invalid target block time");
+								continue;
+							}
+						}
+					}
+				};
+
+				if let Err(error) = chain.update_target_block_time(target_ms)
+				{
+					println!("Failed Update target block time: {}", error);
+					continue;
+				}
+
+				if target_ms.is_none()
+				{
+					println!("Turned retargeting off");
+					continue;
+				}
+
+				let window = prompt("Enter how many blocks to average over: ");
+
+				let window = match window.parse()
+				{
+					Ok(window) => window,
+					Err(_) =>
+					{
+						println!("Invalid retarget window: it must be a whole number");
+						continue;
+					}
+				};
+
+				match chain.update_retarget_window(window)
+				{
+					Ok(_) => println!("Updated retargeting"),
+					Err(error) => println!("Failed Update retarget window: {}", error)
+				}
+			}
+			Some(10) =>
+			{
+				let allow_empty_blocks = !chain.get_allow_empty_blocks();
+
+				match chain.update_allow_empty_blocks(allow_empty_blocks)
+				{
+					Ok(_) if allow_empty_blocks => println!("Blocks with only the reward can be mined"),
+					Ok(_) => println!("Blocks now need at least one pending transaction to be mined"),
+					Err(error) => println!("Failed Update empty blocks setting: {}", error)
+				}
+			}
+			Some(11) =>
+			{
+				let stats = chain.mining_stats();
+
+				for stat in stats
+				{
+					println!(
+						"Block {}: {} attempts at difficulty {} in {:.3}s",
+						stat.get_block(),
+						stat.get_attempts(),
+						stat.get_difficulty(),
+						stat.get_duration().as_secs_f64()
+					);
+				}
+
+				let attempts: u64 = stats.iter().map(|stat| stat.get_attempts()).sum();
+				let time: Duration = stats.iter().map(|stat| stat.get_duration()).sum();
+
+				// The genesis block is always mined here, so there is at least one entry.
+				println!(
+					"{} blocks mined, {} attempts on average, {:.3}s in total",
+					stats.len(),
+					attempts / stats.len() as u64,
+					time.as_secs_f64()
+				);
+			}
+			Some(12) =>
+			{
+				let path = prompt("Enter file to save to: ");
+
+				match chain.save_to_file(Path::new(&path))
+				{
+					Ok(_) => println!("Chain saved"),
+					Err(error) => println!("Failed to save chain: {}", error)
+				}
+			}
+			Some(13) =>
+			{
+				let path = prompt("Enter file to load from: ");
+
+				// The current chain is only replaced once the file is known to be good.
+				match Chain::load_from_file(Path::new(&path))
+				{
+					Ok(loaded) =>
+					{
+						chain = loaded;
+						println!("Loaded a chain of {} blocks", chain.get_block_count());
+					}
+					Err(error) => println!("Failed to load chain: {}", error)
+				}
+			}
+			Some(14) =>
+			{
+				println!(
+					"Current max transactions per block: {}",
+					chain.get_max_transactions_per_block()
+				);
+
+				let max_transactions = prompt("Enter new max transactions per block: ");
+
+				let max_transactions = match max_transactions.parse()
+				{
+					Ok(max_transactions) => max_transactions,
+					Err(_) =>
+					{
+						println!("Invalid max transactions per block: it must be a whole number");
+						continue;
+					}
+				};
+
+				match chain.update_max_transactions_per_block(max_transactions)
+				{
+					Ok(_) => println!("Updated max transactions per block"),
+					Err(error) => println!("Failed Update max transactions per block: {}", error)
+				}
+			}
+			Some(15) =>
+			{
+				println!("Current max mining attempts: {}", chain.get_max_mining_attempts());
+
+				let max_attempts = prompt("Enter new max mining attempts: ");
+
+				let max_attempts = match max_attempts.parse()
+				{
+					Ok(max_attempts) => max_attempts,
+					Err(_) =>
+					{
+						println!("Invalid max mining attempts: it must be a whole number");
+						continue;
+					}
+				};
+
+				match chain.update_max_mining_attempts(max_attempts)
+				{
+					Ok(_) => println!("Updated max mining attempts"),
+					Err(error) => println!("Failed Update max mining attempts: {}", error)
 				}
 			}
 			_ => println!("Invalid option please retry")
@@ -122,8 +444,90 @@ fn main()
 	}
 }
 
-fn receive_input_from_user(value: &mut String)
+// Mines a block, pending transactions included, every interval on a background thread until Enter is pressed. A block
+// that is already being mined is finished first, so stopping can take as long as one block.
+fn auto_mine(chain: &mut Chain, interval: Duration)
+{
+	// The miner only gets the chain through the lock, so nothing else can touch it while a block is being mined.
+	let chain = Mutex::new(chain);
+	let (stop_tx, stop_rx) = mpsc::channel::<()>();
+
+	let chain = &chain;
+
+	thread::scope(|scope| {
+		scope.spawn(move || {
+			loop
+			{
+				match chain.lock().unwrap().generate_new_block()
+				{
+					Ok(included) => println!("Block generated successfully with {} transactions", included),
+					Err(error) => println!("Failed to generate new block: {}", error)
+				}
+
+				// Waiting on the channel rather than sleeping lets a stop request cut the interval short.
+				if stop_rx.recv_timeout(interval) != Err(RecvTimeoutError::Timeout)
+				{
+					break;
+				}
+			}
+		});
+
+		// Stdin closing stops auto-mining as well, and the menu then exits on it.
+		read_input(&mut io::stdin().lock());
+
+		let _ = stop_tx.send(());
+	});
+}
+
+// Shows the message and returns the line typed in answer, trimmed. Once stdin is closed, e.g. with Ctrl-D, there is
+// nothing left to answer any prompt with, so the program exits.
+fn prompt(message: &str) -> String
 {
-	io::stdout().flush();
-	io::stdin().read_line(value);
+	print!("{}", message);
+	io::stdout().flush().expect("Failed to flush stdout");
+
+	read_input(&mut io::stdin().lock()).unwrap_or_else(|| {
+		println!();
+		println!("Input closed, exiting");
+		process::exit(0);
+	})
+}
+
+// The next line, trimmed, or None at the end of the input. A read that fails is treated the same way, since nothing
+// more can be read after it either.
+fn read_input(reader: &mut impl BufRead) -> Option<String>
+{
+	let mut line = String::new();
+
+	match reader.read_line(&mut line)
+	{
+		Ok(0) | Err(_) => None,
+		Ok(_) => Some(line.trim().to_string())
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn read_input_trims_lines_and_reports_the_end()
+	{
+		let mut input = io::Cursor::new("  3 \n\n   \r\nlast");
+
+		assert_eq!(read_input(&mut input), Some(String::from("3")));
+
+		// Empty lines are still answers, left for the caller to reject.
+		assert_eq!(read_input(&mut input), Some(String::new()));
+		assert_eq!(read_input(&mut input), Some(String::new()));
+
+		// The last line counts even without a newline, and only then is the input over.
+		assert_eq!(read_input(&mut input), Some(String::from("last")));
+		assert_eq!(read_input(&mut input), None);
+		assert_eq!(read_input(&mut input), None);
+
+		// Invalid UTF-8 can't be read as a line, so it ends the input too.
+		assert_eq!(read_input(&mut io::Cursor::new(vec![0xff, b'\n'])), None);
+	}
 }
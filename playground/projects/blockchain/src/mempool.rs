@@ -0,0 +1,96 @@
+use crate::blockchain::{ChainError, Transaction};
+use serde_derive::{Deserialize, Serialize};
+
+// Transactions waiting to be mined, oldest first. A transaction is nothing but its sender, receiver and amount, so an
+// exact copy of one already waiting can't be told apart from it: it is refused rather than paid out twice.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Mempool
+{
+	transactions: Vec<Transaction>
+}
+
+impl Mempool
+{
+	pub fn new() -> Mempool { Mempool::default() }
+
+	pub fn add(&mut self, transaction: Transaction) -> Result<(), ChainError>
+	{
+		if self.contains(&transaction)
+		{
+			return Err(ChainError::DuplicateTransaction);
+		}
+
+		self.transactions.push(transaction);
+		Ok(())
+	}
+
+	pub fn contains(&self, transaction: &Transaction) -> bool { self.transactions.contains(transaction) }
+
+	// Takes out up to 'max' of the oldest transactions.
+	pub fn drain(&mut self, max: usize) -> Vec<Transaction>
+	{
+		let count = self.transactions.len().min(max);
+
+		self.transactions.drain(..count).collect()
+	}
+
+	// Puts transactions taken with 'drain' back in front, as if they had never left.
+	pub fn requeue(&mut self, transactions: Vec<Transaction>) { self.transactions.splice(0..0, transactions); }
+
+	pub fn len(&self) -> usize { self.transactions.len() }
+
+	pub fn is_empty(&self) -> bool { self.transactions.is_empty() }
+
+	pub fn get_transactions(&self) -> &[Transaction] { &self.transactions }
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn transaction(amount: u64) -> Transaction { Transaction::new(String::from("alice"), String::from("bob"), amount) }
+
+	#[test]
+	fn exact_duplicates_are_only_kept_once()
+	{
+		let mut mempool = Mempool::new();
+
+		assert!(mempool.add(transaction(5)).is_ok());
+		assert!(matches!(
+			mempool.add(transaction(5)),
+			Err(ChainError::DuplicateTransaction)
+		));
+		assert!(mempool.add(transaction(6)).is_ok());
+		assert_eq!(mempool.len(), 2);
+
+		// Once mined, the same transaction can be queued again.
+		assert_eq!(mempool.drain(1), vec![transaction(5)]);
+		assert!(mempool.add(transaction(5)).is_ok());
+	}
+
+	#[test]
+	fn drain_takes_the_oldest_and_requeue_puts_them_back()
+	{
+		let mut mempool = Mempool::new();
+
+		for amount in 1..=3
+		{
+			mempool.add(transaction(amount)).unwrap();
+		}
+
+		let drained = mempool.drain(2);
+
+		assert_eq!(drained, vec![transaction(1), transaction(2)]);
+		assert_eq!(mempool.get_transactions(), [transaction(3)]);
+
+		mempool.requeue(drained);
+
+		assert_eq!(
+			mempool.get_transactions(),
+			[transaction(1), transaction(2), transaction(3)]
+		);
+		assert_eq!(mempool.drain(10).len(), 3);
+		assert!(mempool.is_empty());
+	}
+}
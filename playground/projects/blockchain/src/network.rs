@@ -0,0 +1,197 @@
+use crate::blockchain::{Block, Chain, ChainError};
+use std::{
+	collections::HashSet,
+	io::{self, ErrorKind, Read, Write},
+	net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+	sync::{Arc, Mutex, MutexGuard},
+	thread,
+	time::Duration
+};
+
+// A block is a few hundred bytes per transaction, so this leaves plenty of room while keeping a peer from making the
+// reader allocate whatever length it likes.
+pub const MAX_MESSAGE_LEN: usize = 4 * 1024 * 1024;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Each block goes over the wire as a big-endian u32 length followed by that many bytes of the block's JSON.
+pub fn write_block(writer: &mut impl Write, block: &Block) -> io::Result<()> { writer.write_all(&encode(block)?) }
+
+pub fn read_block(reader: &mut impl Read) -> io::Result<Block>
+{
+	let mut length = [0; 4];
+	reader.read_exact(&mut length)?;
+
+	let length = u32::from_be_bytes(length) as usize;
+
+	if length > MAX_MESSAGE_LEN
+	{
+		return Err(io::Error::new(
+			ErrorKind::InvalidData,
+			format!("Block of {} bytes exceeds {}", length, MAX_MESSAGE_LEN)
+		));
+	}
+
+	let mut json = vec![0; length];
+	reader.read_exact(&mut json)?;
+
+	serde_json::from_slice(&json).map_err(|error| io::Error::new(ErrorKind::InvalidData, error))
+}
+
+fn encode(block: &Block) -> io::Result<Vec<u8>>
+{
+	let json = serde_json::to_vec(block).map_err(|error| io::Error::new(ErrorKind::InvalidInput, error))?;
+
+	let length = u32::try_from(json.len())
+		.ok()
+		.filter(|length| *length as usize <= MAX_MESSAGE_LEN)
+		.ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "Block is too large to send"))?;
+
+	let mut message = Vec::with_capacity(4 + json.len());
+
+	message.extend_from_slice(&length.to_be_bytes());
+	message.extend_from_slice(&json);
+
+	Ok(message)
+}
+
+// A chain that shares the blocks it mines with its peers and takes in theirs. Only new blocks are passed around, there
+// is no syncing of what came before. Every block a node accepts is relayed to all of its peers, and each node remembers
+// the blocks it has already seen, so a block stops spreading once everyone has it instead of circling forever.
+pub struct Node
+{
+	chain:   Arc<Mutex<Chain>>,
+	peers:   Arc<Mutex<Vec<SocketAddr>>>,
+	// Header hashes of every block mined here or received.
+	seen:    Arc<Mutex<HashSet<String>>>,
+	address: SocketAddr
+}
+
+impl Node
+{
+	// Starts listening for blocks on the address, which may use port 0 to have one picked.
+	pub fn start(address: impl ToSocketAddrs, chain: Chain, peers: Vec<SocketAddr>) -> io::Result<Node>
+	{
+		let listener = TcpListener::bind(address)?;
+
+		let node = Node {
+			chain:   Arc::new(Mutex::new(chain)),
+			peers:   Arc::new(Mutex::new(peers)),
+			seen:    Arc::new(Mutex::new(HashSet::new())),
+			address: listener.local_addr()?
+		};
+
+		let (chain, peers, seen) = (node.chain.clone(), node.peers.clone(), node.seen.clone());
+
+		thread::spawn(move || {
+			for stream in listener.incoming().map_while(Result::ok)
+			{
+				let (chain, peers, seen) = (chain.clone(), peers.clone(), seen.clone());
+
+				thread::spawn(move || receive(stream, &chain, &peers, &seen));
+			}
+		});
+
+		Ok(node)
+	}
+
+	pub fn local_addr(&self) -> SocketAddr { self.address }
+
+	pub fn add_peer(&self, peer: SocketAddr) { self.peers.lock().unwrap().push(peer) }
+
+	// The chain is shared with the thread taking in blocks, so it is only reachable through the lock.
+	pub fn chain(&self) -> MutexGuard<'_, Chain> { self.chain.lock().unwrap() }
+
+	// Mines a block like 'Chain::generate_new_block' and sends it to every peer.
+	pub fn mine(&self) -> Result<usize, ChainError>
+	{
+		let (included, message) = {
+			let mut chain = self.chain();
+
+			let included = chain.generate_new_block()?;
+
+			let block = chain.last_block().expect("a block was just mined");
+
+			self.seen.lock().unwrap().insert(Chain::hash(block.get_header()));
+
+			(included, encode(block))
+		};
+
+		if let Ok(message) = message
+		{
+			broadcast(&self.peers, &message);
+		}
+
+		Ok(included)
+	}
+}
+
+// Takes in the blocks a peer sends until it disconnects or sends something unreadable.
+fn receive(mut stream: TcpStream, chain: &Mutex<Chain>, peers: &Mutex<Vec<SocketAddr>>, seen: &Mutex<HashSet<String>>)
+{
+	while let Ok(block) = read_block(&mut stream)
+	{
+		if !seen.lock().unwrap().insert(Chain::hash(block.get_header()))
+		{
+			continue;
+		}
+
+		// Encoded before 'add_block' takes the block, so it can be relayed once accepted.
+		let message = encode(&block);
+
+		if chain.lock().unwrap().add_block(block).is_ok()
+		{
+			if let Ok(message) = message
+			{
+				broadcast(peers, &message);
+			}
+		}
+	}
+}
+
+// A peer that can't be reached just misses the block. Peers are copied out first, so the lock isn't held while
+// connecting.
+fn broadcast(peers: &Mutex<Vec<SocketAddr>>, message: &[u8])
+{
+	let peers = peers.lock().unwrap().clone();
+
+	for peer in peers
+	{
+		if let Ok(mut stream) = TcpStream::connect_timeout(&peer, CONNECT_TIMEOUT)
+		{
+			let _ = stream.write_all(message);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use crate::blockchain::MIN_DIFFICULTY;
+	use std::io::Cursor;
+
+	#[test]
+	fn blocks_round_trip_over_the_wire()
+	{
+		let chain = Chain::new(String::from("miner"), MIN_DIFFICULTY).unwrap();
+		let block = chain.last_block().unwrap();
+
+		let mut wire = vec![];
+		write_block(&mut wire, block).unwrap();
+
+		let received = read_block(&mut Cursor::new(wire)).unwrap();
+
+		assert_eq!(Chain::hash(received.get_header()), Chain::hash(block.get_header()));
+	}
+
+	#[test]
+	fn oversized_lengths_are_rejected_before_reading()
+	{
+		let wire = u32::MAX.to_be_bytes().to_vec();
+
+		let error = read_block(&mut Cursor::new(wire)).unwrap_err();
+
+		assert_eq!(error.kind(), ErrorKind::InvalidData);
+	}
+}
@@ -0,0 +1,126 @@
+use blockchain::{
+	blockchain::{Block, BlockHeader, Chain, MIN_DIFFICULTY},
+	network::{self, Node}
+};
+use serde_json::Value;
+use std::{
+	net::TcpStream,
+	thread,
+	time::{Duration, Instant}
+};
+
+// Polls until the node's chain reaches the length, giving up after a few seconds.
+fn wait_for_length(node: &Node, length: usize) -> bool
+{
+	let deadline = Instant::now() + Duration::from_secs(5);
+
+	while Instant::now() < deadline
+	{
+		if node.chain().get_block_count() >= length
+		{
+			return true;
+		}
+
+		thread::sleep(Duration::from_millis(10));
+	}
+
+	false
+}
+
+// Changes the block's header the way 'change' says, then mines it again so its proof of work holds for the new header.
+fn remined(block: &Block, change: impl FnOnce(&mut Value)) -> Block
+{
+	let mut header = serde_json::to_value(block.get_header()).unwrap();
+	change(&mut header);
+
+	let mut header: BlockHeader = serde_json::from_value(header).unwrap();
+	Chain::proof_of_work(&mut header, u64::MAX).unwrap();
+
+	let mut block = serde_json::to_value(block).unwrap();
+	block["header"] = serde_json::to_value(&header).unwrap();
+
+	serde_json::from_value(block).unwrap()
+}
+
+#[test]
+fn mined_blocks_reach_peers_and_stop_spreading()
+{
+	// Both nodes start from the same genesis block, so a block mined on one follows on from the other's last block.
+	let genesis = Chain::new(String::from("first"), MIN_DIFFICULTY).unwrap();
+
+	let first = Node::start("127.0.0.1:0", genesis.clone(), vec![]).unwrap();
+	let second = Node::start("127.0.0.1:0", genesis, vec![first.local_addr()]).unwrap();
+
+	// Each node is the other's peer, so the block comes back to where it was mined and has to be recognised there.
+	first.add_peer(second.local_addr());
+
+	assert!(second.mine().is_ok());
+
+	assert!(wait_for_length(&first, 2));
+
+	let mined = Chain::hash(second.chain().last_block().unwrap().get_header());
+	let received = Chain::hash(first.chain().last_block().unwrap().get_header());
+
+	assert_eq!(received, mined);
+	assert!(first.chain().is_valid());
+
+	// Gives the relayed copy time to arrive back before checking it wasn't added again.
+	thread::sleep(Duration::from_millis(200));
+
+	assert_eq!(second.chain().get_block_count(), 2);
+	assert_eq!(first.chain().get_block_count(), 2);
+}
+
+#[test]
+fn forged_blocks_are_refused_and_mining_carries_on()
+{
+	let genesis = Chain::new(String::from("node"), MIN_DIFFICULTY).unwrap();
+	let node = Node::start("127.0.0.1:0", genesis.clone(), vec![]).unwrap();
+
+	let mut elsewhere = genesis;
+	elsewhere.generate_new_block().unwrap();
+	let block = elsewhere.last_block().unwrap().clone();
+
+	let unlinked = remined(&block, |header| {
+		header["prev_hash"] = Value::from(Chain::hash(&"elsewhere"))
+	});
+
+	// Any nonce other than the one that was found most likely misses the difficulty, but it is checked to be sure.
+	let mut unmined = serde_json::to_value(&block).unwrap();
+	let zeros = "0".repeat(MIN_DIFFICULTY as usize);
+
+	loop
+	{
+		let nonce = unmined["header"]["nonce"].as_u64().unwrap();
+		unmined["header"]["nonce"] = Value::from(nonce + 1);
+
+		let header: BlockHeader = serde_json::from_value(unmined["header"].clone()).unwrap();
+
+		if !Chain::hash(&header).starts_with(&zeros)
+		{
+			break;
+		}
+	}
+
+	let unmined: Block = serde_json::from_value(unmined).unwrap();
+
+	// Sent over one connection, so the genuine block only arrives once the forged ones have been dealt with.
+	let mut stream = TcpStream::connect(node.local_addr()).unwrap();
+
+	for block in [&unlinked, &unmined, &block]
+	{
+		network::write_block(&mut stream, block).unwrap();
+	}
+
+	assert!(wait_for_length(&node, 2));
+
+	assert_eq!(
+		Chain::hash(node.chain().last_block().unwrap().get_header()),
+		Chain::hash(block.get_header())
+	);
+	assert!(node.chain().is_valid());
+
+	assert!(node.mine().is_ok());
+	assert_eq!(node.chain().get_block_count(), 3);
+	assert!(node.chain().is_valid());
+}
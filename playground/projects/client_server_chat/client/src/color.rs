@@ -0,0 +1,64 @@
+// ANSI foreground colors that nicknames are spread across. Red is left out so nobody's name reads like an error.
+const PALETTE: [u8; 10] = [32, 33, 34, 35, 36, 92, 93, 94, 95, 96];
+
+// FNV-1a. The standard library's hasher is free to change between releases, and a nickname has to keep its color
+// from one session to the next.
+fn hash(nickname: &str) -> u64
+{
+	let mut hash: u64 = 0xcbf29ce484222325;
+
+	for byte in nickname.bytes()
+	{
+		hash ^= byte as u64;
+		hash = hash.wrapping_mul(0x100000001b3);
+	}
+
+	hash
+}
+
+pub fn color_of(nickname: &str) -> u8 { PALETTE[(hash(nickname) % PALETTE.len() as u64) as usize] }
+
+// Wraps the nickname in its color, or leaves it plain when escape codes would end up as garbage, e.g. in a file.
+pub fn paint(nickname: &str, enabled: bool) -> String
+{
+	match enabled
+	{
+		true => format!("\x1b[{}m{}\x1b[0m", color_of(nickname), nickname),
+		false => nickname.to_string()
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn a_nickname_always_gets_the_same_color()
+	{
+		// Pinning the hash itself catches any change that would recolor everyone between sessions.
+		assert_eq!(hash(""), 0xcbf29ce484222325);
+		assert_eq!(hash("a"), 0xaf63dc4c8601ec8c);
+
+		assert_eq!(color_of("alice"), color_of("alice"));
+		assert_eq!(paint("alice", true), format!("\x1b[{}malice\x1b[0m", color_of("alice")));
+	}
+
+	#[test]
+	fn nicknames_are_spread_across_the_palette()
+	{
+		let names = ["alice", "bob", "carol", "dave", "erin", "frank", "grace", "heidi"];
+		let mut colors: Vec<u8> = names.iter().map(|name| color_of(name)).collect();
+
+		colors.sort();
+		colors.dedup();
+
+		assert!(colors.len() > 1);
+	}
+
+	#[test]
+	fn disabled_output_is_plain()
+	{
+		assert_eq!(paint("bob", false), "bob");
+	}
+}
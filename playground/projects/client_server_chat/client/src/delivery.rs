@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+// Chat messages that were sent but haven't been acknowledged yet, by the id they went out with. Acks can arrive in any
+// order, and one for a message that is no longer outstanding is ignored.
+pub struct Deliveries
+{
+	next_id:     u32,
+	outstanding: HashMap<u32, String>
+}
+
+impl Deliveries
+{
+	pub fn new() -> Deliveries
+	{
+		Deliveries {
+			next_id:     0,
+			outstanding: HashMap::new()
+		}
+	}
+
+	// Picks the id for a new message and remembers the text until it is delivered.
+	pub fn assign(&mut self, text: &str) -> u32
+	{
+		let id = self.next_id;
+
+		// By the time the ids wrap around, the early ones are long since acknowledged.
+		self.next_id = self.next_id.wrapping_add(1);
+		self.outstanding.insert(id, text.to_string());
+
+		id
+	}
+
+	// Marks the message as delivered and returns its text, or None if the id isn't outstanding.
+	pub fn delivered(&mut self, id: u32) -> Option<String> { self.outstanding.remove(&id) }
+
+	pub fn outstanding(&self) -> usize { self.outstanding.len() }
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn acks_are_matched_by_id_in_any_order()
+	{
+		let mut deliveries = Deliveries::new();
+
+		let first = deliveries.assign("first");
+		let second = deliveries.assign("second");
+		let third = deliveries.assign("third");
+
+		assert_eq!(deliveries.outstanding(), 3);
+
+		assert_eq!(deliveries.delivered(third), Some(String::from("third")));
+		assert_eq!(deliveries.delivered(first), Some(String::from("first")));
+
+		// A repeated ack, or one for an id that was never sent, changes nothing.
+		assert_eq!(deliveries.delivered(first), None);
+		assert_eq!(deliveries.delivered(third + 1), None);
+
+		assert_eq!(deliveries.outstanding(), 1);
+		assert_eq!(deliveries.delivered(second), Some(String::from("second")));
+		assert_eq!(deliveries.outstanding(), 0);
+	}
+}
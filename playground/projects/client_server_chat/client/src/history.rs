@@ -0,0 +1,78 @@
+use chrono::{DateTime, Local};
+use std::{
+	collections::VecDeque,
+	fs::File,
+	io::{self, BufWriter, Write},
+	path::Path
+};
+
+const MAX_HISTORY: usize = 500;
+
+pub enum Direction
+{
+	Sent,
+	Received
+}
+
+struct Entry
+{
+	timestamp: DateTime<Local>,
+	direction: Direction,
+	text:      String
+}
+
+// Keeps the most recent messages of the session, dropping the oldest once the limit is reached.
+pub struct History
+{
+	entries: VecDeque<Entry>
+}
+
+impl History
+{
+	pub fn new() -> History
+	{
+		History {
+			entries: VecDeque::new()
+		}
+	}
+
+	pub fn push(&mut self, direction: Direction, text: &str)
+	{
+		if self.entries.len() == MAX_HISTORY
+		{
+			self.entries.pop_front();
+		}
+
+		self.entries.push_back(Entry {
+			timestamp: Local::now(),
+			direction,
+			text: text.to_string()
+		});
+	}
+
+	pub fn save(&self, path: &Path) -> io::Result<usize>
+	{
+		let mut writer = BufWriter::new(File::create(path)?);
+
+		for entry in &self.entries
+		{
+			let direction = match entry.direction
+			{
+				Direction::Sent => ">>",
+				Direction::Received => "<<"
+			};
+
+			writeln!(
+				writer,
+				"[{}] {} {}",
+				entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+				direction,
+				entry.text
+			)?;
+		}
+
+		writer.flush()?;
+
+		Ok(self.entries.len())
+	}
+}
@@ -1,83 +1,409 @@
+mod color;
+mod delivery;
+mod history;
+mod reconnect;
+
+use delivery::Deliveries;
+use history::{Direction, History};
+use protocol::{Frame, MAX_CHAT_LEN};
+use reconnect::Backoff;
 use std::{
-	io::{self, ErrorKind, Read, Write},
-	net::TcpStream,
-	sync::mpsc::{self, TryRecvError},
-	thread
+	io::{self, IsTerminal, Write},
+	net::{Shutdown, TcpStream},
+	path::Path,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		mpsc::{self, Receiver, RecvTimeoutError, Sender},
+		Arc, Mutex
+	},
+	thread,
+	time::Duration
 };
 
 const LOCAL_PORT: &str = "127.0.0.1:6000";
-const MSG_SIZE: usize = 32;
 
-fn sleep() { thread::sleep(::std::time::Duration::from_millis(100)); }
+const MAX_RECONNECT_ATTEMPTS: u32 = 10;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+// How often the writer looks up from the outgoing queue to check whether the reader has lost the connection.
+const TICK: Duration = Duration::from_millis(200);
+
+// State that outlives any one connection, shared between the connection threads and the input loop.
+#[derive(Clone)]
+struct Session
+{
+	// Kept up to date by the reader as the server confirms name changes, so a reconnect asks for the current one.
+	nickname:   Arc<Mutex<String>>,
+	// Recorded by the connection and saved by the input loop.
+	history:    Arc<Mutex<History>>,
+	// Messages are given their ids as they are typed, and the reader marks them delivered as the acks come back.
+	deliveries: Arc<Mutex<Deliveries>>
+}
 
 fn main()
 {
 	let mut client = TcpStream::connect(LOCAL_PORT).expect("Stream failed to connect");
 
-	client.set_nonblocking(true).expect("Failed to initiate non-blocking");
+	// Colors only make sense on a terminal, where the escape codes are interpreted rather than shown.
+	let colored = io::stdout().is_terminal();
+
+	let nickname = match choose_nickname(&mut client, colored)
+	{
+		Some(nickname) => nickname,
+		None =>
+		{
+			println!("Good bye!");
+			return;
+		}
+	};
+
+	let session = Session {
+		nickname:   Arc::new(Mutex::new(nickname)),
+		history:    Arc::new(Mutex::new(History::new())),
+		deliveries: Arc::new(Mutex::new(Deliveries::new()))
+	};
 
-	let (tx, rx) = mpsc::channel::<String>();
+	let connection_session = session.clone();
+
+	// Everything written to the socket goes through this channel, so chat messages from the input loop and pongs from
+	// the reader never interleave mid-frame. Messages typed while reconnecting wait in it until there is a connection
+	// to send them on.
+	let (tx, rx) = mpsc::channel::<Frame>();
+	let pong_tx = tx.clone();
+
+	// Cleared while the client is reconnecting, so the input loop can tell the user their messages are being held.
+	let connected = Arc::new(AtomicBool::new(true));
+	let connection_connected = connected.clone();
+
+	// Set once reconnecting has been given up on, so the input loop stops instead of queueing messages nobody will
+	// send.
+	let gave_up = Arc::new(AtomicBool::new(false));
+	let connection_gave_up = gave_up.clone();
 
 	thread::spawn(move || {
+		let mut stream = client;
+		let mut unsent = None;
+
 		loop
 		{
-			let mut buffer = vec![0; MSG_SIZE];
+			unsent = serve(stream, &rx, &pong_tx, &connection_session, colored, unsent);
+
+			println!("Connection with server was severed");
+			connection_connected.store(false, Ordering::SeqCst);
 
-			match client.read_exact(&mut buffer)
+			match reconnect()
 			{
-				Ok(_) =>
+				Some(mut reconnected) =>
 				{
-					let msg = buffer.into_iter().take_while(|&x| x != 0).collect::<Vec<_>>();
+					// The server has forgotten the old connection's name. If the request can't even be written, serving
+					// fails straight away and the next round of reconnecting starts.
+					let _ = Frame::Nick(connection_session.nickname.lock().unwrap().clone()).write_to(&mut reconnected);
 
-					println!("Message received {:?}", msg);
+					stream = reconnected;
+					connection_connected.store(true, Ordering::SeqCst);
 				}
+				None =>
+				{
+					println!("Gave up reconnecting, unsent messages were dropped. Press Enter to exit");
+					connection_gave_up.store(true, Ordering::SeqCst);
+					break;
+				}
+			}
+		}
+	});
 
-				Err(ref err) if err.kind() == ErrorKind::WouldBlock => (),
+	println!("Write a Message:");
 
-				Err(_) =>
+	loop
+	{
+		let mut buffer = String::new();
+
+		// Once stdin is closed there is nothing more to read, so every further read would return an empty message.
+		if io::stdin().read_line(&mut buffer).expect("Reading from stdin failed") == 0
+		{
+			break;
+		}
+
+		let msg = buffer.trim().to_string();
+
+		// Only the exact command counts, so a message like "/saved it!" is still sent as chat.
+		let (command, path) = msg.split_once(' ').unwrap_or((&msg, ""));
+
+		if command == "/save"
+		{
+			save_history(&session.history.lock().unwrap(), path.trim());
+			continue;
+		}
+
+		if command == "/nick"
+		{
+			match path.trim()
+			{
+				"" => println!("Usage: /nick NAME"),
+				// The reader reports whether the server accepted it.
+				name =>
 				{
-					println!("Connection with server was severed");
-					break;
+					let _ = tx.send(Frame::Nick(name.to_string()));
 				}
 			}
 
-			match rx.try_recv()
+			continue;
+		}
+
+		// The writer would refuse the frame anyway, so catch it here rather than treat it as a lost connection.
+		if msg.len() > MAX_CHAT_LEN
+		{
+			println!("Messages are limited to {} bytes", MAX_CHAT_LEN);
+			continue;
+		}
+
+		if msg == ":quit" || gave_up.load(Ordering::SeqCst)
+		{
+			break;
+		}
+
+		if !connected.load(Ordering::SeqCst)
+		{
+			println!("Not connected, the message will be sent once the client reconnects");
+		}
+
+		let id = session.deliveries.lock().unwrap().assign(&msg);
+
+		if tx.send(Frame::Chat { id, text: msg }).is_err()
+		{
+			break;
+		}
+	}
+
+	let unconfirmed = session.deliveries.lock().unwrap().outstanding();
+
+	if unconfirmed > 0
+	{
+		println!("{} sent messages were never confirmed as delivered", unconfirmed);
+	}
+
+	println!("Good bye!");
+}
+
+// Relays frames over one connection until it drops. A chat message that was being written when it did is returned, so
+// it can go out first on the next connection instead of being lost.
+fn serve(
+	stream: TcpStream,
+	rx: &Receiver<Frame>,
+	pong_tx: &Sender<Frame>,
+	session: &Session,
+	colored: bool,
+	mut unsent: Option<Frame>
+) -> Option<Frame>
+{
+	let mut reader = match stream.try_clone()
+	{
+		Ok(reader) => reader,
+		Err(_) => return unsent
+	};
+
+	let mut writer = stream;
+
+	// Cleared by the reader once the server goes away, which is how the writer learns about it while nothing is queued.
+	let alive = Arc::new(AtomicBool::new(true));
+	let reader_alive = alive.clone();
+	let pong_tx = pong_tx.clone();
+	let reader_session = session.clone();
+
+	// Reading blocks in its own thread, so the writer doesn't have to poll the socket while waiting for messages.
+	thread::spawn(move || {
+		loop
+		{
+			// Private messages are marked after the name, which keeps the color the sender always has.
+			let (sender, text, marker) = match Frame::read_from(&mut reader)
 			{
-				Ok(msg) =>
+				Ok(Frame::Broadcast { sender, text }) => (sender, text, ""),
+				Ok(Frame::Direct { sender, text }) => (sender, text, " (private)"),
+				Ok(Frame::Ping) =>
+				{
+					// Answering lets the server know this connection is still alive.
+					let _ = pong_tx.send(Frame::Pong);
+					continue;
+				}
+				Ok(Frame::Nick(confirmed)) =>
 				{
-					let mut buffer = msg.clone().into_bytes();
+					let mut nickname = reader_session.nickname.lock().unwrap();
 
-					buffer.resize(MSG_SIZE, 0);
+					// Rejoining after a reconnect confirms the same name again, which isn't news.
+					if *nickname != confirmed
+					{
+						println!("You are now known as {}", confirmed);
+						*nickname = confirmed;
+					}
 
-					client.write_all(&buffer).expect("Writing to socket failed");
+					continue;
+				}
+				Ok(Frame::Notice(text)) =>
+				{
+					println!("{}", text);
+					continue;
+				}
+				Ok(Frame::Ack(id)) =>
+				{
+					if let Some(msg) = reader_session.deliveries.lock().unwrap().delivered(id)
+					{
+						println!("Message delivered {:?}", msg);
+					}
 
-					println!("Message sent {:?}", msg);
+					continue;
 				}
+				Ok(Frame::Pong | Frame::Chat { .. }) => continue,
+				Err(_) =>
+				{
+					reader_alive.store(false, Ordering::SeqCst);
+					break;
+				}
+			};
 
-				Err(TryRecvError::Empty) => (),
+			println!("{}{}: {}", color::paint(&sender, colored), marker, text);
 
-				Err(TryRecvError::Disconnected) => break
+			reader_session
+				.history
+				.lock()
+				.unwrap()
+				.push(Direction::Received, &format!("{}{}: {}", sender, marker, text));
+		}
+	});
+
+	while alive.load(Ordering::SeqCst)
+	{
+		let frame = match unsent.take()
+		{
+			Some(frame) => frame,
+			None =>
+			{
+				match rx.recv_timeout(TICK)
+				{
+					Ok(frame) => frame,
+					Err(RecvTimeoutError::Timeout) => continue,
+					// The input loop has finished, so there is nothing left to send.
+					Err(RecvTimeoutError::Disconnected) => return None
+				}
 			}
+		};
+
+		if frame.write_to(&mut writer).is_err()
+		{
+			// Shutting the socket down also wakes the reader, which is still blocked on it.
+			let _ = writer.shutdown(Shutdown::Both);
+
+			// A pong only answers the old connection's ping, so it isn't worth sending again.
+			return Some(frame).filter(|frame| matches!(frame, Frame::Chat { .. }));
 		}
 
-		sleep();
-	});
+		if let Frame::Chat { text: msg, .. } = frame
+		{
+			println!("Message sent {:?}", msg);
 
-	println!("Write a Message:");
+			session.history.lock().unwrap().push(Direction::Sent, &msg);
+		}
+	}
+
+	unsent
+}
 
+// Asks for a nickname until the server accepts one, which is what lets the client join the chat. Returns None if stdin
+// closes or the connection drops first.
+fn choose_nickname(stream: &mut TcpStream, colored: bool) -> Option<String>
+{
 	loop
 	{
+		print!("Choose a nickname: ");
+		io::stdout().flush().expect("Failed to flush stdout");
+
 		let mut buffer = String::new();
 
-		io::stdin().read_line(&mut buffer).expect("Reading from stdin failed");
+		if io::stdin().read_line(&mut buffer).expect("Reading from stdin failed") == 0
+		{
+			return None;
+		}
 
-		let msg = buffer.trim().to_string();
+		let requested = buffer.trim();
 
-		if msg == ":quit" || tx.send(msg).is_err()
+		if requested.is_empty()
 		{
-			break;
+			continue;
+		}
+
+		if Frame::Nick(requested.to_string()).write_to(stream).is_err()
+		{
+			println!("Connection with server was severed");
+			return None;
+		}
+
+		// Other traffic keeps flowing while the server decides, so it is handled here until the answer shows up.
+		loop
+		{
+			match Frame::read_from(stream)
+			{
+				Ok(Frame::Nick(nickname)) =>
+				{
+					println!("You are now known as {}", nickname);
+					return Some(nickname);
+				}
+				Ok(Frame::Notice(text)) =>
+				{
+					println!("{}", text);
+					break;
+				}
+				Ok(Frame::Ping) =>
+				{
+					if Frame::Pong.write_to(stream).is_err()
+					{
+						println!("Connection with server was severed");
+						return None;
+					}
+				}
+				Ok(Frame::Broadcast { sender, text }) => println!("{}: {}", color::paint(&sender, colored), text),
+				// Private messages are addressed by nickname, so none can arrive before there is one.
+				Ok(Frame::Pong | Frame::Chat { .. } | Frame::Ack(_) | Frame::Direct { .. }) => (),
+				Err(_) =>
+				{
+					println!("Connection with server was severed");
+					return None;
+				}
+			}
 		}
 	}
+}
 
-	println!("Good bye!");
+// Tries to connect again, waiting longer after each failure, and reports every attempt on the way.
+fn reconnect() -> Option<TcpStream>
+{
+	let mut backoff = Backoff::new(MAX_RECONNECT_ATTEMPTS, INITIAL_BACKOFF, MAX_BACKOFF);
+
+	while let Some(delay) = backoff.next_attempt()
+	{
+		println!("{}", backoff.status(delay));
+		thread::sleep(delay);
+
+		if let Ok(stream) = TcpStream::connect(LOCAL_PORT)
+		{
+			println!("{}", backoff.reconnected());
+			return Some(stream);
+		}
+	}
+
+	None
+}
+
+fn save_history(history: &History, path: &str)
+{
+	if path.is_empty()
+	{
+		println!("Usage: /save PATH");
+		return;
+	}
+
+	match history.save(Path::new(path))
+	{
+		Ok(count) => println!("Saved {} messages to {}", count, path),
+		Err(error) => println!("Failed to save history to {}: {}", path, error)
+	}
 }
@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+// Schedules reconnection attempts, doubling the wait before each one up to a ceiling, and gives up after a fixed number
+// of them.
+pub struct Backoff
+{
+	attempts:      u32,
+	max_attempts:  u32,
+	initial_delay: Duration,
+	max_delay:     Duration
+}
+
+impl Backoff
+{
+	pub fn new(max_attempts: u32, initial_delay: Duration, max_delay: Duration) -> Backoff
+	{
+		Backoff {
+			attempts: 0,
+			max_attempts,
+			initial_delay,
+			max_delay
+		}
+	}
+
+	// Counts another attempt and returns how long to wait before making it, or None once every attempt is used up.
+	pub fn next_attempt(&mut self) -> Option<Duration>
+	{
+		if self.attempts == self.max_attempts
+		{
+			return None;
+		}
+
+		self.attempts += 1;
+
+		let factor = 2u32.saturating_pow(self.attempts - 1);
+
+		Some(self.initial_delay.saturating_mul(factor).min(self.max_delay))
+	}
+
+	pub fn status(&self, delay: Duration) -> String
+	{
+		format!(
+			"reconnecting (attempt {}/{}, next try in {}s)",
+			self.attempts,
+			self.max_attempts,
+			delay.as_secs()
+		)
+	}
+
+	pub fn reconnected(&self) -> String
+	{
+		match self.attempts
+		{
+			1 => String::from("reconnected after 1 attempt"),
+			attempts => format!("reconnected after {} attempts", attempts)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn delays_double_up_to_the_ceiling_and_then_run_out()
+	{
+		let mut backoff = Backoff::new(6, Duration::from_secs(1), Duration::from_secs(10));
+
+		let delays: Vec<u64> = std::iter::from_fn(|| backoff.next_attempt())
+			.map(|delay| delay.as_secs())
+			.collect();
+
+		assert_eq!(delays, vec![1, 2, 4, 8, 10, 10]);
+		assert_eq!(backoff.attempts, 6);
+		assert_eq!(backoff.next_attempt(), None);
+	}
+
+	#[test]
+	fn status_lines_report_the_attempts()
+	{
+		let mut backoff = Backoff::new(10, Duration::from_secs(1), Duration::from_secs(30));
+
+		backoff.next_attempt();
+		assert_eq!(backoff.reconnected(), "reconnected after 1 attempt");
+
+		backoff.next_attempt();
+		let delay = backoff.next_attempt().unwrap();
+
+		assert_eq!(backoff.status(delay), "reconnecting (attempt 3/10, next try in 4s)");
+		assert_eq!(backoff.reconnected(), "reconnected after 3 attempts");
+	}
+}
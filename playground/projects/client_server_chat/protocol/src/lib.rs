@@ -0,0 +1,448 @@
+use std::{
+	error::Error,
+	fmt,
+	io::{self, ErrorKind, Read, Write}
+};
+
+const CHAT: u8 = 0;
+const PING: u8 = 1;
+const PONG: u8 = 2;
+const BROADCAST: u8 = 3;
+const NICK: u8 = 4;
+const NOTICE: u8 = 5;
+const ACK: u8 = 6;
+const DIRECT: u8 = 7;
+
+// The length comes straight off the wire, so without a cap a peer could make the reader allocate up to 4 GiB. Readers
+// may set a lower cap of their own with 'read_from_limited'.
+pub const MAX_FRAME_LEN: usize = 64 * 1024;
+
+// A chat payload starts with its message id, which leaves the rest of the frame for the text.
+pub const MAX_CHAT_LEN: usize = MAX_FRAME_LEN - 4;
+
+// Everything on the wire is a frame: a one byte kind, a big-endian u32 payload length, then the payload. Chat payloads
+// are UTF-8 up to MAX_FRAME_LEN bytes, while control frames (ping/pong) have an empty payload, so both can share the
+// connection. Clients send Chat, and the server relays it to everyone as a Broadcast naming who sent it. A client asks
+// for a nickname with Nick, which the server sends back once the name is theirs, or answers with a Notice saying why
+// not. Each Chat carries an id the client picked, which the server sends back in an Ack once the message has been
+// relayed. A chat message addressed to a single nickname reaches only that client, as a Direct.
+#[derive(Debug, PartialEq)]
+pub enum Frame
+{
+	Chat
+	{
+		id:   u32,
+		text: String
+	},
+	Broadcast
+	{
+		sender: String,
+		text:   String
+	},
+	Ping,
+	Pong,
+	Nick(String),
+	// Server text meant for a single client rather than the whole chat.
+	Notice(String),
+	Ack(u32),
+	// A private message, laid out like a Broadcast.
+	Direct
+	{
+		sender: String,
+		text:   String
+	}
+}
+
+// The error 'read_from' gives, wrapped in an InvalidData io::Error, when a frame's declared length is over the cap.
+// It is returned as soon as the header is read, so none of the payload is read or allocated.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameTooLarge
+{
+	pub length: usize,
+	pub limit:  usize
+}
+
+impl fmt::Display for FrameTooLarge
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		write!(f, "Frame payload of {} bytes exceeds {}", self.length, self.limit)
+	}
+}
+
+impl Error for FrameTooLarge {}
+
+impl FrameTooLarge
+{
+	// Picks a FrameTooLarge out of an error returned by 'read_from', telling it apart from a connection problem.
+	pub fn from_error(error: &io::Error) -> Option<&FrameTooLarge> { error.get_ref()?.downcast_ref() }
+}
+
+// The error 'read_from' gives, wrapped in an InvalidData io::Error, when a frame arrived whole but its payload can't be
+// decoded, such as text that isn't valid UTF-8 or an unknown kind. The frame has been read to its end, so unlike any
+// other error, the next frame can still be read from the same connection.
+#[derive(Debug)]
+pub struct UndecodableFrame
+{
+	pub reason: io::Error
+}
+
+impl fmt::Display for UndecodableFrame
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "Undecodable frame: {}", self.reason) }
+}
+
+impl Error for UndecodableFrame
+{
+	fn source(&self) -> Option<&(dyn Error + 'static)> { Some(&self.reason) }
+}
+
+impl UndecodableFrame
+{
+	// Picks an UndecodableFrame out of an error returned by 'read_from', the same way as 'FrameTooLarge::from_error'.
+	pub fn from_error(error: &io::Error) -> Option<&UndecodableFrame> { error.get_ref()?.downcast_ref() }
+}
+
+impl Frame
+{
+	pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> { writer.write_all(&self.encode()?) }
+
+	// The frame exactly as it goes on the wire, so it can be checked once and then sent to any number of peers.
+	pub fn encode(&self) -> io::Result<Vec<u8>>
+	{
+		let (kind, payload) = match self
+		{
+			Frame::Chat { id, text } =>
+			{
+				let mut payload = Vec::with_capacity(4 + text.len());
+
+				payload.extend_from_slice(&id.to_be_bytes());
+				payload.extend_from_slice(text.as_bytes());
+
+				(CHAT, payload)
+			}
+			Frame::Broadcast { sender, text } => (BROADCAST, encode_named(sender, text)?),
+			Frame::Direct { sender, text } => (DIRECT, encode_named(sender, text)?),
+			Frame::Ping => (PING, vec![]),
+			Frame::Pong => (PONG, vec![]),
+			Frame::Nick(nickname) => (NICK, nickname.as_bytes().to_vec()),
+			Frame::Notice(text) => (NOTICE, text.as_bytes().to_vec()),
+			Frame::Ack(id) => (ACK, id.to_be_bytes().to_vec())
+		};
+
+		if payload.len() > MAX_FRAME_LEN
+		{
+			return Err(io::Error::new(
+				ErrorKind::InvalidInput,
+				format!("Frame payload of {} bytes exceeds {}", payload.len(), MAX_FRAME_LEN)
+			));
+		}
+
+		let mut buffer = Vec::with_capacity(5 + payload.len());
+
+		buffer.push(kind);
+		buffer.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+		buffer.extend_from_slice(&payload);
+
+		Ok(buffer)
+	}
+
+	pub fn read_from(reader: &mut impl Read) -> io::Result<Frame> { Frame::read_from_limited(reader, MAX_FRAME_LEN) }
+
+	// Like 'read_from', but refuses any payload longer than 'limit' bytes.
+	pub fn read_from_limited(reader: &mut impl Read, limit: usize) -> io::Result<Frame>
+	{
+		let mut header = [0; 5];
+		reader.read_exact(&mut header)?;
+
+		let length = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+
+		if length > limit
+		{
+			return Err(io::Error::new(ErrorKind::InvalidData, FrameTooLarge { length, limit }));
+		}
+
+		let mut payload = vec![0; length];
+		reader.read_exact(&mut payload)?;
+
+		Frame::decode(header[0], payload)
+			.map_err(|reason| io::Error::new(ErrorKind::InvalidData, UndecodableFrame { reason }))
+	}
+
+	fn decode(kind: u8, mut payload: Vec<u8>) -> io::Result<Frame>
+	{
+		match kind
+		{
+			CHAT =>
+			{
+				if payload.len() < 4
+				{
+					return Err(io::Error::new(ErrorKind::InvalidData, "Chat is missing its id"));
+				}
+
+				let text = payload.split_off(4);
+
+				Ok(Frame::Chat {
+					id:   u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]),
+					text: utf8(text)?
+				})
+			}
+			BROADCAST =>
+			{
+				let (sender, text) = decode_named(payload)?;
+
+				Ok(Frame::Broadcast { sender, text })
+			}
+			DIRECT =>
+			{
+				let (sender, text) = decode_named(payload)?;
+
+				Ok(Frame::Direct { sender, text })
+			}
+			PING => Ok(Frame::Ping),
+			PONG => Ok(Frame::Pong),
+			NICK => Ok(Frame::Nick(utf8(payload)?)),
+			NOTICE => Ok(Frame::Notice(utf8(payload)?)),
+			ACK =>
+			{
+				let id: [u8; 4] = payload
+					.try_into()
+					.map_err(|_| io::Error::new(ErrorKind::InvalidData, "Ack must be a 4 byte id"))?;
+
+				Ok(Frame::Ack(u32::from_be_bytes(id)))
+			}
+			kind =>
+			{
+				Err(io::Error::new(
+					ErrorKind::InvalidData,
+					format!("Unknown frame kind {}", kind)
+				))
+			}
+		}
+	}
+}
+
+// The payload of a message that names its sender: the sender's length goes first so the reader knows where the text
+// starts.
+fn encode_named(sender: &str, text: &str) -> io::Result<Vec<u8>>
+{
+	let sender_len =
+		u16::try_from(sender.len()).map_err(|_| io::Error::new(ErrorKind::InvalidInput, "Sender name is too long"))?;
+
+	let mut payload = Vec::with_capacity(2 + sender.len() + text.len());
+
+	payload.extend_from_slice(&sender_len.to_be_bytes());
+	payload.extend_from_slice(sender.as_bytes());
+	payload.extend_from_slice(text.as_bytes());
+
+	Ok(payload)
+}
+
+fn decode_named(mut payload: Vec<u8>) -> io::Result<(String, String)>
+{
+	if payload.len() < 2
+	{
+		return Err(io::Error::new(ErrorKind::InvalidData, "Message is missing its sender"));
+	}
+
+	let sender_len = u16::from_be_bytes([payload[0], payload[1]]) as usize;
+
+	if payload.len() < 2 + sender_len
+	{
+		return Err(io::Error::new(
+			ErrorKind::InvalidData,
+			"Message sender overruns the frame"
+		));
+	}
+
+	let text = payload.split_off(2 + sender_len);
+	let sender = payload.split_off(2);
+
+	Ok((utf8(sender)?, utf8(text)?))
+}
+
+fn utf8(bytes: Vec<u8>) -> io::Result<String>
+{
+	String::from_utf8(bytes).map_err(|error| io::Error::new(ErrorKind::InvalidData, error))
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::io::Cursor;
+
+	#[test]
+	fn frames_round_trip_intact()
+	{
+		let long = "a message that is much longer than the old 32 byte buffer ever allowed";
+		let frames = vec![
+			Frame::Chat {
+				id:   0,
+				text: long.to_string()
+			},
+			Frame::Ping,
+			Frame::Chat {
+				id:   u32::MAX,
+				text: "héllo 👋".to_string()
+			},
+			Frame::Broadcast {
+				sender: "127.0.0.1:5000".to_string(),
+				text:   "hi: all".to_string()
+			},
+			Frame::Broadcast {
+				sender: String::new(),
+				text:   String::new()
+			},
+			Frame::Pong,
+			Frame::Nick("alice".to_string()),
+			Frame::Notice("nickname taken: alice".to_string()),
+			Frame::Ack(7),
+			Frame::Direct {
+				sender: "alice".to_string(),
+				text:   "just between us".to_string()
+			},
+			Frame::Chat {
+				id:   1,
+				text: String::new()
+			},
+		];
+
+		let mut wire = vec![];
+
+		for frame in &frames
+		{
+			frame.write_to(&mut wire).unwrap();
+		}
+
+		let mut reader = Cursor::new(wire);
+
+		for frame in frames
+		{
+			assert_eq!(Frame::read_from(&mut reader).unwrap(), frame);
+		}
+	}
+
+	#[test]
+	fn invalid_utf8_is_rejected()
+	{
+		let mut wire = vec![CHAT];
+		wire.extend_from_slice(&6u32.to_be_bytes());
+		wire.extend_from_slice(&[0, 0, 0, 1, 0xff, 0xfe]);
+
+		let error = Frame::read_from(&mut Cursor::new(wire)).unwrap_err();
+
+		assert_eq!(error.kind(), ErrorKind::InvalidData);
+	}
+
+	#[test]
+	fn reading_goes_on_after_a_truncated_character()
+	{
+		// "é" is two bytes, and only the first made it into the frame.
+		let mut wire = vec![CHAT];
+		wire.extend_from_slice(&6u32.to_be_bytes());
+		wire.extend_from_slice(&[0, 0, 0, 1, b'a', 0xc3]);
+
+		Frame::Ping.write_to(&mut wire).unwrap();
+
+		let mut reader = Cursor::new(wire);
+		let error = Frame::read_from(&mut reader).unwrap_err();
+
+		assert_eq!(error.kind(), ErrorKind::InvalidData);
+		assert!(UndecodableFrame::from_error(&error).is_some());
+		assert!(FrameTooLarge::from_error(&error).is_none());
+		assert_eq!(Frame::read_from(&mut reader).unwrap(), Frame::Ping);
+
+		// Neither a frame that is too large nor a connection cut short leaves anything to read on from.
+		let error = Frame::read_from(&mut Cursor::new(vec![CHAT, 0, 0])).unwrap_err();
+		assert!(UndecodableFrame::from_error(&error).is_none());
+	}
+
+	#[test]
+	fn oversized_frames_are_rejected()
+	{
+		// Only the header is sent, so reading the payload would fail with UnexpectedEof if the length were trusted.
+		let mut wire = vec![CHAT];
+		wire.extend_from_slice(&u32::MAX.to_be_bytes());
+
+		let error = Frame::read_from(&mut Cursor::new(wire)).unwrap_err();
+
+		assert_eq!(error.kind(), ErrorKind::InvalidData);
+		assert_eq!(FrameTooLarge::from_error(&error).unwrap().length, u32::MAX as usize);
+
+		let chat = |text: String| Frame::Chat { id: 0, text };
+
+		let error = chat("x".repeat(MAX_CHAT_LEN + 1)).write_to(&mut vec![]).unwrap_err();
+
+		assert_eq!(error.kind(), ErrorKind::InvalidInput);
+
+		let mut wire = vec![];
+		let largest = chat("x".repeat(MAX_CHAT_LEN));
+
+		largest.write_to(&mut wire).unwrap();
+		assert_eq!(Frame::read_from(&mut Cursor::new(wire)).unwrap(), largest);
+	}
+
+	#[test]
+	fn readers_can_lower_the_cap()
+	{
+		let mut wire = vec![];
+		Frame::Notice("x".repeat(100)).write_to(&mut wire).unwrap();
+
+		// Only the header is left once the length is refused, so nothing of the payload was read.
+		let mut reader = Cursor::new(wire);
+		let error = Frame::read_from_limited(&mut reader, 99).unwrap_err();
+		let too_large = FrameTooLarge::from_error(&error).unwrap();
+
+		assert_eq!((too_large.length, too_large.limit), (100, 99));
+		assert_eq!(reader.position(), 5);
+
+		reader.set_position(0);
+		assert!(Frame::read_from_limited(&mut reader, 100).is_ok());
+
+		// Errors from the connection itself aren't mistaken for an oversized frame.
+		let error = Frame::read_from(&mut Cursor::new(vec![CHAT])).unwrap_err();
+		assert!(FrameTooLarge::from_error(&error).is_none());
+	}
+
+	#[test]
+	fn broadcast_sender_must_fit_in_the_frame()
+	{
+		for kind in [BROADCAST, DIRECT]
+		{
+			let mut wire = vec![kind];
+			wire.extend_from_slice(&3u32.to_be_bytes());
+			wire.extend_from_slice(&[0, 5, b'a']);
+
+			let error = Frame::read_from(&mut Cursor::new(wire)).unwrap_err();
+
+			assert_eq!(error.kind(), ErrorKind::InvalidData);
+		}
+	}
+
+	#[test]
+	fn truncated_ids_are_rejected()
+	{
+		for kind in [CHAT, ACK]
+		{
+			let mut wire = vec![kind];
+			wire.extend_from_slice(&3u32.to_be_bytes());
+			wire.extend_from_slice(&[0, 0, 1]);
+
+			let error = Frame::read_from(&mut Cursor::new(wire)).unwrap_err();
+
+			assert_eq!(error.kind(), ErrorKind::InvalidData);
+		}
+	}
+
+	#[test]
+	fn unknown_kinds_are_rejected()
+	{
+		let mut wire = vec![8];
+		wire.extend_from_slice(&0u32.to_be_bytes());
+
+		let error = Frame::read_from(&mut Cursor::new(wire)).unwrap_err();
+
+		assert_eq!(error.kind(), ErrorKind::InvalidData);
+	}
+}
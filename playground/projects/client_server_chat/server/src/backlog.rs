@@ -0,0 +1,112 @@
+use protocol::Frame;
+use std::collections::VecDeque;
+
+// The most recent messages relayed to everyone, oldest first, kept so that clients joining later can catch up on the
+// conversation. Once full, each new message pushes out the oldest one.
+pub struct Backlog
+{
+	capacity: usize,
+	messages: VecDeque<(String, String)>
+}
+
+impl Backlog
+{
+	// A capacity of 0 keeps nothing, which turns the replay off.
+	pub fn new(capacity: usize) -> Backlog
+	{
+		Backlog {
+			capacity,
+			messages: VecDeque::with_capacity(capacity)
+		}
+	}
+
+	pub fn push(&mut self, sender: String, text: String)
+	{
+		if self.capacity == 0
+		{
+			return;
+		}
+
+		if self.messages.len() == self.capacity
+		{
+			self.messages.pop_front();
+		}
+
+		self.messages.push_back((sender, text));
+	}
+
+	// The messages as they were originally broadcast, in the order they were sent.
+	pub fn frames(&self) -> impl Iterator<Item = Frame> + '_
+	{
+		self.messages.iter().map(|(sender, text)| {
+			Frame::Broadcast {
+				sender: sender.clone(),
+				text:   text.clone()
+			}
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn texts(backlog: &Backlog) -> Vec<String>
+	{
+		backlog
+			.frames()
+			.map(|frame| {
+				match frame
+				{
+					Frame::Broadcast { text, .. } => text,
+					frame => panic!("unexpected frame {:?}", frame)
+				}
+			})
+			.collect()
+	}
+
+	#[test]
+	fn late_joiners_get_the_most_recent_messages_in_order()
+	{
+		let mut backlog = Backlog::new(3);
+
+		for index in 1..=5
+		{
+			backlog.push(String::from("alice"), format!("message {}", index));
+		}
+
+		assert_eq!(texts(&backlog), ["message 3", "message 4", "message 5"]);
+
+		// What reaches the client is the same frame everyone else was sent.
+		let mut wire = vec![];
+
+		for frame in backlog.frames()
+		{
+			frame.write_to(&mut wire).unwrap();
+		}
+
+		let mut wire = wire.as_slice();
+
+		for index in 3..=5
+		{
+			assert_eq!(
+				Frame::read_from(&mut wire).unwrap(),
+				Frame::Broadcast {
+					sender: String::from("alice"),
+					text:   format!("message {}", index)
+				}
+			);
+		}
+	}
+
+	#[test]
+	fn a_zero_capacity_keeps_nothing()
+	{
+		let mut backlog = Backlog::new(0);
+
+		backlog.push(String::from("alice"), String::from("hello"));
+
+		assert!(texts(&backlog).is_empty());
+	}
+}
@@ -0,0 +1,96 @@
+use chrono::Local;
+use std::{
+	fs::{File, OpenOptions},
+	io::{self, BufWriter, Write},
+	path::{Path, PathBuf}
+};
+
+// Every message relayed to everyone, appended to a file one line at a time so the conversation outlives the server.
+pub struct ChatLog
+{
+	path:   PathBuf,
+	writer: BufWriter<File>
+}
+
+impl ChatLog
+{
+	// Appends to whatever the file already holds, so the log of earlier runs is kept.
+	pub fn open(path: &Path) -> io::Result<ChatLog>
+	{
+		let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+		Ok(ChatLog {
+			path:   path.to_path_buf(),
+			writer: BufWriter::new(file)
+		})
+	}
+
+	// A message that can't be written is reported and skipped, since losing the log is no reason to stop the chat.
+	pub fn record(&mut self, sender: &str, text: &str)
+	{
+		if let Err(error) = self.write(sender, text)
+		{
+			eprintln!("Failed to write to the chat log {}: {}", self.path.display(), error);
+		}
+	}
+
+	fn write(&mut self, sender: &str, text: &str) -> io::Result<()>
+	{
+		// Line breaks and other control characters become spaces, so no message can pass for more than one line.
+		let text: String = text
+			.chars()
+			.map(|character| {
+				if character.is_control()
+				{
+					' '
+				}
+				else
+				{
+					character
+				}
+			})
+			.collect();
+
+		writeln!(
+			self.writer,
+			"[{}] {}: {}",
+			Local::now().format("%Y-%m-%d %H:%M:%S"),
+			sender,
+			text
+		)?;
+
+		// Flushed line by line, so whatever was said before a crash is on disk.
+		self.writer.flush()
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::fs;
+
+	#[test]
+	fn messages_are_appended_one_line_each()
+	{
+		let path = std::env::temp_dir().join(format!("chat_log_{}.log", std::process::id()));
+		let _ = fs::remove_file(&path);
+
+		ChatLog::open(&path).unwrap().record("alice", "hello");
+
+		// Reopening carries on after what is already there.
+		let mut log = ChatLog::open(&path).unwrap();
+		log.record("SERVER", "restarted");
+		log.record("bob", "two\nlines");
+
+		let contents = fs::read_to_string(&path).unwrap();
+		let lines: Vec<&str> = contents.lines().collect();
+
+		assert_eq!(lines.len(), 3);
+		assert!(lines[0].starts_with('[') && lines[0].ends_with("] alice: hello"));
+		assert!(lines[1].ends_with("] SERVER: restarted"));
+		assert!(lines[2].ends_with("] bob: two lines"));
+
+		fs::remove_file(&path).unwrap();
+	}
+}
@@ -0,0 +1,78 @@
+use std::{
+	collections::HashMap,
+	net::SocketAddr,
+	time::{Duration, Instant}
+};
+
+// Tracks when each client last proved it was alive, so connections that died without closing can be dropped.
+pub struct Heartbeat
+{
+	timeout:   Duration,
+	last_seen: HashMap<SocketAddr, Instant>
+}
+
+impl Heartbeat
+{
+	pub fn new(timeout: Duration) -> Heartbeat
+	{
+		Heartbeat {
+			timeout,
+			last_seen: HashMap::new()
+		}
+	}
+
+	// Called when a client connects and again every time it answers a ping.
+	pub fn seen(&mut self, address: SocketAddr, now: Instant) { self.last_seen.insert(address, now); }
+
+	pub fn forget(&mut self, address: &SocketAddr) { self.last_seen.remove(address); }
+
+	// Removes and returns every client that hasn't been seen within the timeout.
+	pub fn expire(&mut self, now: Instant) -> Vec<SocketAddr>
+	{
+		let timeout = self.timeout;
+
+		let expired: Vec<SocketAddr> = self
+			.last_seen
+			.iter()
+			.filter(|(_, last_seen)| now.duration_since(**last_seen) > timeout)
+			.map(|(address, _)| *address)
+			.collect();
+
+		for address in &expired
+		{
+			self.last_seen.remove(address);
+		}
+
+		expired
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn unresponsive_clients_expire()
+	{
+		let timeout = Duration::from_secs(15);
+		let responsive: SocketAddr = "127.0.0.1:7001".parse().unwrap();
+		let unresponsive: SocketAddr = "127.0.0.1:7002".parse().unwrap();
+
+		let start = Instant::now();
+		let mut heartbeat = Heartbeat::new(timeout);
+
+		heartbeat.seen(responsive, start);
+		heartbeat.seen(unresponsive, start);
+
+		// Only one of them answers the pings that follow.
+		heartbeat.seen(responsive, start + Duration::from_secs(10));
+
+		assert!(heartbeat.expire(start + timeout).is_empty());
+		assert_eq!(heartbeat.expire(start + Duration::from_secs(16)), vec![unresponsive]);
+
+		// Once reported, a client isn't reported again, and the responsive one is still tracked.
+		assert!(heartbeat.expire(start + Duration::from_secs(20)).is_empty());
+		assert_eq!(heartbeat.expire(start + Duration::from_secs(26)), vec![responsive]);
+	}
+}
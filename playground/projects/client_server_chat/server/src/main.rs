@@ -1,81 +1,602 @@
+mod backlog;
+mod chat_log;
+mod heartbeat;
+mod nicknames;
+mod stats;
+
+use backlog::Backlog;
+use chat_log::ChatLog;
+use heartbeat::Heartbeat;
+use nicknames::Nicknames;
+use protocol::{Frame, FrameTooLarge, UndecodableFrame, MAX_FRAME_LEN};
+use stats::Stats;
 use std::{
-	io::{ErrorKind, Read, Write},
-	net::TcpListener,
-	sync::mpsc,
-	thread
+	env,
+	io::{self, BufRead, Write},
+	net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+	path::PathBuf,
+	sync::mpsc::{self, RecvTimeoutError},
+	thread,
+	time::{Duration, Instant}
 };
 
 const LOCAL_PORT: &str = "127.0.0.1:6000";
-const MSG_SIZE: usize = 32;
 
-fn sleep() { thread::sleep(std::time::Duration::from_millis(100)); }
+// Clients are pinged this often, and dropped if they haven't answered for PONG_TIMEOUT.
+const PING_INTERVAL: Duration = Duration::from_secs(5);
+const PONG_TIMEOUT: Duration = Duration::from_secs(15);
+
+// Writes happen on the main loop, so a client that stops reading must not be able to block it for longer than this.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// How often a throughput line is printed. Each line covers the interval since the previous one, plus running totals.
+const STATS_INTERVAL: Duration = Duration::from_secs(60);
+
+// Who console announcements appear to come from. No client may take it as a nickname.
+const ADMIN_SENDER: &str = "SERVER";
+
+// Nicknames are shown in front of every message, so they are kept short.
+const MAX_NICKNAME_LEN: usize = 32;
+
+// How many of the latest messages a new client is sent when it connects, unless --backlog says otherwise.
+const DEFAULT_BACKLOG_LEN: usize = 20;
+
+const USAGE: &str = "Usage: server [--max-frame-size BYTES] [--backlog MESSAGES] [--log PATH] [--max-clients CLIENTS]";
+
+struct Options
+{
+	// The longest frame payload a client may send.
+	max_frame_len: usize,
+	backlog_len:   usize,
+	// Where every message relayed to everyone is appended, if anywhere.
+	log_path:      Option<PathBuf>,
+	// How many clients may be connected at once. Each one costs a thread and a socket, so without a cap anyone could
+	// keep connecting until the server runs out of both.
+	max_clients:   Option<usize>
+}
+
+// Everything the main loop reacts to. Each source blocks in its own thread and reports here, so the main loop only
+// wakes up when there is actual work and is the only place that touches the client list.
+enum Event
+{
+	Connected(TcpStream, SocketAddr),
+	Chat(SocketAddr, u32, String),
+	Announcement(String),
+	Nick(SocketAddr, String),
+	Pong(SocketAddr),
+	// The client declared a frame longer than the server accepts. Disconnected follows once the connection is closed.
+	FrameTooLarge(SocketAddr, FrameTooLarge),
+	// A frame from the client that arrived whole but made no sense, with why. Only that frame is lost.
+	Undecodable(SocketAddr, String),
+	Disconnected(SocketAddr),
+	Shutdown
+}
 
 fn main()
 {
+	let args: Vec<String> = env::args().collect();
+
+	let options = parse_options(&args).unwrap_or_else(|error| {
+		eprintln!("{}: {}", args[0], error);
+		std::process::exit(1);
+	});
+
+	let max_frame_len = options.max_frame_len;
+	let max_clients = options.max_clients;
+
+	// Asked for by name, so a log that can't be opened stops the server before anyone joins rather than going
+	// unnoticed.
+	let mut chat_log = options.log_path.as_deref().map(|path| {
+		ChatLog::open(path).unwrap_or_else(|error| {
+			eprintln!("{}: Failed to open the chat log {}: {}", args[0], path.display(), error);
+			std::process::exit(1);
+		})
+	});
+
 	let server = TcpListener::bind(LOCAL_PORT).expect("Listener failed to bind");
 
-	server.set_nonblocking(true).expect("Failed to initialize non-blocking");
+	let (tx, rx) = mpsc::channel::<Event>();
 
-	let mut clients = vec![];
+	let shutdown_tx = tx.clone();
 
-	let (tx, rx) = mpsc::channel::<String>();
+	ctrlc::set_handler(move || {
+		let _ = shutdown_tx.send(Event::Shutdown);
+	})
+	.expect("Failed to install Ctrl-C handler");
+
+	let accept_tx = tx.clone();
+
+	thread::spawn(move || {
+		loop
+		{
+			if let Ok((tcp_stream, socket_address)) = server.accept()
+			{
+				if accept_tx.send(Event::Connected(tcp_stream, socket_address)).is_err()
+				{
+					break;
+				}
+			}
+		}
+	});
+
+	// Lines typed into the server console are announced to everyone.
+	let admin_tx = tx.clone();
+
+	thread::spawn(move || {
+		for line in io::stdin().lock().lines().map_while(Result::ok)
+		{
+			let line = line.trim();
+
+			if !line.is_empty() && admin_tx.send(Event::Announcement(line.to_string())).is_err()
+			{
+				break;
+			}
+		}
+	});
+
+	let mut clients: Vec<(SocketAddr, TcpStream)> = vec![];
+	let mut client_threads = vec![];
+
+	let mut nicknames = Nicknames::new();
+	let mut heartbeat = Heartbeat::new(PONG_TIMEOUT);
+	let mut last_ping = Instant::now();
+	let mut stats = Stats::new(last_ping);
+	let mut backlog = Backlog::new(options.backlog_len);
 
 	loop
 	{
-		if let Ok((mut tcp_stream, socket_address)) = server.accept()
+		// Waking up for the next ping or stats line are the only reasons to stop waiting without an event.
+		let wait = PING_INTERVAL
+			.saturating_sub(last_ping.elapsed())
+			.min(stats.time_until_due(STATS_INTERVAL, Instant::now()));
+
+		let event = match rx.recv_timeout(wait)
 		{
-			println!("Client {} connected", socket_address);
+			Ok(event) => Some(event),
+			Err(RecvTimeoutError::Timeout) => None,
+			Err(RecvTimeoutError::Disconnected) => break
+		};
 
-			let tx = tx.clone();
+		match event
+		{
+			Some(Event::Connected(tcp_stream, socket_address)) =>
+			{
+				println!("Client {} connected", socket_address);
 
-			clients.push(tcp_stream.try_clone().expect("Failed to clone client"));
+				if let Err(error) = tcp_stream.set_write_timeout(Some(WRITE_TIMEOUT))
+				{
+					println!("Rejecting {}: {}", socket_address, error);
+					continue;
+				}
 
-			thread::spawn(move || {
-				loop
+				// Turned away before it gets a reader thread, so a full server costs nothing more than the reply.
+				if max_clients.is_some_and(|max_clients| clients.len() >= max_clients)
 				{
-					let mut buffer = vec![0; MSG_SIZE];
+					println!("Rejecting {}: server full", socket_address);
+
+					let mut tcp_stream = tcp_stream;
 
-					match tcp_stream.read_exact(&mut buffer)
+					if let Ok(bytes) = Frame::Notice(String::from("server full")).encode()
 					{
-						Ok(_) =>
-						{
-							let msg = buffer.into_iter().take_while(|&x| x != 0).collect::<Vec<_>>();
+						let _ = tcp_stream.write_all(&bytes);
+						stats.bytes_sent(bytes.len());
+					}
 
-							let msg = String::from_utf8(msg).expect("Invalid utf8 message");
+					let _ = tcp_stream.shutdown(Shutdown::Both);
+					continue;
+				}
 
-							println!("{}: {:?}", socket_address, msg);
+				let reader = tcp_stream.try_clone().expect("Failed to clone client");
+				let tx = tx.clone();
 
-							tx.send(msg).expect("Failed to send message to rx");
-						}
+				clients.push((socket_address, tcp_stream));
+				heartbeat.seen(socket_address, Instant::now());
+				client_threads.push(thread::spawn(move || {
+					read_messages(reader, socket_address, max_frame_len, tx)
+				}));
 
-						Err(ref error) if error.kind() == ErrorKind::WouldBlock => (),
+				// Events are handled one at a time, so nothing live can reach the client before the backlog has been
+				// sent.
+				for frame in backlog.frames()
+				{
+					stats.bytes_sent(send_to(&mut clients, socket_address, &frame));
+				}
+			}
+			Some(Event::Chat(socket_address, id, text)) =>
+			{
+				// Clients that haven't picked a nickname are known by their address.
+				let sender = match nicknames.name_of(&socket_address)
+				{
+					Some(nickname) => nickname.to_string(),
+					None => socket_address.to_string()
+				};
 
-						Err(_) =>
+				// A private message is neither printed nor kept for the backlog, since it was only meant for one
+				// person.
+				if let Some((recipient, text)) = direct_message(&text)
+				{
+					let frame = match nicknames.address_of(recipient)
+					{
+						Some(_) if text.is_empty() => Frame::Notice(String::from("Usage: /msg NICKNAME TEXT")),
+						Some(recipient) =>
 						{
-							println!("Closing connection to: {}", socket_address);
-							break;
+							let direct = Frame::Direct {
+								sender,
+								text: text.to_string()
+							};
+
+							stats.message_relayed();
+							stats.bytes_sent(send_to(&mut clients, recipient, &direct));
+
+							Frame::Ack(id)
 						}
+						None => Frame::Notice(format!("no such nickname: {}", recipient))
+					};
+
+					stats.bytes_sent(send_to(&mut clients, socket_address, &frame));
+					continue;
+				}
+
+				println!("{}: {:?}", sender, text);
+
+				if let Some(chat_log) = &mut chat_log
+				{
+					chat_log.record(&sender, &text);
+				}
+
+				backlog.push(sender.clone(), text.clone());
+
+				stats.message_relayed();
+				stats.bytes_sent(broadcast(&mut clients, &Frame::Broadcast { sender, text }));
+
+				// Everyone still connected has been sent the message by now, so the sender can count it as delivered.
+				stats.bytes_sent(send_to(&mut clients, socket_address, &Frame::Ack(id)));
+			}
+			Some(Event::Announcement(text)) =>
+			{
+				let sender = String::from(ADMIN_SENDER);
+
+				if let Some(chat_log) = &mut chat_log
+				{
+					chat_log.record(&sender, &text);
+				}
+
+				backlog.push(sender.clone(), text.clone());
+
+				stats.message_relayed();
+				stats.bytes_sent(broadcast(&mut clients, &Frame::Broadcast { sender, text }));
+			}
+			Some(Event::Nick(socket_address, nickname)) =>
+			{
+				let previous = nicknames.name_of(&socket_address).map(String::from);
+
+				let problem = nickname_problem(&nickname).or_else(|| {
+					let taken = !nicknames.claim(socket_address, &nickname);
+
+					taken.then(|| format!("nickname taken: {}", nickname))
+				});
+
+				let reply = match problem
+				{
+					Some(problem) => Frame::Notice(problem),
+					None => Frame::Nick(nickname.clone())
+				};
+
+				stats.bytes_sent(send_to(&mut clients, socket_address, &reply));
+
+				// Only a name that actually changed is worth telling everyone about.
+				if matches!(reply, Frame::Nick(_)) && previous.as_deref() != Some(nickname.as_str())
+				{
+					let text = match previous
+					{
+						Some(previous) => format!("{} is now known as {}", previous, nickname),
+						None => format!("{} joined", nickname)
+					};
+
+					println!("{}", text);
+
+					let sender = String::from(ADMIN_SENDER);
+
+					if let Some(chat_log) = &mut chat_log
+					{
+						chat_log.record(&sender, &text);
+					}
+
+					stats.bytes_sent(broadcast(&mut clients, &Frame::Broadcast { sender, text }));
+				}
+			}
+			Some(Event::Pong(socket_address)) => heartbeat.seen(socket_address, Instant::now()),
+			Some(Event::FrameTooLarge(socket_address, too_large)) =>
+			{
+				println!("Disconnecting {}: {}", socket_address, too_large);
+
+				// The rest of the frame is still unread, so nothing after it on this connection can be trusted.
+				let notice = Frame::Notice(format!(
+					"disconnected: message of {} bytes is over the server's limit of {}",
+					too_large.length, too_large.limit
+				));
+
+				stats.bytes_sent(send_to(&mut clients, socket_address, &notice));
+
+				for (_, client) in clients.iter().filter(|(address, _)| *address == socket_address)
+				{
+					let _ = client.shutdown(Shutdown::Both);
+				}
+			}
+			Some(Event::Undecodable(socket_address, reason)) =>
+			{
+				println!("Dropping a frame from {}: {}", socket_address, reason);
+
+				let notice = Frame::Notice(format!("message dropped: {}", reason));
+
+				stats.bytes_sent(send_to(&mut clients, socket_address, &notice));
+			}
+			Some(Event::Disconnected(socket_address)) =>
+			{
+				println!("Closing connection to: {}", socket_address);
+				heartbeat.forget(&socket_address);
+				clients.retain(|(address, _)| *address != socket_address);
+
+				// Only clients that were announced as joining, by picking a nickname, are announced as leaving.
+				if let Some(nickname) = nicknames.name_of(&socket_address)
+				{
+					let text = format!("{} left", nickname);
+
+					println!("{}", text);
+
+					let sender = String::from(ADMIN_SENDER);
+
+					if let Some(chat_log) = &mut chat_log
+					{
+						chat_log.record(&sender, &text);
 					}
 
-					sleep();
+					stats.bytes_sent(broadcast(&mut clients, &Frame::Broadcast { sender, text }));
 				}
-			});
+
+				nicknames.release(&socket_address);
+			}
+			Some(Event::Shutdown) => break,
+			None => ()
+		}
+
+		if last_ping.elapsed() >= PING_INTERVAL
+		{
+			stats.bytes_sent(broadcast(&mut clients, &Frame::Ping));
+			last_ping = Instant::now();
+
+			for socket_address in heartbeat.expire(last_ping)
+			{
+				println!("Client {} stopped responding", socket_address);
+
+				// Shutting the stream down wakes its thread, which is stuck waiting on a read that will never finish.
+				for (_, client) in clients.iter().filter(|(address, _)| *address == socket_address)
+				{
+					let _ = client.shutdown(Shutdown::Both);
+				}
+
+				clients.retain(|(address, _)| *address != socket_address);
+			}
 		}
 
-		if let Ok(msg) = rx.try_recv()
+		if stats.is_due(STATS_INTERVAL, Instant::now())
 		{
-			clients = clients
-				.into_iter()
-				.filter_map(|mut client| {
-					let mut buff = msg.clone().into_bytes();
+			println!("{}", stats.report(clients.len(), Instant::now()));
+		}
+	}
+
+	println!("Shutting down server");
+
+	for (_, mut client) in clients
+	{
+		// The client may already be gone, in which case there is nobody left to notify.
+		let farewell = Frame::Broadcast {
+			sender: String::from(ADMIN_SENDER),
+			text:   String::from("server shutting down")
+		};
+
+		let _ = farewell.write_to(&mut client);
+		let _ = client.shutdown(Shutdown::Both);
+	}
+
+	// Shutting the streams down unblocks any thread still waiting on a read, so every join returns promptly.
+	for client_thread in client_threads
+	{
+		let _ = client_thread.join();
+	}
+}
+
+// Reads "--max-frame-size BYTES", "--backlog MESSAGES", "--log PATH" and "--max-clients CLIENTS", falling back to the
+// defaults for whichever isn't given.
+fn parse_options(args: &[String]) -> Result<Options, String>
+{
+	let mut options = Options {
+		max_frame_len: MAX_FRAME_LEN,
+		backlog_len:   DEFAULT_BACKLOG_LEN,
+		log_path:      None,
+		max_clients:   None
+	};
+
+	let mut remaining = args[1..].iter();
+
+	while let Some(flag) = remaining.next()
+	{
+		let value = remaining.next().ok_or_else(|| String::from(USAGE))?;
+
+		match flag.as_str()
+		{
+			"--max-frame-size" =>
+			{
+				options.max_frame_len = match value.parse::<usize>()
+				{
+					// Clients can't send anything larger, so a higher cap would only look like it raised the limit.
+					Ok(bytes) if (1..=MAX_FRAME_LEN).contains(&bytes) => bytes,
+					_ =>
+					{
+						return Err(format!(
+							"--max-frame-size must be between 1 and {} bytes",
+							MAX_FRAME_LEN
+						))
+					}
+				};
+			}
+			"--backlog" =>
+			{
+				options.backlog_len = value
+					.parse::<usize>()
+					.map_err(|_| String::from("--backlog must be a number of messages, 0 to turn it off"))?;
+			}
+			"--log" => options.log_path = Some(PathBuf::from(value)),
+			"--max-clients" =>
+			{
+				options.max_clients = match value.parse::<usize>()
+				{
+					Ok(clients) if clients > 0 => Some(clients),
+					_ => return Err(String::from("--max-clients must be a number of clients, at least 1"))
+				};
+			}
+			_ => return Err(String::from(USAGE))
+		}
+	}
+
+	Ok(options)
+}
+
+// Blocks on the client's socket, forwarding each message and pong until the connection closes.
+fn read_messages(mut tcp_stream: TcpStream, socket_address: SocketAddr, max_frame_len: usize, tx: mpsc::Sender<Event>)
+{
+	loop
+	{
+		// A frame that can't be decoded, e.g. text cut off in the middle of a character, is dropped on its own, while
+		// any other read error is treated like a broken connection. Neither takes the server down.
+		let event = match Frame::read_from_limited(&mut tcp_stream, max_frame_len)
+		{
+			Ok(Frame::Chat { id, text }) => Event::Chat(socket_address, id, text),
+			Ok(Frame::Nick(nickname)) => Event::Nick(socket_address, nickname),
+			Ok(Frame::Pong) => Event::Pong(socket_address),
+			Ok(Frame::Ping | Frame::Broadcast { .. } | Frame::Notice(_) | Frame::Ack(_) | Frame::Direct { .. }) =>
+			{
+				continue
+			}
+			Err(error) if UndecodableFrame::from_error(&error).is_some() =>
+			{
+				Event::Undecodable(socket_address, error.to_string())
+			}
+			Err(error) =>
+			{
+				// The main loop tells the client why before closing the connection.
+				if let Some(too_large) = FrameTooLarge::from_error(&error)
+				{
+					let _ = tx.send(Event::FrameTooLarge(socket_address, *too_large));
+				}
 
-					buff.resize(MSG_SIZE, 0);
+				let _ = tx.send(Event::Disconnected(socket_address));
+				break;
+			}
+		};
 
-					client.write_all(&buff).map(|_| client).ok()
-				})
-				.collect::<Vec<_>>();
+		if tx.send(event).is_err()
+		{
+			break;
 		}
+	}
+}
+
+// Splits "/msg NICKNAME TEXT" into the nickname and the text, which may be empty. Anything else is a message for
+// everyone.
+fn direct_message(text: &str) -> Option<(&str, &str)>
+{
+	let rest = text.strip_prefix("/msg ")?.trim_start();
+
+	Some(
+		rest.split_once(' ')
+			.map_or((rest, ""), |(recipient, text)| (recipient, text.trim()))
+	)
+}
+
+// Why a nickname can't be used no matter who else is connected, if there is a reason.
+fn nickname_problem(nickname: &str) -> Option<String>
+{
+	if nickname.trim().is_empty()
+	{
+		return Some(String::from("nickname can't be empty"));
+	}
+
+	if nickname.len() > MAX_NICKNAME_LEN
+	{
+		return Some(format!("nickname can't be longer than {} bytes", MAX_NICKNAME_LEN));
+	}
 
-		sleep();
+	if nickname.eq_ignore_ascii_case(ADMIN_SENDER)
+	{
+		return Some(format!("nickname taken: {}", nickname));
 	}
+
+	None
+}
+
+// Sends the frame to a single client, dropping it if the write fails the same way 'broadcast' does. Returns the number
+// of bytes delivered.
+fn send_to(clients: &mut Vec<(SocketAddr, TcpStream)>, socket_address: SocketAddr, frame: &Frame) -> usize
+{
+	let bytes = match frame.encode()
+	{
+		Ok(bytes) => bytes,
+		Err(error) =>
+		{
+			println!("Dropping message that can't be sent: {}", error);
+			return 0;
+		}
+	};
+
+	let mut delivered = 0;
+
+	clients.retain_mut(|(address, client)| {
+		if *address != socket_address
+		{
+			return true;
+		}
+
+		if client.write_all(&bytes).is_ok()
+		{
+			delivered = bytes.len();
+			return true;
+		}
+
+		let _ = client.shutdown(Shutdown::Both);
+		false
+	});
+
+	delivered
+}
+
+// Sends the frame to every client, dropping any whose connection has failed or whose write timed out. Returns the
+// number of bytes delivered.
+fn broadcast(clients: &mut Vec<(SocketAddr, TcpStream)>, frame: &Frame) -> usize
+{
+	// A frame that can't be encoded, e.g. a maximum length message once the sender is added, would otherwise fail every
+	// write and look like all clients had disconnected.
+	let bytes = match frame.encode()
+	{
+		Ok(bytes) => bytes,
+		Err(error) =>
+		{
+			println!("Dropping message that can't be sent: {}", error);
+			return 0;
+		}
+	};
+
+	clients.retain_mut(|(_, client)| {
+		if client.write_all(&bytes).is_ok()
+		{
+			return true;
+		}
+
+		// A timed out write may have left half a frame behind, so the connection can't be reused. Shutting it down
+		// also ends the client's read thread, which is still holding its own handle to the socket.
+		let _ = client.shutdown(Shutdown::Both);
+		false
+	});
+
+	bytes.len() * clients.len()
 }
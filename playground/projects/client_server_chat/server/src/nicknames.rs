@@ -0,0 +1,115 @@
+use std::{collections::HashMap, net::SocketAddr};
+
+// The nickname each client has claimed. Names are compared without regard to ASCII case, so "Alice" and "alice" can't
+// both be in the chat and be mistaken for one another.
+pub struct Nicknames
+{
+	names: HashMap<SocketAddr, String>
+}
+
+impl Nicknames
+{
+	pub fn new() -> Nicknames { Nicknames { names: HashMap::new() } }
+
+	// Gives the client the nickname unless another client already has it. Claiming a new one frees the client's old
+	// name, and a client may always claim the name it already has.
+	pub fn claim(&mut self, client: SocketAddr, nickname: &str) -> bool
+	{
+		let taken = self
+			.names
+			.iter()
+			.any(|(address, name)| *address != client && name.eq_ignore_ascii_case(nickname));
+
+		if taken
+		{
+			return false;
+		}
+
+		self.names.insert(client, nickname.to_string());
+		true
+	}
+
+	pub fn release(&mut self, client: &SocketAddr) { self.names.remove(client); }
+
+	pub fn name_of(&self, client: &SocketAddr) -> Option<&str> { self.names.get(client).map(String::as_str) }
+
+	// The client holding the nickname, with the same disregard for case as 'claim'.
+	pub fn address_of(&self, nickname: &str) -> Option<SocketAddr>
+	{
+		self.names
+			.iter()
+			.find(|(_, name)| name.eq_ignore_ascii_case(nickname))
+			.map(|(address, _)| *address)
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::{
+		sync::{Arc, Barrier, Mutex},
+		thread
+	};
+
+	#[test]
+	fn only_one_of_two_racing_clients_gets_the_name()
+	{
+		let nicknames = Arc::new(Mutex::new(Nicknames::new()));
+		let start = Arc::new(Barrier::new(2));
+
+		let racers: Vec<_> = ["127.0.0.1:5001", "127.0.0.1:5002"]
+			.into_iter()
+			.map(|address| {
+				let nicknames = nicknames.clone();
+				let start = start.clone();
+				let address: SocketAddr = address.parse().unwrap();
+
+				thread::spawn(move || {
+					start.wait();
+					(address, nicknames.lock().unwrap().claim(address, "alice"))
+				})
+			})
+			.collect();
+
+		let results: Vec<(SocketAddr, bool)> = racers.into_iter().map(|racer| racer.join().unwrap()).collect();
+		let winners: Vec<SocketAddr> = results
+			.iter()
+			.filter(|(_, won)| *won)
+			.map(|(address, _)| *address)
+			.collect();
+
+		assert_eq!(winners.len(), 1);
+
+		let mut nicknames = nicknames.lock().unwrap();
+		let loser = results.iter().find(|(_, won)| !won).unwrap().0;
+
+		assert_eq!(nicknames.name_of(&winners[0]), Some("alice"));
+		assert_eq!(nicknames.name_of(&loser), None);
+
+		// Case doesn't make a different name, but the holder can re-claim it, and leaving frees it for others.
+		assert!(!nicknames.claim(loser, "ALICE"));
+		assert!(nicknames.claim(winners[0], "alice"));
+
+		nicknames.release(&winners[0]);
+		assert!(nicknames.claim(loser, "Alice"));
+	}
+
+	#[test]
+	fn clients_are_found_by_nickname_whatever_the_case()
+	{
+		let mut nicknames = Nicknames::new();
+		let alice: SocketAddr = "127.0.0.1:5001".parse().unwrap();
+
+		assert_eq!(nicknames.address_of("alice"), None);
+
+		nicknames.claim(alice, "Alice");
+
+		assert_eq!(nicknames.address_of("alice"), Some(alice));
+		assert_eq!(nicknames.address_of("ALICE"), Some(alice));
+		assert_eq!(nicknames.address_of("bob"), None);
+
+		nicknames.release(&alice);
+		assert_eq!(nicknames.address_of("alice"), None);
+	}
+}
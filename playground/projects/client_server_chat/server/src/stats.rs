@@ -0,0 +1,113 @@
+use std::time::{Duration, Instant};
+
+// Throughput counters for the periodic stats line. Each report covers the time since the previous one and then starts
+// a new interval, while the totals keep counting for as long as the server runs.
+pub struct Stats
+{
+	interval_start: Instant,
+	messages:       u64,
+	bytes_sent:     u64,
+	total_messages: u64,
+	total_bytes:    u64
+}
+
+impl Stats
+{
+	pub fn new(now: Instant) -> Stats
+	{
+		Stats {
+			interval_start: now,
+			messages:       0,
+			bytes_sent:     0,
+			total_messages: 0,
+			total_bytes:    0
+		}
+	}
+
+	pub fn message_relayed(&mut self)
+	{
+		self.messages += 1;
+		self.total_messages += 1;
+	}
+
+	// Counts every byte written to a client, pings included.
+	pub fn bytes_sent(&mut self, bytes: usize)
+	{
+		self.bytes_sent += bytes as u64;
+		self.total_bytes += bytes as u64;
+	}
+
+	pub fn is_due(&self, every: Duration, now: Instant) -> bool { now.duration_since(self.interval_start) >= every }
+
+	pub fn time_until_due(&self, every: Duration, now: Instant) -> Duration
+	{
+		every.saturating_sub(now.duration_since(self.interval_start))
+	}
+
+	// Summarises the interval that just ended and starts the next one.
+	pub fn report(&mut self, clients: usize, now: Instant) -> String
+	{
+		let minutes = now.duration_since(self.interval_start).as_secs_f64() / 60.0;
+
+		let per_minute = match minutes > 0.0
+		{
+			true => self.messages as f64 / minutes,
+			false => 0.0
+		};
+
+		let line = format!(
+			"Stats: {:.1} messages/min, {} bytes sent, {} clients connected (total: {} messages, {} bytes)",
+			per_minute, self.bytes_sent, clients, self.total_messages, self.total_bytes
+		);
+
+		self.interval_start = now;
+		self.messages = 0;
+		self.bytes_sent = 0;
+
+		line
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn intervals_reset_while_totals_accumulate()
+	{
+		let start = Instant::now();
+		let mut stats = Stats::new(start);
+
+		for _ in 0..6
+		{
+			stats.message_relayed();
+		}
+
+		stats.bytes_sent(100);
+		stats.bytes_sent(50);
+
+		assert!(!stats.is_due(Duration::from_secs(60), start + Duration::from_secs(59)));
+		assert!(stats.is_due(Duration::from_secs(60), start + Duration::from_secs(60)));
+
+		// Six messages in half a minute.
+		assert_eq!(
+			stats.report(2, start + Duration::from_secs(30)),
+			"Stats: 12.0 messages/min, 150 bytes sent, 2 clients connected (total: 6 messages, 150 bytes)"
+		);
+
+		stats.message_relayed();
+		stats.bytes_sent(10);
+
+		assert_eq!(
+			stats.report(1, start + Duration::from_secs(90)),
+			"Stats: 1.0 messages/min, 10 bytes sent, 1 clients connected (total: 7 messages, 160 bytes)"
+		);
+
+		// An interval with no time in it doesn't divide by zero.
+		assert_eq!(
+			stats.report(0, start + Duration::from_secs(90)),
+			"Stats: 0.0 messages/min, 0 bytes sent, 0 clients connected (total: 7 messages, 160 bytes)"
+		);
+	}
+}
@@ -0,0 +1,959 @@
+use crate::{
+	config::{Config, ConfigError},
+	ports::{Ports, PortsError},
+	probe::ProbeKind,
+	report::Format,
+	resolve_host,
+	target::{Target, TargetError},
+	Protocol
+};
+use std::{error, fmt, path::PathBuf, time::Duration};
+
+const HELP: &str = "Usage: ip_sniffer [OPTIONS] IP_ADDRESS|HOST_NAME|NETWORK/PREFIX
+  -p <ports>                    Ports to scan, e.g. 80, 20-1024 or 22,80,8000-8100 (default 1-65535)
+  -j <threads>                  Number of threads to scan with (default 50)
+  -t <milliseconds>             How long each port gets to answer before it counts as filtered (default 1000)
+  -u or --udp                   Scan UDP ports instead of TCP. UDP doesn't acknowledge anything, so only ports
+                                that answer with ICMP port unreachable are known to be closed. Every other port is
+                                reported as open, which really means open or filtered
+  --max-concurrency <count>     Limit how many connection attempts are in flight at once. Each thread makes one
+                                attempt at a time, so this can only lower the concurrency -j gives, not raise it
+  --limit <count>               Stop scanning once this many open ports have been found
+  --knock <port,port,...>       Knock on the ports in order instead of scanning
+  --knock-delay <milliseconds>  Delay between knocks (default 200)
+  --knock-probe <port>          Check whether a port became reachable after knocking
+  --format <text|json|jsonl>    How the open ports are written out (default text)
+  --output <path>               Also write the open ports to this file, creating missing directories
+  --no-service-names            Leave out the usual service of well-known open ports, e.g. the (ssh) in
+                                \"22 is open (ssh)\"
+  -b or --banner                Read the first line each open port sends on its own, e.g. an SSH version
+  --count-only                  Only report how many ports were open, closed, filtered and scanned
+  --probe <http>                Check that each open port answers like the given service
+  --tui                         Show the scan's progress and open ports live in a terminal UI (press q to quit)
+  --progress                    Keep a count of the ports scanned so far on stderr
+  --force                       Scan even when the host doesn't answer the reachability check
+  --ipv6                        Scan a host name's IPv6 address rather than its IPv4 one, when it has both
+  --resolve                     Show the target's hostname from a reverse DNS lookup before scanning
+  --script                      Exit with 0 if any port is open, 1 if none are, and 2 on error. With --knock,
+                                0 and 1 report whether the --knock-probe port became reachable
+  --config <path>               Read settings from a JSON config file. Flags given alongside it take precedence
+  --save-config <path>          Save the settings of this run to a JSON config file, then run as usual
+  --check                       Print the settings the arguments resolve to and exit without scanning
+  -h or -help                   Show this help message";
+
+#[derive(Debug)]
+pub enum ArgumentError
+{
+	HelpRequested,
+	TooManyArguments,
+	NotEnoughArguments,
+	InvalidSyntax,
+	InvalidIpAddress,
+	HostResolutionFailed(String),
+	TooManyHosts,
+	NetworkWithKnockOrTui,
+	InvalidPorts,
+	InvalidPortRange,
+	InvalidThreadsValue,
+	InvalidTimeoutValue,
+	InvalidConcurrencyValue,
+	ConcurrencyAboveThreads,
+	InvalidLimitValue,
+	InvalidFormat,
+	InvalidProbe,
+	ProbeWithCountOnly,
+	ProbeWithUdp,
+	BannerConflict,
+	InvalidKnockSequence,
+	InvalidKnockDelay,
+	InvalidKnockProbe,
+	ScanOptionWithKnock,
+	ScriptWithoutKnockProbe,
+	ProgressWithTui,
+	InvalidConfig(ConfigError)
+}
+
+impl fmt::Display for ArgumentError
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		match self
+		{
+			ArgumentError::HelpRequested => write!(f, "Help requested"),
+			ArgumentError::TooManyArguments => write!(f, "Too many arguments"),
+			ArgumentError::NotEnoughArguments => write!(f, "Not enough arguments"),
+			ArgumentError::InvalidSyntax => write!(f, "Invalid syntax"),
+			ArgumentError::InvalidIpAddress => write!(f, "Invalid IP address or network"),
+			ArgumentError::HostResolutionFailed(host_name) => write!(f, "Couldn't resolve host name {}", host_name),
+			ArgumentError::TooManyHosts => write!(f, "Network is too large: {}", TargetError::TooManyHosts),
+			ArgumentError::NetworkWithKnockOrTui => write!(f, "--knock and --tui only work with a single host"),
+			ArgumentError::InvalidPorts => write!(f, "Invalid ports: {}", PortsError::Invalid),
+			ArgumentError::InvalidPortRange => write!(f, "Invalid port range: {}", PortsError::ReversedRange),
+			ArgumentError::InvalidThreadsValue => write!(f, "Invalid threads value"),
+			ArgumentError::InvalidTimeoutValue => write!(f, "Invalid timeout value, expected milliseconds above 0"),
+			ArgumentError::InvalidConcurrencyValue => write!(f, "Invalid max concurrency value"),
+			ArgumentError::ConcurrencyAboveThreads =>
+			{
+				write!(
+					f,
+					"--max-concurrency can't be more than the number of threads, since each thread only makes one \
+					 connection attempt at a time"
+				)
+			}
+			ArgumentError::InvalidLimitValue => write!(f, "Invalid limit value"),
+			ArgumentError::InvalidFormat => write!(f, "Invalid format, expected text, json or jsonl"),
+			ArgumentError::InvalidProbe => write!(f, "Invalid probe, expected http"),
+			ArgumentError::ProbeWithCountOnly =>
+			{
+				write!(
+					f,
+					"--probe reports on each open port, so it can't be used with --count-only"
+				)
+			}
+			ArgumentError::ProbeWithUdp => write!(f, "--probe connects over TCP, so it can't be used with --udp"),
+			ArgumentError::BannerConflict =>
+			{
+				write!(
+					f,
+					"--banner reads from TCP connections and lists each port, so it can't be used with --udp, --probe or \
+					 --count-only"
+				)
+			}
+			ArgumentError::InvalidKnockSequence => write!(f, "Invalid knock sequence"),
+			ArgumentError::InvalidKnockDelay => write!(f, "Invalid knock delay"),
+			ArgumentError::InvalidKnockProbe => write!(f, "Invalid knock probe port"),
+			ArgumentError::ScanOptionWithKnock =>
+			{
+				write!(
+					f,
+					"-p, -j, -t, --udp, --max-concurrency, --limit, --format, --output, --no-service-names, --banner, --count-only, \
+					 --probe, --tui, --progress and --force only apply to scanning, not --knock"
+				)
+			}
+			ArgumentError::ScriptWithoutKnockProbe =>
+			{
+				write!(f, "--script with --knock needs a --knock-probe port to report on")
+			}
+			ArgumentError::ProgressWithTui =>
+			{
+				write!(f, "--tui shows the scan's progress itself, so --progress isn't needed")
+			}
+			ArgumentError::InvalidConfig(error) => write!(f, "Failed to read config file: {}", error)
+		}
+	}
+}
+
+impl error::Error for ArgumentError
+{
+	fn source(&self) -> Option<&(dyn error::Error + 'static)>
+	{
+		match self
+		{
+			ArgumentError::InvalidConfig(error) => Some(error),
+			_ => None
+		}
+	}
+}
+
+pub struct Arguments
+{
+	pub target:          Target,
+	pub protocol:        Protocol,
+	// Sorted, each port once.
+	pub ports:           Vec<u16>,
+	pub threads:         u16,
+	pub timeout:         Duration,
+	pub max_concurrency: Option<usize>,
+	pub limit:           Option<usize>,
+	pub format:          Format,
+	pub output:          Option<PathBuf>,
+	// Whether the text report names the usual service of each well-known open port.
+	pub service_names:   bool,
+	pub banner:          bool,
+	pub count_only:      bool,
+	pub probe:           Option<ProbeKind>,
+	pub knock:           Option<Vec<u16>>,
+	pub knock_delay:     Duration,
+	pub knock_probe:     Option<u16>,
+	pub tui:             bool,
+	// Whether a count of the ports scanned so far is kept up to date on stderr.
+	pub progress:        bool,
+	pub resolve:         bool,
+	pub script:          bool,
+	// Skips the reachability check, for hosts that drop every connection attempt but are known to be up.
+	pub force:           bool,
+	// Where to write 'config', the settings this run was built from.
+	pub save_config:     Option<PathBuf>,
+	// Only show what would be scanned, see 'summary'.
+	pub check:           bool,
+	pub config:          Config
+}
+
+// An 'impl' block in Rust is used to define implementations of methods and associated functions for a particular type.
+// This block can be used for implementing methods for struct and enum definitions, for trait implementations, or for
+// providing associated functions that don’t take self as a parameter.
+impl Arguments
+{
+	pub fn new(args: &[String]) -> Result<Arguments, ArgumentError>
+	{
+		if args.len() < 2
+		{
+			return Err(ArgumentError::NotEnoughArguments);
+		}
+
+		if args[1] == "-h" || args[1] == "-help"
+		{
+			if args.len() != 2
+			{
+				return Err(ArgumentError::TooManyArguments);
+			}
+
+			// The ! in println! indicates that println is a macro in Rust, not a regular function.
+			// Macros allow for code generation or metaprogramming at compile time, offering more
+			// flexibility than functions, such as accepting a variable number of arguments.
+			println!("{}", HELP);
+			return Err(ArgumentError::HelpRequested);
+		}
+
+		// The flags are gathered into a config of their own first, so they can be laid over a config file's values.
+		let mut flags = Config::default();
+		let mut config_path = None;
+		let mut save_config = None;
+		let mut check = false;
+		let mut prefer_ipv6 = false;
+		// A target that isn't an address is looked up once all the flags are in, since --ipv6 may come after it.
+		let mut host_name = None;
+
+		let mut remaining = args[1..].iter();
+
+		while let Some(flag) = remaining.next()
+		{
+			match flag.as_str()
+			{
+				"-p" =>
+				{
+					let ports = Arguments::value(&mut remaining)?.parse().map_err(|error| {
+						match error
+						{
+							PortsError::Invalid => ArgumentError::InvalidPorts,
+							PortsError::ReversedRange => ArgumentError::InvalidPortRange
+						}
+					})?;
+
+					flags.ports = Some(ports);
+				}
+				"-j" =>
+				{
+					let threads = Arguments::value(&mut remaining)?
+						.parse::<u16>()
+						.map_err(|_| ArgumentError::InvalidThreadsValue)?;
+
+					flags.threads = Some(threads);
+				}
+				"-u" | "--udp" => flags.protocol = Some(Protocol::Udp),
+				"-t" =>
+				{
+					let milliseconds = Arguments::value(&mut remaining)?
+						.parse::<u64>()
+						.map_err(|_| ArgumentError::InvalidTimeoutValue)?;
+
+					flags.timeout_ms = Some(milliseconds);
+				}
+				"--max-concurrency" =>
+				{
+					let permits = Arguments::value(&mut remaining)?
+						.parse::<usize>()
+						.map_err(|_| ArgumentError::InvalidConcurrencyValue)?;
+
+					flags.max_concurrency = Some(permits);
+				}
+				"--limit" =>
+				{
+					let count = Arguments::value(&mut remaining)?
+						.parse::<usize>()
+						.map_err(|_| ArgumentError::InvalidLimitValue)?;
+
+					flags.limit = Some(count);
+				}
+				"--format" =>
+				{
+					let format = Arguments::value(&mut remaining)?
+						.parse()
+						.map_err(|_| ArgumentError::InvalidFormat)?;
+
+					flags.format = Some(format);
+				}
+				"--output" => flags.output = Some(PathBuf::from(Arguments::value(&mut remaining)?)),
+				"--knock" =>
+				{
+					let sequence = Arguments::value(&mut remaining)?
+						.split(',')
+						.map(|port| port.trim().parse::<u16>())
+						.collect::<Result<Vec<u16>, _>>()
+						.map_err(|_| ArgumentError::InvalidKnockSequence)?;
+
+					flags.knock = Some(sequence);
+				}
+				"--knock-delay" =>
+				{
+					let milliseconds = Arguments::value(&mut remaining)?
+						.parse::<u64>()
+						.map_err(|_| ArgumentError::InvalidKnockDelay)?;
+
+					flags.knock_delay_ms = Some(milliseconds);
+				}
+				"--knock-probe" =>
+				{
+					let port = Arguments::value(&mut remaining)?
+						.parse::<u16>()
+						.map_err(|_| ArgumentError::InvalidKnockProbe)?;
+
+					flags.knock_probe = Some(port);
+				}
+				"--no-service-names" => flags.service_names = Some(false),
+				"-b" | "--banner" => flags.banner = Some(true),
+				"--count-only" => flags.count_only = Some(true),
+				"--probe" =>
+				{
+					let probe = Arguments::value(&mut remaining)?
+						.parse()
+						.map_err(|_| ArgumentError::InvalidProbe)?;
+
+					flags.probe = Some(probe);
+				}
+				"--tui" => flags.tui = Some(true),
+				"--progress" => flags.progress = Some(true),
+				"--resolve" => flags.resolve = Some(true),
+				"--script" => flags.script = Some(true),
+				"--force" => flags.force = Some(true),
+				"--ipv6" => prefer_ipv6 = true,
+				"--config" => config_path = Some(PathBuf::from(Arguments::value(&mut remaining)?)),
+				"--save-config" => save_config = Some(PathBuf::from(Arguments::value(&mut remaining)?)),
+				"--check" => check = true,
+				_ if flag.starts_with('-') => return Err(ArgumentError::InvalidSyntax),
+				_ if flags.ip_address.is_some() || host_name.is_some() => return Err(ArgumentError::TooManyArguments),
+				_ =>
+				{
+					match flag.parse()
+					{
+						Ok(target) => flags.ip_address = Some(target),
+						// Host names can't have a prefix, so anything with one was meant as a network.
+						Err(TargetError::Invalid) if !flag.contains('/') => host_name = Some(flag.clone()),
+						Err(TargetError::Invalid) => return Err(ArgumentError::InvalidIpAddress),
+						Err(TargetError::TooManyHosts) => return Err(ArgumentError::TooManyHosts)
+					}
+				}
+			}
+		}
+
+		if let Some(host_name) = host_name
+		{
+			let ip_address =
+				resolve_host(&host_name, prefer_ipv6).ok_or(ArgumentError::HostResolutionFailed(host_name))?;
+
+			flags.ip_address = Some(Target::Host(ip_address));
+		}
+
+		let config = match config_path
+		{
+			Some(path) =>
+			{
+				Config::load(&path)
+					.map_err(ArgumentError::InvalidConfig)?
+					.overridden_by(flags)
+			}
+			None => flags
+		};
+
+		Arguments::from_config(config, save_config, check)
+	}
+
+	// Checks the merged settings and fills in the defaults for anything neither the file nor the flags set.
+	fn from_config(config: Config, save_config: Option<PathBuf>, check: bool) -> Result<Arguments, ArgumentError>
+	{
+		let target = config.ip_address.ok_or(ArgumentError::NotEnoughArguments)?;
+
+		// A zero timeout isn't a short one, connecting with it fails outright.
+		if config.timeout_ms == Some(0)
+		{
+			return Err(ArgumentError::InvalidTimeoutValue);
+		}
+
+		// Without a single thread nothing is scanned, which would pass for a scan that found nothing.
+		if config.threads == Some(0)
+		{
+			return Err(ArgumentError::InvalidThreadsValue);
+		}
+
+		// With no permits at all, every thread would wait forever.
+		if config.max_concurrency == Some(0)
+		{
+			return Err(ArgumentError::InvalidConcurrencyValue);
+		}
+
+		// Every thread has at most one attempt in flight, so the permits can only hold the threads back, never add to
+		// them. A cap above the thread count would look like it raised the concurrency while doing nothing.
+		let threads = config.threads.unwrap_or(50);
+
+		if config.max_concurrency.is_some_and(|permits| permits > threads as usize)
+		{
+			return Err(ArgumentError::ConcurrencyAboveThreads);
+		}
+
+		if config.limit == Some(0)
+		{
+			return Err(ArgumentError::InvalidLimitValue);
+		}
+
+		if config.knock_probe.is_some() && config.knock.is_none()
+		{
+			return Err(ArgumentError::InvalidSyntax);
+		}
+
+		let count_only = config.count_only.unwrap_or(false);
+		let banner = config.banner.unwrap_or(false);
+		let tui = config.tui.unwrap_or(false);
+		let progress = config.progress.unwrap_or(false);
+		let script = config.script.unwrap_or(false);
+		let force = config.force.unwrap_or(false);
+
+		// Knocking doesn't use the scanner, so its options are only looked at to reject them in that mode.
+		let protocol = config.protocol.unwrap_or(Protocol::Tcp);
+
+		let scan_option_given = config.ports.is_some()
+			|| config.threads.is_some()
+			|| config.timeout_ms.is_some()
+			|| protocol == Protocol::Udp
+			|| config.max_concurrency.is_some()
+			|| config.limit.is_some()
+			|| config.format.is_some()
+			|| config.output.is_some()
+			|| config.service_names.is_some()
+			|| banner || count_only
+			|| config.probe.is_some()
+			|| tui || progress
+			|| force;
+
+		if target.is_network() && (config.knock.is_some() || tui)
+		{
+			return Err(ArgumentError::NetworkWithKnockOrTui);
+		}
+
+		if config.knock.is_some() && scan_option_given
+		{
+			return Err(ArgumentError::ScanOptionWithKnock);
+		}
+
+		if count_only && config.probe.is_some()
+		{
+			return Err(ArgumentError::ProbeWithCountOnly);
+		}
+
+		if protocol == Protocol::Udp && config.probe.is_some()
+		{
+			return Err(ArgumentError::ProbeWithUdp);
+		}
+
+		if banner && (protocol == Protocol::Udp || config.probe.is_some() || count_only)
+		{
+			return Err(ArgumentError::BannerConflict);
+		}
+
+		if tui && progress
+		{
+			return Err(ArgumentError::ProgressWithTui);
+		}
+
+		if config.knock.is_some() && script && config.knock_probe.is_none()
+		{
+			return Err(ArgumentError::ScriptWithoutKnockProbe);
+		}
+
+		Ok(Arguments {
+			target,
+			protocol,
+			ports: config.ports.clone().unwrap_or_else(Ports::all).ports(),
+			threads,
+			timeout: Duration::from_millis(config.timeout_ms.unwrap_or(1000)),
+			max_concurrency: config.max_concurrency,
+			limit: config.limit,
+			format: config.format.unwrap_or(Format::Text),
+			output: config.output.clone(),
+			service_names: config.service_names.unwrap_or(true),
+			banner,
+			count_only,
+			probe: config.probe,
+			knock: config.knock.clone(),
+			knock_delay: Duration::from_millis(config.knock_delay_ms.unwrap_or(200)),
+			knock_probe: config.knock_probe,
+			tui,
+			progress,
+			resolve: config.resolve.unwrap_or(false),
+			script,
+			force,
+			save_config,
+			check,
+			config
+		})
+	}
+
+	// Describes what a run with these arguments would do, one setting per line, for --check.
+	pub fn summary(&self) -> String
+	{
+		let hosts = self.target.hosts();
+
+		// Networks are contiguous, so the first and last host stand in for the whole list.
+		let host_range = match (hosts.first(), hosts.last())
+		{
+			(Some(first), Some(last)) if hosts.len() > 1 => format!(" ({} to {})", first, last),
+			_ => String::new()
+		};
+
+		let mut summary = format!("Target:          {}\n", self.target);
+		summary.push_str(&format!("Hosts:           {}{}\n", hosts.len(), host_range));
+
+		if let Some(sequence) = &self.knock
+		{
+			let sequence: Vec<String> = sequence.iter().map(u16::to_string).collect();
+			let probe = self.knock_probe.map_or(String::from("none"), |port| port.to_string());
+
+			summary.push_str(&format!("Knock sequence:  {}\n", sequence.join(", ")));
+			summary.push_str(&format!("Knock delay:     {:?}\n", self.knock_delay));
+			summary.push_str(&format!("Knock probe:     {}\n", probe));
+
+			return summary;
+		}
+
+		let max_concurrency = self
+			.max_concurrency
+			.map_or(String::from("unlimited"), |count| count.to_string());
+		let limit = self.limit.map_or(String::from("none"), |count| count.to_string());
+		let output = self
+			.output
+			.as_ref()
+			.map_or(String::from("stdout"), |path| path.display().to_string());
+		let probe = self.probe.map_or(String::from("none"), |kind| kind.to_string());
+
+		summary.push_str(&format!("Protocol:        {}\n", self.protocol));
+		summary.push_str(&format!("Ports:           {}\n", self.ports.len()));
+		summary.push_str(&format!("Threads:         {}\n", self.threads));
+		summary.push_str(&format!("Timeout:         {:?}\n", self.timeout));
+		summary.push_str(&format!("Max concurrency: {}\n", max_concurrency));
+		summary.push_str(&format!("Limit:           {}\n", limit));
+		summary.push_str(&format!("Format:          {}\n", self.format));
+		summary.push_str(&format!("Output:          {}\n", output));
+		summary.push_str(&format!("Probe:           {}\n", probe));
+
+		summary
+	}
+
+	// Takes the value following a flag, e.g. the "100" in "-j 100".
+	fn value<'a>(remaining: &mut impl Iterator<Item = &'a String>) -> Result<&'a String, ArgumentError>
+	{
+		remaining.next().ok_or(ArgumentError::NotEnoughArguments)
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn parse(args: &str) -> Result<Arguments, ArgumentError>
+	{
+		let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+		Arguments::new(&args)
+	}
+
+	#[test]
+	fn knock_rejects_scan_options()
+	{
+		for args in [
+			"ip_sniffer --knock 1,2 -j 10 127.0.0.1",
+			"ip_sniffer --knock 1,2 --limit 1 127.0.0.1",
+			"ip_sniffer --knock 1,2 --tui 127.0.0.1",
+			"ip_sniffer --knock 1,2 --format json 127.0.0.1",
+			"ip_sniffer --knock 1,2 --output scan.txt 127.0.0.1",
+			"ip_sniffer --knock 1,2 --count-only 127.0.0.1",
+			"ip_sniffer --knock 1,2 --probe http 127.0.0.1",
+			"ip_sniffer --knock 1,2 --force 127.0.0.1",
+			"ip_sniffer --max-concurrency 5 --knock 1,2 127.0.0.1"
+		]
+		{
+			assert!(
+				matches!(parse(args), Err(ArgumentError::ScanOptionWithKnock)),
+				"{}",
+				args
+			);
+		}
+	}
+
+	#[test]
+	fn knock_script_needs_a_probe()
+	{
+		assert!(matches!(
+			parse("ip_sniffer --knock 1,2 --script 127.0.0.1"),
+			Err(ArgumentError::ScriptWithoutKnockProbe)
+		));
+
+		let arguments = parse("ip_sniffer --knock 1,2 --knock-probe 22 --script 127.0.0.1")
+			.ok()
+			.unwrap();
+
+		assert_eq!(arguments.knock, Some(vec![1, 2]));
+		assert_eq!(arguments.knock_probe, Some(22));
+		assert!(arguments.script);
+	}
+
+	#[test]
+	fn format_and_output_are_parsed()
+	{
+		let arguments = parse("ip_sniffer 127.0.0.1").ok().unwrap();
+
+		assert_eq!(arguments.format, Format::Text);
+		assert_eq!(arguments.output, None);
+
+		let arguments = parse("ip_sniffer --format jsonl --output reports/scan.jsonl 127.0.0.1")
+			.ok()
+			.unwrap();
+
+		assert_eq!(arguments.format, Format::Jsonl);
+		assert_eq!(arguments.output, Some(PathBuf::from("reports/scan.jsonl")));
+
+		assert!(matches!(
+			parse("ip_sniffer --format yaml 127.0.0.1"),
+			Err(ArgumentError::InvalidFormat)
+		));
+		assert!(matches!(
+			parse("ip_sniffer 127.0.0.1 --output"),
+			Err(ArgumentError::NotEnoughArguments)
+		));
+	}
+
+	#[test]
+	fn flags_override_the_config_file()
+	{
+		let path = std::env::temp_dir().join(format!("ip_sniffer_arguments_{}.json", std::process::id()));
+
+		std::fs::write(
+			&path,
+			"{ \"ip_address\": \"10.0.0.1\", \"threads\": 200, \"format\": \"json\", \"resolve\": true }"
+		)
+		.unwrap();
+
+		let arguments = parse(&format!("ip_sniffer --config {} -j 10 --limit 3", path.display()))
+			.ok()
+			.unwrap();
+
+		assert_eq!(arguments.target, Target::Host([10, 0, 0, 1].into()));
+		assert_eq!(arguments.threads, 10);
+		assert_eq!(arguments.limit, Some(3));
+		assert_eq!(arguments.format, Format::Json);
+		assert!(arguments.resolve);
+		assert!(!arguments.tui);
+
+		// The target on the command line replaces the file's, too.
+		let arguments = parse(&format!("ip_sniffer --config {} 127.0.0.1", path.display()))
+			.ok()
+			.unwrap();
+
+		assert_eq!(arguments.target, Target::Host([127, 0, 0, 1].into()));
+		assert_eq!(arguments.threads, 200);
+
+		std::fs::remove_file(&path).unwrap();
+
+		assert!(matches!(
+			parse(&format!("ip_sniffer --config {}", path.display())),
+			Err(ArgumentError::InvalidConfig(_))
+		));
+	}
+
+	#[test]
+	fn config_errors_are_kept_as_the_source()
+	{
+		let error = parse("ip_sniffer --config /nonexistent/ip_sniffer.json").err().unwrap();
+
+		assert!(error::Error::source(&error).unwrap().is::<ConfigError>());
+		assert!(error::Error::source(&ArgumentError::InvalidSyntax).is_none());
+	}
+
+	#[test]
+	fn probe_is_parsed_and_needs_the_port_list()
+	{
+		assert_eq!(parse("ip_sniffer 127.0.0.1").ok().unwrap().probe, None);
+		assert_eq!(
+			parse("ip_sniffer --probe http 127.0.0.1").ok().unwrap().probe,
+			Some(ProbeKind::Http)
+		);
+
+		assert!(matches!(
+			parse("ip_sniffer --probe ftp 127.0.0.1"),
+			Err(ArgumentError::InvalidProbe)
+		));
+		assert!(matches!(
+			parse("ip_sniffer --probe http --count-only 127.0.0.1"),
+			Err(ArgumentError::ProbeWithCountOnly)
+		));
+	}
+
+	#[test]
+	fn resolve_is_off_unless_requested()
+	{
+		assert!(!parse("ip_sniffer 127.0.0.1").ok().unwrap().resolve);
+		assert!(parse("ip_sniffer --resolve 127.0.0.1").ok().unwrap().resolve);
+	}
+
+	#[test]
+	fn networks_are_accepted_for_scanning_only()
+	{
+		let arguments = parse("ip_sniffer 10.0.0.0/30").ok().unwrap();
+
+		assert_eq!(arguments.target.hosts().len(), 2);
+
+		assert!(matches!(
+			parse("ip_sniffer 10.0.0.0/8"),
+			Err(ArgumentError::TooManyHosts)
+		));
+		assert!(matches!(
+			parse("ip_sniffer 10.0.0.0/40"),
+			Err(ArgumentError::InvalidIpAddress)
+		));
+		assert!(matches!(
+			parse("ip_sniffer --tui 10.0.0.0/30"),
+			Err(ArgumentError::NetworkWithKnockOrTui)
+		));
+		assert!(matches!(
+			parse("ip_sniffer --knock 1,2 10.0.0.0/30"),
+			Err(ArgumentError::NetworkWithKnockOrTui)
+		));
+	}
+
+	#[test]
+	fn check_summarises_the_resolved_settings()
+	{
+		let arguments = parse("ip_sniffer --check -j 10 -t 500 --limit 3 10.0.0.0/30")
+			.ok()
+			.unwrap();
+
+		assert!(arguments.check);
+		assert!(!parse("ip_sniffer 127.0.0.1").ok().unwrap().check);
+
+		let summary = arguments.summary();
+
+		for line in [
+			"Target:          10.0.0.0/30",
+			"Hosts:           2 (10.0.0.1 to 10.0.0.2)",
+			"Ports:           65535",
+			"Threads:         10",
+			"Timeout:         500ms",
+			"Max concurrency: unlimited",
+			"Limit:           3"
+		]
+		{
+			assert!(summary.lines().any(|summary_line| summary_line == line), "{}", line);
+		}
+
+		let summary = parse("ip_sniffer --check --knock 1,2 127.0.0.1")
+			.ok()
+			.unwrap()
+			.summary();
+
+		assert!(summary.contains("Hosts:           1\n"));
+		assert!(summary.contains("Knock sequence:  1, 2\n"));
+		assert!(!summary.contains("Threads"));
+	}
+
+	#[test]
+	fn udp_is_a_scan_option_without_probes()
+	{
+		assert_eq!(parse("ip_sniffer 127.0.0.1").ok().unwrap().protocol, Protocol::Tcp);
+		assert_eq!(parse("ip_sniffer -u 127.0.0.1").ok().unwrap().protocol, Protocol::Udp);
+		assert_eq!(
+			parse("ip_sniffer --udp -j 10 127.0.0.1").ok().unwrap().protocol,
+			Protocol::Udp
+		);
+
+		assert!(matches!(
+			parse("ip_sniffer --udp --probe http 127.0.0.1"),
+			Err(ArgumentError::ProbeWithUdp)
+		));
+		assert!(matches!(
+			parse("ip_sniffer --knock 1,2 --udp 127.0.0.1"),
+			Err(ArgumentError::ScanOptionWithKnock)
+		));
+	}
+
+	#[test]
+	fn ports_default_to_all_and_can_be_narrowed()
+	{
+		assert_eq!(parse("ip_sniffer 127.0.0.1").ok().unwrap().ports.len(), 65535);
+		assert_eq!(parse("ip_sniffer -p 80 127.0.0.1").ok().unwrap().ports, [80]);
+
+		let arguments = parse("ip_sniffer -p 20-22,443 -j 2 127.0.0.1").ok().unwrap();
+
+		assert_eq!(arguments.ports, [20, 21, 22, 443]);
+		assert_eq!(arguments.threads, 2);
+
+		assert!(matches!(
+			parse("ip_sniffer -p 1024-20 127.0.0.1"),
+			Err(ArgumentError::InvalidPortRange)
+		));
+		assert!(matches!(
+			parse("ip_sniffer -p 0-80 127.0.0.1"),
+			Err(ArgumentError::InvalidPorts)
+		));
+		assert!(matches!(
+			parse("ip_sniffer --knock 1,2 -p 80 127.0.0.1"),
+			Err(ArgumentError::ScanOptionWithKnock)
+		));
+	}
+
+	#[test]
+	fn zero_threads_permits_and_limits_are_rejected()
+	{
+		assert!(matches!(
+			parse("ip_sniffer -j 0 127.0.0.1"),
+			Err(ArgumentError::InvalidThreadsValue)
+		));
+		assert!(matches!(
+			parse("ip_sniffer --max-concurrency 0 127.0.0.1"),
+			Err(ArgumentError::InvalidConcurrencyValue)
+		));
+		assert!(matches!(
+			parse("ip_sniffer --limit 0 127.0.0.1"),
+			Err(ArgumentError::InvalidLimitValue)
+		));
+	}
+
+	#[test]
+	fn max_concurrency_can_only_lower_the_thread_count()
+	{
+		assert_eq!(
+			parse("ip_sniffer --max-concurrency 10 127.0.0.1")
+				.ok()
+				.unwrap()
+				.max_concurrency,
+			Some(10)
+		);
+		assert_eq!(
+			parse("ip_sniffer -j 200 --max-concurrency 200 127.0.0.1")
+				.ok()
+				.unwrap()
+				.max_concurrency,
+			Some(200)
+		);
+
+		for args in [
+			"ip_sniffer --max-concurrency 51 127.0.0.1",
+			"ip_sniffer -j 10 --max-concurrency 20 127.0.0.1"
+		]
+		{
+			assert!(
+				matches!(parse(args), Err(ArgumentError::ConcurrencyAboveThreads)),
+				"{}",
+				args
+			);
+		}
+	}
+
+	#[test]
+	fn timeout_defaults_to_a_second()
+	{
+		assert_eq!(
+			parse("ip_sniffer 127.0.0.1").ok().unwrap().timeout,
+			Duration::from_millis(1000)
+		);
+		assert_eq!(
+			parse("ip_sniffer -t 250 127.0.0.1").ok().unwrap().timeout,
+			Duration::from_millis(250)
+		);
+
+		for args in ["ip_sniffer -t 0 127.0.0.1", "ip_sniffer -t soon 127.0.0.1"]
+		{
+			assert!(
+				matches!(parse(args), Err(ArgumentError::InvalidTimeoutValue)),
+				"{}",
+				args
+			);
+		}
+	}
+
+	#[test]
+	fn host_names_are_resolved_to_an_address()
+	{
+		let arguments = parse("ip_sniffer localhost").ok().unwrap();
+
+		assert_eq!(arguments.target, Target::Host([127, 0, 0, 1].into()));
+
+		// Without an IPv6 address to prefer, the IPv4 one is still used.
+		let arguments = parse("ip_sniffer localhost --ipv6").ok().unwrap();
+
+		assert!(matches!(arguments.target, Target::Host(ip_address) if ip_address.is_loopback()));
+
+		assert!(matches!(
+			parse("ip_sniffer no-such-host.invalid"),
+			Err(ArgumentError::HostResolutionFailed(host_name)) if host_name == "no-such-host.invalid"
+		));
+		assert!(matches!(
+			parse("ip_sniffer localhost 127.0.0.1"),
+			Err(ArgumentError::TooManyArguments)
+		));
+	}
+
+	#[test]
+	fn service_names_are_shown_unless_turned_off()
+	{
+		assert!(parse("ip_sniffer 127.0.0.1").ok().unwrap().service_names);
+		assert!(
+			!parse("ip_sniffer --no-service-names 127.0.0.1")
+				.ok()
+				.unwrap()
+				.service_names
+		);
+	}
+
+	#[test]
+	fn banner_only_works_with_tcp_port_lists()
+	{
+		assert!(!parse("ip_sniffer 127.0.0.1").ok().unwrap().banner);
+		assert!(parse("ip_sniffer -b 127.0.0.1").ok().unwrap().banner);
+		assert!(
+			parse("ip_sniffer --banner --format json 127.0.0.1")
+				.ok()
+				.unwrap()
+				.banner
+		);
+
+		for args in [
+			"ip_sniffer --banner --udp 127.0.0.1",
+			"ip_sniffer --banner --probe http 127.0.0.1",
+			"ip_sniffer --banner --count-only 127.0.0.1"
+		]
+		{
+			assert!(matches!(parse(args), Err(ArgumentError::BannerConflict)), "{}", args);
+		}
+	}
+
+	#[test]
+	fn progress_is_a_scan_option_the_tui_already_covers()
+	{
+		assert!(!parse("ip_sniffer 127.0.0.1").ok().unwrap().progress);
+		assert!(parse("ip_sniffer --progress 127.0.0.1").ok().unwrap().progress);
+
+		assert!(matches!(
+			parse("ip_sniffer --progress --tui 127.0.0.1"),
+			Err(ArgumentError::ProgressWithTui)
+		));
+		assert!(matches!(
+			parse("ip_sniffer --progress --knock 1,2 127.0.0.1"),
+			Err(ArgumentError::ScanOptionWithKnock)
+		));
+	}
+
+	#[test]
+	fn force_is_off_unless_requested()
+	{
+		assert!(!parse("ip_sniffer 127.0.0.1").ok().unwrap().force);
+		assert!(parse("ip_sniffer --force 127.0.0.1").ok().unwrap().force);
+	}
+}
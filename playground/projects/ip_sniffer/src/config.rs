@@ -0,0 +1,197 @@
+use crate::{
+	ports::Ports,
+	probe::ProbeKind,
+	report::{self, Format},
+	target::Target,
+	Protocol
+};
+use serde::{Deserialize, Serialize};
+use std::{
+	error, fmt, fs, io,
+	path::{Path, PathBuf}
+};
+
+#[derive(Debug)]
+pub enum ConfigError
+{
+	Io(io::Error),
+	Malformed(serde_json::Error)
+}
+
+impl fmt::Display for ConfigError
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		match self
+		{
+			ConfigError::Io(error) => write!(f, "{}", error),
+			ConfigError::Malformed(error) => write!(f, "Malformed config: {}", error)
+		}
+	}
+}
+
+impl error::Error for ConfigError
+{
+	fn source(&self) -> Option<&(dyn error::Error + 'static)>
+	{
+		match self
+		{
+			ConfigError::Io(error) => Some(error),
+			ConfigError::Malformed(error) => Some(error)
+		}
+	}
+}
+
+// Every setting the command line accepts, as read from a config file or from the flags themselves. A setting that
+// wasn't given is None, which is what lets the flags override only the values they actually set, and keeps saved files
+// from pinning defaults that were never chosen.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config
+{
+	#[serde(skip_serializing_if = "Option::is_none")]
+	// Named for the single host it used to be, so older config files still load.
+	pub ip_address: Option<Target>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub protocol:        Option<Protocol>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub ports:           Option<Ports>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub threads:         Option<u16>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub timeout_ms:      Option<u64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub max_concurrency: Option<usize>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub limit:           Option<usize>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub format:          Option<Format>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub output:          Option<PathBuf>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub banner:          Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub count_only:      Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub service_names:   Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub probe:           Option<ProbeKind>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub knock:           Option<Vec<u16>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub knock_delay_ms:  Option<u64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub knock_probe:     Option<u16>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub tui:             Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub progress:        Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub resolve:         Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub script:          Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub force:           Option<bool>
+}
+
+impl Config
+{
+	pub fn load(path: &Path) -> Result<Config, ConfigError>
+	{
+		let json = fs::read_to_string(path).map_err(ConfigError::Io)?;
+
+		serde_json::from_str(&json).map_err(ConfigError::Malformed)
+	}
+
+	pub fn save(&self, path: &Path) -> Result<(), ConfigError>
+	{
+		let json = serde_json::to_string_pretty(self).map_err(ConfigError::Malformed)?;
+
+		let mut file = report::create_output(path).map_err(ConfigError::Io)?;
+
+		io::Write::write_all(&mut file, format!("{}\n", json).as_bytes()).map_err(ConfigError::Io)
+	}
+
+	// Keeps this config's values except where 'overrides' sets one of its own.
+	pub fn overridden_by(self, overrides: Config) -> Config
+	{
+		Config {
+			ip_address:      overrides.ip_address.or(self.ip_address),
+			protocol:        overrides.protocol.or(self.protocol),
+			ports:           overrides.ports.or(self.ports),
+			threads:         overrides.threads.or(self.threads),
+			timeout_ms:      overrides.timeout_ms.or(self.timeout_ms),
+			max_concurrency: overrides.max_concurrency.or(self.max_concurrency),
+			limit:           overrides.limit.or(self.limit),
+			format:          overrides.format.or(self.format),
+			output:          overrides.output.or(self.output),
+			banner:          overrides.banner.or(self.banner),
+			count_only:      overrides.count_only.or(self.count_only),
+			service_names:   overrides.service_names.or(self.service_names),
+			probe:           overrides.probe.or(self.probe),
+			knock:           overrides.knock.or(self.knock),
+			knock_delay_ms:  overrides.knock_delay_ms.or(self.knock_delay_ms),
+			knock_probe:     overrides.knock_probe.or(self.knock_probe),
+			tui:             overrides.tui.or(self.tui),
+			progress:        overrides.progress.or(self.progress),
+			resolve:         overrides.resolve.or(self.resolve),
+			script:          overrides.script.or(self.script),
+			force:           overrides.force.or(self.force)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn overrides_only_replace_the_values_they_set()
+	{
+		let file = Config {
+			threads: Some(200),
+			format: Some(Format::Json),
+			resolve: Some(true),
+			..Config::default()
+		};
+
+		let flags = Config {
+			threads: Some(10),
+			limit: Some(3),
+			..Config::default()
+		};
+
+		let merged = file.overridden_by(flags);
+
+		assert_eq!(merged.threads, Some(10));
+		assert_eq!(merged.limit, Some(3));
+		assert_eq!(merged.format, Some(Format::Json));
+		assert_eq!(merged.resolve, Some(true));
+		assert_eq!(merged.tui, None);
+	}
+
+	#[test]
+	fn saved_configs_load_back_the_same()
+	{
+		let path = std::env::temp_dir().join(format!("ip_sniffer_config_{}.json", std::process::id()));
+
+		let config = Config {
+			ip_address: Some("10.0.0.1".parse().unwrap()),
+			knock: Some(vec![1111, 2222]),
+			knock_probe: Some(22),
+			..Config::default()
+		};
+
+		assert!(config.save(&path).is_ok());
+
+		// Settings that weren't given stay out of the file, so they keep following the defaults.
+		assert!(!fs::read_to_string(&path).unwrap().contains("threads"));
+		assert_eq!(Config::load(&path).ok(), Some(config));
+
+		fs::write(&path, "{ \"thread\": 10 }").unwrap();
+		assert!(matches!(Config::load(&path), Err(ConfigError::Malformed(_))));
+
+		fs::remove_file(&path).unwrap();
+	}
+}
@@ -0,0 +1,658 @@
+pub mod arguments;
+pub mod config;
+pub mod ports;
+pub mod probe;
+pub mod report;
+mod semaphore;
+pub mod services;
+pub mod target;
+
+use semaphore::Semaphore;
+use serde::{Deserialize, Serialize};
+use std::{
+	fmt,
+	io::{ErrorKind, Read},
+	net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		mpsc::{channel, Sender},
+		Arc, Mutex
+	},
+	thread,
+	time::Duration,
+	vec
+};
+
+// What the ports are scanned over.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol
+{
+	Tcp,
+	// Only a closed port answers for certain, so every port that stays silent is reported as open. See 'is_open_udp'.
+	Udp
+}
+
+impl fmt::Display for Protocol
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		match self
+		{
+			Protocol::Tcp => write!(f, "tcp"),
+			Protocol::Udp => write!(f, "udp")
+		}
+	}
+}
+
+#[derive(Clone, Copy)]
+pub struct ScanOptions
+{
+	pub protocol:        Protocol,
+	// How many ports are tried at a time. Never more threads than ports are started.
+	pub threads:         u16,
+	pub timeout:         Duration,
+	// Caps how many connection attempts may be in flight at once across all threads. Without it every thread can
+	// have one attempt open, so the thread count is the concurrency, and a cap above it has no effect.
+	pub max_concurrency: Option<usize>,
+	// Stops the scan once this many open ports have been found.
+	pub limit:           Option<usize>,
+	// Keeps each open TCP connection to read what the service announces, see 'read_banner'. Waits up to the timeout.
+	pub banner:          bool
+}
+
+/// Tries a single TCP connection and reports whether it was accepted within the timeout.
+///
+/// ```
+/// use std::{net::TcpListener, time::Duration};
+///
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let address = listener.local_addr().unwrap();
+///
+/// assert!(ip_sniffer::is_open(address, Duration::from_millis(200)));
+///
+/// drop(listener);
+/// assert!(!ip_sniffer::is_open(address, Duration::from_millis(200)));
+/// ```
+pub fn is_open(socket_address: SocketAddr, timeout: Duration) -> bool
+{
+	TcpStream::connect_timeout(&socket_address, timeout).is_ok()
+}
+
+/// Sends an empty datagram and guesses whether the port is open or filtered from what comes back. UDP has no
+/// handshake, so a closed port is the only one that reliably answers, with an ICMP port unreachable that shows up as a
+/// refused connection. A reply means the port is open, but most services ignore an empty datagram, and a firewall
+/// dropping it looks exactly the same, so silence until the timeout is reported as open too.
+///
+/// ```
+/// use ip_sniffer::is_open_udp;
+/// use std::{net::UdpSocket, time::Duration};
+///
+/// let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+/// let address = socket.local_addr().unwrap();
+///
+/// // The socket never replies, so the port is open or filtered.
+/// assert!(is_open_udp(address, Duration::from_millis(200)));
+///
+/// drop(socket);
+/// assert!(!is_open_udp(address, Duration::from_millis(200)));
+/// ```
+pub fn is_open_udp(socket_address: SocketAddr, timeout: Duration) -> bool
+{
+	let local_address: IpAddr = match socket_address
+	{
+		SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+		SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into()
+	};
+
+	// Connecting the socket is what lets the ICMP error be reported back on it.
+	let answer = UdpSocket::bind(SocketAddr::new(local_address, 0)).and_then(|socket| {
+		socket.connect(socket_address)?;
+		socket.set_read_timeout(Some(timeout))?;
+		socket.send(&[])?;
+		socket.recv(&mut [0; 1]).map(|_| ())
+	});
+
+	match answer
+	{
+		Ok(()) => true,
+		Err(error) => matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+	}
+}
+
+/// Reads the first line a service sends on its own once connected, such as SSH, FTP and SMTP do, reading at most
+/// 512 bytes. Bytes that aren't valid UTF-8 are replaced rather than rejected. Returns None when nothing arrives within
+/// the timeout, which is what most services that wait for the client to speak first do.
+///
+/// ```
+/// use ip_sniffer::read_banner;
+/// use std::{io::Write, net::TcpListener, time::Duration};
+///
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let mut stream = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+/// let (mut server, _) = listener.accept().unwrap();
+///
+/// let timeout = Duration::from_millis(200);
+///
+/// assert_eq!(read_banner(&mut stream, timeout), None);
+///
+/// server.write_all(b"SSH-2.0-OpenSSH_9.6\r\nmore").unwrap();
+///
+/// let banner = read_banner(&mut stream, timeout);
+/// assert_eq!(banner.as_deref(), Some("SSH-2.0-OpenSSH_9.6"));
+/// ```
+pub fn read_banner(stream: &mut TcpStream, timeout: Duration) -> Option<String>
+{
+	stream.set_read_timeout(Some(timeout)).ok()?;
+
+	let mut banner = vec![];
+	let mut buffer = [0; 512];
+
+	// A banner can arrive in pieces, so reading goes on until the line is complete or no more is coming.
+	while banner.len() < buffer.len() && !banner.contains(&b'\n')
+	{
+		match stream.read(&mut buffer[..512 - banner.len()])
+		{
+			Ok(0) | Err(_) => break,
+			Ok(read) => banner.extend_from_slice(&buffer[..read])
+		}
+	}
+
+	let banner = String::from_utf8_lossy(&banner);
+	let line = banner.lines().next()?.trim();
+
+	(!line.is_empty()).then(|| line.to_string())
+}
+
+/// Tries the ports one at a time, in the order given, and returns the first that is open.
+///
+/// ```
+/// use ip_sniffer::first_open;
+/// use std::net::TcpListener;
+///
+/// let bind = || TcpListener::bind("127.0.0.1:0").unwrap();
+/// let port = |listener: &TcpListener| listener.local_addr().unwrap().port();
+///
+/// let closed = port(&bind());
+/// let listener = bind();
+/// let open = port(&listener);
+///
+/// let localhost = [127, 0, 0, 1].into();
+/// let timeout = std::time::Duration::from_millis(200);
+///
+/// assert_eq!(first_open(localhost, &[closed, open], timeout), Some(open));
+/// assert_eq!(first_open(localhost, &[closed], timeout), None);
+/// ```
+pub fn first_open(ip_address: IpAddr, ports: &[u16], timeout: Duration) -> Option<u16>
+{
+	ports
+		.iter()
+		.copied()
+		.find(|port| is_open(SocketAddr::new(ip_address, *port), timeout))
+}
+
+/// Tries the ports all at once and reports whether the host answered on any of them. A refused connection counts as an
+/// answer, since something had to be there to refuse it, so only a host that lets every attempt time out or be reported
+/// unreachable is taken to be down. Takes at most about one timeout.
+///
+/// ```
+/// use ip_sniffer::host_responds;
+/// use std::net::TcpListener;
+///
+/// // Nothing listens on the port once the listener is gone, but the host still refuses the connection.
+/// let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+///
+/// let localhost = [127, 0, 0, 1].into();
+/// let timeout = std::time::Duration::from_millis(200);
+///
+/// assert!(host_responds(localhost, &[closed], timeout));
+/// assert!(!host_responds(localhost, &[], timeout));
+/// ```
+pub fn host_responds(ip_address: IpAddr, ports: &[u16], timeout: Duration) -> bool
+{
+	let (tx, rx) = channel();
+
+	for port in ports.iter().copied()
+	{
+		let tx = tx.clone();
+
+		thread::spawn(move || {
+			let answered = match TcpStream::connect_timeout(&SocketAddr::new(ip_address, port), timeout)
+			{
+				Ok(_) => true,
+				Err(error) => matches!(error.kind(), ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset)
+			};
+
+			// The receiver stops listening after the first answer, so later results have nowhere to go.
+			let _ = tx.send(answered);
+		});
+	}
+
+	drop(tx);
+
+	rx.iter().any(|answered| answered)
+}
+
+/// Looks up the host's addresses and picks the one to scan, an IPv4 address unless 'prefer_ipv6' is set. A host with
+/// addresses of only one kind gets one of those either way. Returns None when the name doesn't resolve.
+///
+/// ```
+/// use ip_sniffer::resolve_host;
+///
+/// assert!(resolve_host("localhost", false).unwrap().is_loopback());
+/// assert_eq!(resolve_host("no-such-host.invalid", false), None);
+/// ```
+pub fn resolve_host(host_name: &str, prefer_ipv6: bool) -> Option<IpAddr>
+{
+	let addresses: Vec<IpAddr> = (host_name, 0)
+		.to_socket_addrs()
+		.ok()?
+		.map(|socket_address| socket_address.ip())
+		.collect();
+
+	addresses
+		.iter()
+		.copied()
+		.find(|address| address.is_ipv6() == prefer_ipv6)
+		.or_else(|| addresses.first().copied())
+}
+
+// Looks up the host name the target's PTR record points to. Any failure, including there being no record at all, is
+// reported as None so it never gets in the way of a scan.
+pub fn reverse_dns(ip_address: IpAddr) -> Option<String>
+{
+	let host_name = dns_lookup::lookup_addr(&ip_address).ok()?;
+
+	// Without a PTR record the lookup falls back to the numeric address, which says nothing new.
+	if host_name == ip_address.to_string()
+	{
+		return None;
+	}
+
+	Some(host_name)
+}
+
+// Scans the given ports on the host and returns the open ones in ascending order once the scan has finished. The
+// threads take the ports from a shared queue one at a time, each trying its port with the given connect timeout.
+pub fn scan_target(ip_address: IpAddr, ports: impl Iterator<Item = u16>, threads: u16, timeout: Duration) -> Vec<u16>
+{
+	let mut open_ports = vec![];
+
+	let options = ScanOptions {
+		protocol: Protocol::Tcp,
+		threads,
+		timeout,
+		max_concurrency: None,
+		limit: None,
+		banner: false
+	};
+
+	scan_host_streaming(ip_address, ports, &options, |port| open_ports.push(port));
+
+	open_ports.sort();
+	open_ports
+}
+
+// The outcome of trying a single port, reported for every port so callers can track progress.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScanEvent
+{
+	// With the port's banner, if the options asked for one and the service sent it.
+	Open(u16, Option<String>),
+	// The host refused the connection, so nothing listens on the port. UDP ports are only ever open or closed, see
+	// 'is_open_udp'.
+	Closed(u16),
+	// Nothing answered before the timeout, which usually means a firewall dropped the attempt.
+	Filtered(u16)
+}
+
+// Like 'scan_target', but calls 'on_open' as soon as each open port is found. Ports arrive in the order the threads
+// discover them, not sorted, which lets callers show results live while the scan is still running.
+pub fn scan_host_streaming(
+	ip_address: IpAddr,
+	ports: impl Iterator<Item = u16>,
+	options: &ScanOptions,
+	mut on_open: impl FnMut(u16)
+)
+{
+	scan_host_events(ip_address, ports, options, |event| {
+		if let ScanEvent::Open(port, _) = event
+		{
+			on_open(port);
+		}
+
+		true
+	});
+}
+
+// Calls 'on_event' with the outcome of every port as it is tried. Returning false from it stops the scan early, and
+// the limit in the options only counts open ports.
+pub fn scan_host_events(
+	ip_address: IpAddr,
+	ports: impl Iterator<Item = u16>,
+	options: &ScanOptions,
+	mut on_event: impl FnMut(ScanEvent) -> bool
+)
+{
+	let ports: Vec<u16> = ports.collect();
+	let threads = ports.len().min(options.threads as usize);
+	let semaphore = options.max_concurrency.map(|permits| Arc::new(Semaphore::new(permits)));
+	let stop = Arc::new(AtomicBool::new(false));
+
+	// The ports still to be tried, shared by a fixed set of threads. Each thread takes the next port as soon as it's
+	// done with its last, so the thread count only decides how many ports are tried at once, and a slow stretch of
+	// ports doesn't hold up a thread that was handed all of them.
+	let queue = Arc::new(Mutex::new(ports.into_iter()));
+
+	// Create a channel for communication between threads.
+	// 'port_sender' is used to send the result of each port from scanning threads to the collecting thread.
+	// 'port_receiver' is used by the collecting thread to receive those results from scanning threads.
+	let (port_sender, port_receiver) = channel();
+
+	for _ in 0..threads
+	{
+		let thread_port_sender = port_sender.clone();
+		let thread_queue = queue.clone();
+		let thread_semaphore = semaphore.clone();
+		let thread_stop = stop.clone();
+		let thread_options = *options;
+
+		thread::spawn(move || {
+			scan(
+				thread_port_sender,
+				thread_queue,
+				ip_address,
+				thread_options,
+				thread_semaphore,
+				thread_stop
+			);
+		});
+	}
+
+	// The original sender is no longer needed at this point, so it can be dropped
+	// to close the channel and allow the receiving loop to terminate.
+	drop(port_sender);
+
+	let mut found = 0;
+
+	for event in port_receiver
+	{
+		if let ScanEvent::Open(..) = event
+		{
+			found += 1;
+		}
+
+		let keep_going = on_event(event);
+
+		if !keep_going || options.limit.is_some_and(|limit| found >= limit)
+		{
+			// Ports other threads are about to report are dropped along with the receiver, so the caller never sees
+			// more than the limit.
+			stop.store(true, Ordering::SeqCst);
+			break;
+		}
+	}
+}
+
+fn scan(
+	tx: Sender<ScanEvent>,
+	queue: Arc<Mutex<vec::IntoIter<u16>>>,
+	ip_address: IpAddr,
+	options: ScanOptions,
+	semaphore: Option<Arc<Semaphore>>,
+	stop: Arc<AtomicBool>
+)
+{
+	while !stop.load(Ordering::SeqCst)
+	{
+		// The queue is only locked long enough to take a port, not while it's being tried.
+		let port = match queue.lock().unwrap().next()
+		{
+			Some(port) => port,
+			None => break
+		};
+
+		let socket_address = SocketAddr::new(ip_address, port);
+
+		// Holding the permit for the duration of the attempt is what bounds the number of simultaneous connections.
+		let permit = semaphore.as_ref().map(|semaphore| semaphore.acquire());
+		let event = attempt(socket_address, &options);
+		drop(permit);
+
+		if tx.send(event).is_err()
+		{
+			break;
+		}
+	}
+}
+
+// Tries one port the way the options say.
+fn attempt(socket_address: SocketAddr, options: &ScanOptions) -> ScanEvent
+{
+	let port = socket_address.port();
+
+	match options.protocol
+	{
+		Protocol::Udp if is_open_udp(socket_address, options.timeout) => ScanEvent::Open(port, None),
+		Protocol::Udp => ScanEvent::Closed(port),
+		Protocol::Tcp =>
+		{
+			match TcpStream::connect_timeout(&socket_address, options.timeout)
+			{
+				Ok(mut stream) =>
+				{
+					let banner = options
+						.banner
+						.then(|| read_banner(&mut stream, options.timeout))
+						.flatten();
+
+					ScanEvent::Open(port, banner)
+				}
+				Err(error) if matches!(error.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) =>
+				{
+					ScanEvent::Filtered(port)
+				}
+				// Refused, or any other answer from the host or a router on the way, which at least isn't silence.
+				Err(_) => ScanEvent::Closed(port)
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::{
+		io::Write,
+		net::{Ipv4Addr, TcpListener}
+	};
+
+	#[test]
+	fn scan_target_finds_a_listening_port()
+	{
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+		let open_port = listener.local_addr().unwrap().port();
+
+		let open_ports = scan_target(
+			IpAddr::V4(Ipv4Addr::LOCALHOST),
+			[open_port].into_iter(),
+			4,
+			Duration::from_millis(200)
+		);
+
+		assert_eq!(open_ports, vec![open_port]);
+	}
+
+	#[test]
+	fn scan_host_streaming_reports_each_open_port()
+	{
+		let listeners: Vec<TcpListener> = (0..3)
+			.map(|_| TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap())
+			.collect();
+		let mut expected: Vec<u16> = listeners
+			.iter()
+			.map(|listener| listener.local_addr().unwrap().port())
+			.collect();
+
+		let mut reported = vec![];
+
+		let options = ScanOptions {
+			protocol:        Protocol::Tcp,
+			threads:         2,
+			timeout:         Duration::from_millis(200),
+			max_concurrency: Some(1),
+			limit:           None,
+			banner:          false
+		};
+
+		scan_host_streaming(
+			IpAddr::V4(Ipv4Addr::LOCALHOST),
+			expected.clone().into_iter(),
+			&options,
+			|port| reported.push(port)
+		);
+
+		expected.sort();
+		reported.sort();
+		assert_eq!(reported, expected);
+	}
+
+	#[test]
+	fn scan_host_events_reports_every_port_until_told_to_stop()
+	{
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+		let open_port = listener.local_addr().unwrap().port();
+
+		// Nothing listens on a port once its listener is dropped.
+		let closed_port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+			.unwrap()
+			.local_addr()
+			.unwrap()
+			.port();
+
+		let options = ScanOptions {
+			protocol:        Protocol::Tcp,
+			threads:         1,
+			timeout:         Duration::from_millis(200),
+			max_concurrency: None,
+			limit:           None,
+			banner:          false
+		};
+
+		let mut events = vec![];
+
+		scan_host_events(
+			IpAddr::V4(Ipv4Addr::LOCALHOST),
+			[open_port, closed_port].into_iter(),
+			&options,
+			|event| {
+				events.push(event);
+				true
+			}
+		);
+
+		assert_eq!(
+			events,
+			vec![ScanEvent::Open(open_port, None), ScanEvent::Closed(closed_port)]
+		);
+
+		let mut events = 0;
+
+		scan_host_events(
+			IpAddr::V4(Ipv4Addr::LOCALHOST),
+			[open_port, closed_port, open_port].into_iter(),
+			&options,
+			|_| {
+				events += 1;
+				false
+			}
+		);
+
+		assert_eq!(events, 1);
+	}
+
+	#[test]
+	fn scan_host_events_tries_each_port_once_whatever_the_thread_count()
+	{
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+		let open_port = listener.local_addr().unwrap().port();
+
+		for threads in [1, 3, 500]
+		{
+			let options = ScanOptions {
+				protocol: Protocol::Tcp,
+				threads,
+				timeout: Duration::from_millis(200),
+				max_concurrency: None,
+				limit: None,
+				banner: false
+			};
+
+			let mut ports = vec![];
+
+			scan_host_events(IpAddr::V4(Ipv4Addr::LOCALHOST), 1..=20, &options, |event| {
+				ports.push(match event
+				{
+					ScanEvent::Open(port, _) | ScanEvent::Closed(port) | ScanEvent::Filtered(port) => port
+				});
+				true
+			});
+
+			ports.sort();
+			assert_eq!(ports, (1..=20).collect::<Vec<u16>>(), "{} threads", threads);
+		}
+
+		assert!(scan_target(
+			IpAddr::V4(Ipv4Addr::LOCALHOST),
+			[open_port].into_iter(),
+			500,
+			Duration::from_millis(200)
+		)
+		.contains(&open_port));
+	}
+
+	#[test]
+	fn scan_host_events_reads_banners_when_asked()
+	{
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+		let port = listener.local_addr().unwrap().port();
+
+		thread::spawn(move || {
+			for mut stream in listener.incoming().map_while(Result::ok)
+			{
+				let _ = stream.write_all(b"220 mail.example.com ESMTP\r\n");
+			}
+		});
+
+		let mut options = ScanOptions {
+			protocol:        Protocol::Tcp,
+			threads:         1,
+			timeout:         Duration::from_millis(500),
+			max_concurrency: None,
+			limit:           None,
+			banner:          true
+		};
+
+		let mut events = vec![];
+
+		scan_host_events(IpAddr::V4(Ipv4Addr::LOCALHOST), [port].into_iter(), &options, |event| {
+			events.push(event);
+			true
+		});
+
+		let banner = Some(String::from("220 mail.example.com ESMTP"));
+
+		assert_eq!(events, vec![ScanEvent::Open(port, banner)]);
+
+		options.banner = false;
+		events.clear();
+
+		scan_host_events(IpAddr::V4(Ipv4Addr::LOCALHOST), [port].into_iter(), &options, |event| {
+			events.push(event);
+			true
+		});
+
+		assert_eq!(events, vec![ScanEvent::Open(port, None)]);
+	}
+}
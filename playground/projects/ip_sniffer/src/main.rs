@@ -1,206 +1,516 @@
+mod tui;
+
+use ip_sniffer::{
+	arguments::{ArgumentError, Arguments},
+	config::ConfigError,
+	host_responds, probe,
+	report::{self, Coverage, Format, HostResult},
+	reverse_dns, scan_host_events, ScanEvent, ScanOptions
+};
 use std::{
-	env, fmt,
+	collections::HashMap,
+	env, error, fmt,
+	fs::File,
+	io::{self, Write},
 	net::{IpAddr, SocketAddr, TcpStream},
-	process,
-	str::FromStr,
-	sync::mpsc::{channel, Sender},
+	path::PathBuf,
+	process::{self, ExitCode},
+	sync::atomic::{AtomicBool, Ordering},
 	thread,
-	time::Duration
+	time::{Duration, Instant}
 };
 
-const MAX: u16 = 65535;
+const KNOCK_TIMEOUT: Duration = Duration::from_millis(500);
+
+// Ports that most hosts answer on, open or refused, when they are up. Refusing counts as answering, so the list only
+// needs one port that isn't silently dropped by a firewall.
+const REACHABILITY_PORTS: [u16; 12] = [21, 22, 23, 25, 53, 80, 135, 139, 443, 445, 3389, 8080];
+
+// Generous, so a slow host isn't mistaken for a missing one. All the ports are tried at once, so this is also about how
+// long the check takes.
+const REACHABILITY_TIMEOUT: Duration = Duration::from_secs(2);
+
+// How long a probe gets to connect, send its request and read the answer, per port.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
 
-enum ArgumentError
+// How often --progress updates its count. Ports can finish by the thousand every second, so not on every one of them.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+// Exit codes. A run that finishes exits with EXIT_SUCCESS, unless --script asks for EXIT_OPEN_PORTS or
+// EXIT_NO_OPEN_PORTS instead. Anything that stops the run early, bad arguments included, exits with EXIT_ERROR, except
+// Ctrl-C, which exits with the shell's usual code for it.
+const EXIT_SUCCESS: u8 = 0;
+const EXIT_OPEN_PORTS: u8 = 0;
+const EXIT_NO_OPEN_PORTS: u8 = 1;
+const EXIT_ERROR: u8 = 2;
+const EXIT_INTERRUPTED: u8 = 130;
+
+// Set by the first Ctrl-C, which stops the scan but still reports what it found.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// Everything that can stop a run before it's done.
+#[derive(Debug)]
+enum Error
 {
-	HelpRequested,
-	TooManyArguments,
-	NotEnoughArguments,
-	InvalidSyntax,
-	InvalidIpAddress,
-	InvalidThreadsValue
+	Arguments(ArgumentError),
+	SaveConfig(PathBuf, ConfigError),
+	Output(PathBuf, io::Error),
+	Report(io::Error),
+	Tui(io::Error),
+	Interrupt(ctrlc::Error)
 }
 
-impl fmt::Display for ArgumentError
+impl fmt::Display for Error
 {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
 	{
 		match self
 		{
-			ArgumentError::HelpRequested => write!(f, "Help requested"),
-			ArgumentError::TooManyArguments => write!(f, "Too many arguments"),
-			ArgumentError::NotEnoughArguments => write!(f, "Not enough arguments"),
-			ArgumentError::InvalidSyntax => write!(f, "Invalid syntax"),
-			ArgumentError::InvalidIpAddress => write!(f, "Invalid IP address"),
-			ArgumentError::InvalidThreadsValue => write!(f, "Invalid threads value")
+			Error::Arguments(error) => write!(f, "problem parsing arguments: {}", error),
+			Error::SaveConfig(path, error) => write!(f, "can't save the config to {}: {}", path.display(), error),
+			Error::Output(path, error) => write!(f, "can't write to {}: {}", path.display(), error),
+			Error::Report(error) => write!(f, "failed to write the report: {}", error),
+			Error::Tui(error) => write!(f, "terminal UI failed: {}", error),
+			Error::Interrupt(error) => write!(f, "can't handle Ctrl-C: {}", error)
+		}
+	}
+}
+
+impl error::Error for Error
+{
+	fn source(&self) -> Option<&(dyn error::Error + 'static)>
+	{
+		match self
+		{
+			Error::Arguments(error) => Some(error),
+			Error::SaveConfig(_, error) => Some(error),
+			Error::Output(_, error) | Error::Report(error) | Error::Tui(error) => Some(error),
+			Error::Interrupt(error) => Some(error)
 		}
 	}
 }
 
-struct Arguments
+impl From<ArgumentError> for Error
 {
-	ip_address: IpAddr,
-	threads:    u16
+	fn from(error: ArgumentError) -> Error { Error::Arguments(error) }
 }
 
-// An 'impl' block in Rust is used to define implementations of methods and associated functions for a particular type.
-// This block can be used for implementing methods for struct and enum definitions, for trait implementations, or for
-// providing associated functions that don’t take self as a parameter.
-impl Arguments
+fn main() -> ExitCode
 {
-	fn new(args: &[String]) -> Result<Arguments, ArgumentError>
+	let args: Vec<String> = env::args().collect();
+
+	match run(&args)
 	{
-		if args.len() < 2
+		Ok(code) => ExitCode::from(code),
+		// The help has already been printed, and asking for it isn't a mistake.
+		Err(Error::Arguments(ArgumentError::HelpRequested)) => ExitCode::from(EXIT_SUCCESS),
+		Err(error) =>
 		{
-			return Err(ArgumentError::NotEnoughArguments);
+			eprintln!("{} {}", args[0], error);
+			ExitCode::from(EXIT_ERROR)
 		}
-		else if args.len() > 4
+	}
+}
+
+// Carries out the command line and returns the code to exit with.
+fn run(args: &[String]) -> Result<u8, Error>
+{
+	let arguments = Arguments::new(args)?;
+
+	if arguments.check
+	{
+		print!("{}", arguments.summary());
+		return Ok(EXIT_SUCCESS);
+	}
+
+	if let Some(path) = &arguments.save_config
+	{
+		arguments
+			.config
+			.save(path)
+			.map_err(|error| Error::SaveConfig(path.clone(), error))?;
+	}
+
+	// Knocking is a separate mode from scanning, so it skips the worker threads entirely.
+	if let Some(sequence) = &arguments.knock
+	{
+		// Parsing only allows knocking on a single host.
+		let ip_address = arguments.target.hosts()[0];
+		let reachable = knock(ip_address, sequence, arguments.knock_delay, arguments.knock_probe);
+
+		// Parsing guarantees a probe port in script mode, so there is always a result to report.
+		return Ok(match (arguments.script, reachable)
 		{
-			return Err(ArgumentError::TooManyArguments);
+			(true, Some(true)) => EXIT_OPEN_PORTS,
+			(true, _) => EXIT_NO_OPEN_PORTS,
+			(false, _) => EXIT_SUCCESS
+		});
+	}
+
+	// Opened before scanning so an unwritable path fails fast rather than after the whole scan.
+	let mut output = match &arguments.output
+	{
+		Some(path) => Some(report::create_output(path).map_err(|error| Error::Output(path.clone(), error))?),
+		None => None
+	};
+
+	// A JSON report on stdout has to be the only thing there, or it can't be parsed. Counts replace the per-port
+	// output, so progress is left out for them as well.
+	let quiet = (output.is_none() && arguments.format != Format::Text) || arguments.count_only;
+
+	// A network ends with a table covering every host, which is also its whole report in JSON, since a report per host
+	// on top of it couldn't be parsed as one document. The table already has the counts, too.
+	let network = arguments.target.is_network();
+	let report_each_host = !network || (arguments.format == Format::Text && !arguments.count_only);
+
+	// Ctrl-C stops the scan rather than the program, so the ports found so far still get reported. A second one, e.g.
+	// while the open ports are being probed, exits straight away.
+	ctrlc::set_handler(|| {
+		if INTERRUPTED.swap(true, Ordering::SeqCst)
+		{
+			process::exit(EXIT_INTERRUPTED as i32);
 		}
+	})
+	.map_err(Error::Interrupt)?;
+
+	let mut results = vec![];
 
-		let flag = args[1].clone();
+	// Totals across every host scanned, for the summary at the end.
+	let started = Instant::now();
+	let mut open_total = 0;
+	let mut filtered_total = 0;
+	let mut scanned_total = 0;
 
-		match IpAddr::from_str(&flag)
+	for ip_address in arguments.target.hosts()
+	{
+		// The rest of a network is left out of the table rather than listed as unscanned.
+		if INTERRUPTED.load(Ordering::SeqCst)
 		{
-			Ok(ip_address) =>
+			break;
+		}
+
+		// A host that is down lets every port time out, which would otherwise be reported as a host with no open ports.
+		let up = arguments.force || host_responds(ip_address, &REACHABILITY_PORTS, REACHABILITY_TIMEOUT);
+
+		// The run still finished as asked, so only --script, which reports whether open ports were found, exits with
+		// anything but success. None were.
+		if !up && !network
+		{
+			eprintln!(
+				"{} host appears down: none of ports {:?} answered, skipping the scan (use --force to scan anyway)",
+				ip_address, REACHABILITY_PORTS
+			);
+
+			return Ok(match arguments.script
+			{
+				true => EXIT_NO_OPEN_PORTS,
+				false => EXIT_SUCCESS
+			});
+		}
+
+		if !up
+		{
+			if !quiet
 			{
-				return Ok(Arguments {
-					ip_address,
-					threads: 50
-				})
+				println!("{} appears down, skipping it", ip_address);
 			}
-			Err(_) =>
+
+			results.push(HostResult {
+				ip_address,
+				up,
+				open: None
+			});
+
+			continue;
+		}
+
+		let scan = scan_host(ip_address, &arguments, quiet)?;
+		let coverage = Coverage {
+			scanned:  scan.scanned,
+			filtered: &scan.filtered_ports,
+			duration: scan.duration
+		};
+
+		let render = |format| {
+			match (arguments.count_only, arguments.probe)
 			{
-				if flag.contains("-h") || flag.contains("-help")
+				(true, _) =>
 				{
-					if args.len() == 2
-					{
-						// The ! in println! indicates that println is a macro in Rust, not a regular function.
-						// Macros allow for code generation or metaprogramming at compile time, offering more
-						// flexibility than functions, such as accepting a variable number of arguments.
-						println!(
-							"Usage:
-                            \r\n-j to select number of threads. 
-                            \r\n-h or -help to show this help message"
-						);
-						return Err(ArgumentError::HelpRequested);
-					}
-					else
-					{
-						return Err(ArgumentError::TooManyArguments);
-					}
+					report::render_counts(
+						format,
+						ip_address,
+						scan.open_ports.len(),
+						scan.filtered_ports.len(),
+						scan.scanned
+					)
 				}
-				else if flag.contains("-j")
+				(false, Some(kind)) =>
 				{
-					let threads = match args[2].parse::<u16>()
-					{
-						Ok(parsed_vale) => parsed_vale,
-						Err(_) => return Err(ArgumentError::InvalidThreadsValue)
-					};
-
-					let ip_address = match IpAddr::from_str(&args[3])
-					{
-						Ok(parsed_vale) => parsed_vale,
-						Err(_) => return Err(ArgumentError::InvalidIpAddress)
-					};
-
-					return Ok(Arguments { ip_address, threads });
+					report::render_probed(
+						format,
+						ip_address,
+						&scan.open_ports,
+						coverage,
+						arguments.service_names,
+						kind,
+						&scan.confirmed
+					)
 				}
-				else
+				(false, None) =>
 				{
-					return Err(ArgumentError::InvalidSyntax);
+					report::render(
+						format,
+						ip_address,
+						&scan.open_ports,
+						coverage,
+						arguments.service_names,
+						arguments.banner.then_some(&scan.banners)
+					)
 				}
 			}
-		}
-	}
-}
+		};
 
-fn main()
-{
-	let args: Vec<String> = env::args().collect();
-
-	let program = args[0].clone();
-
-	let arguments = Arguments::new(&args).unwrap_or_else(|error| {
-		match error
+		if !network
 		{
-			ArgumentError::HelpRequested => process::exit(0),
-			_ =>
-			{
-				eprintln!("{} problem parsing arguments: {}", program, error);
-				process::exit(0);
-			}
+			write_report(&mut output, render(arguments.format), render(Format::Text))?;
+		}
+		else if report_each_host
+		{
+			print!("{}", render(Format::Text));
 		}
-	});
-
-	let number_of_threads = arguments.threads;
-	let ip_address = arguments.ip_address;
-
-	// Create a channel for communication between threads.
-	// 'port_sender' is used to send open port numbers from scanning threads to the main thread.
-	// 'port_receiver' is used by the main thread to receive open port numbers from scanning threads.
-	let (port_sender, port_receiver) = channel();
 
-	for thread_index in 0..number_of_threads
-	{
-		let thread_port_sender = port_sender.clone();
+		open_total += scan.open_ports.len();
+		filtered_total += scan.filtered_ports.len();
+		scanned_total += scan.scanned;
 
-		thread::spawn(move || {
-			scan(thread_port_sender, thread_index, ip_address, number_of_threads);
+		results.push(HostResult {
+			ip_address,
+			up,
+			open: Some(scan.open_ports.len())
 		});
 	}
 
-	let mut open_ports = vec![];
-
-	// The original sender is no longer needed at this point, so it can be dropped
-	// to close the channel and allow the receiving loop to terminate.
-	drop(port_sender);
-
-	for port in port_receiver
+	if network
 	{
-		open_ports.push(port);
+		write_report(
+			&mut output,
+			report::render_summary(arguments.format, &results),
+			report::render_summary(Format::Text, &results)
+		)?;
 	}
 
-	println!("");
+	let interrupted = INTERRUPTED.load(Ordering::SeqCst);
 
-	open_ports.sort();
+	// On stderr so it never mixes with a report on stdout, whatever its format.
+	eprintln!(
+		"Scan {}: {} open, {} closed, {} filtered, {} scanned in {:.1}s",
+		if interrupted { "interrupted" } else { "finished" },
+		open_total,
+		scanned_total - open_total - filtered_total,
+		filtered_total,
+		scanned_total,
+		started.elapsed().as_secs_f64()
+	);
+
+	let any_open = results.iter().any(|result| result.open.unwrap_or(0) > 0);
 
-	for port in open_ports
+	if interrupted
 	{
-		println!("{} is open", port);
+		return Ok(EXIT_INTERRUPTED);
 	}
+
+	Ok(match (arguments.script, any_open)
+	{
+		(true, true) => EXIT_OPEN_PORTS,
+		(true, false) => EXIT_NO_OPEN_PORTS,
+		(false, _) => EXIT_SUCCESS
+	})
+}
+
+// What scanning one host found.
+struct HostScan
+{
+	open_ports:     Vec<u16>,
+	// The ports that let the connection time out rather than refusing it.
+	filtered_ports: Vec<u16>,
+	// What each open port announced, for the ports that did, when banners were asked for.
+	banners:        HashMap<u16, String>,
+	// The open ports the probe confirmed, if there was one.
+	confirmed:      Vec<u16>,
+	scanned:        usize,
+	// How long the ports took, not counting the probes.
+	duration:       Duration
 }
 
-fn scan(tx: Sender<u16>, start_port: u16, ip_address: IpAddr, number_of_threads: u16)
+// Scans every port on the host, showing progress unless 'quiet', then probes the open ones if asked to.
+fn scan_host(ip_address: IpAddr, arguments: &Arguments, quiet: bool) -> Result<HostScan, Error>
 {
-	let mut port = start_port + 1;
-	loop
+	let target = match arguments.resolve.then(|| reverse_dns(ip_address)).flatten()
 	{
-		let socket_address = SocketAddr::new(ip_address, port);
-		let timeout = Duration::from_nanos(1);
+		Some(host_name) => format!("{} ({})", ip_address, host_name),
+		None => ip_address.to_string()
+	};
+
+	let options = ScanOptions {
+		protocol:        arguments.protocol,
+		threads:         arguments.threads,
+		timeout:         arguments.timeout,
+		max_concurrency: arguments.max_concurrency,
+		limit:           arguments.limit,
+		banner:          arguments.banner
+	};
+
+	let started = Instant::now();
 
-		match TcpStream::connect_timeout(&socket_address, timeout)
+	let (mut open_ports, mut filtered_ports, banners, scanned) = match arguments.tui
+	{
+		true =>
 		{
-			Ok(_) =>
+			tui::run(
+				target,
+				ip_address,
+				arguments.ports.clone(),
+				options,
+				arguments.service_names
+			)
+			.map(|scan| (scan.open_ports, scan.filtered_ports, scan.banners, scan.scanned))
+			.map_err(Error::Tui)?
+		}
+		false =>
+		{
+			if !quiet
 			{
-				print!(".");
+				println!("Scanning {}", target);
+			}
+
+			let mut open_ports = vec![];
+			let mut filtered_ports = vec![];
+			let mut banners = HashMap::new();
+			let mut scanned = 0;
+			let mut last_progress = Instant::now();
+
+			scan_host_events(ip_address, arguments.ports.iter().copied(), &options, |event| {
+				scanned += 1;
 
-				if tx.send(port).is_err()
+				if arguments.progress && last_progress.elapsed() >= PROGRESS_INTERVAL
 				{
-					break;
+					eprint!("\r{}: {}/{} ports scanned", ip_address, scanned, arguments.ports.len());
+					last_progress = Instant::now();
 				}
+
+				match event
+				{
+					ScanEvent::Open(port, banner) =>
+					{
+						// The count on stderr already shows the scan moving, and the dots would break up its line.
+						if !quiet && !arguments.progress
+						{
+							print!(".");
+						}
+
+						open_ports.push(port);
+						banners.extend(banner.map(|banner| (port, banner)));
+					}
+					ScanEvent::Filtered(port) => filtered_ports.push(port),
+					ScanEvent::Closed(_) => ()
+				}
+
+				!INTERRUPTED.load(Ordering::SeqCst)
+			});
+
+			if !quiet
+			{
+				println!();
 			}
-			Err(_) =>
+
+			// The final count, which the interval may have skipped, and the end of its line.
+			if arguments.progress
 			{
-				// println!("Closed {} is unavailable", port);
+				eprintln!("\r{}: {}/{} ports scanned", ip_address, scanned, arguments.ports.len());
 			}
+
+			(open_ports, filtered_ports, banners, scanned)
 		}
+	};
 
-		if MAX - port <= number_of_threads
+	let duration = started.elapsed();
+
+	open_ports.sort();
+	filtered_ports.sort();
+
+	// Probing happens once the scan is over, so it doesn't compete with the scan for connections.
+	let confirmed: Vec<u16> = match arguments.probe
+	{
+		Some(kind) =>
 		{
-			break;
+			let probe = kind.probe();
+
+			open_ports
+				.iter()
+				.copied()
+				.filter(|port| probe::confirm(SocketAddr::new(ip_address, *port), probe.as_ref(), PROBE_TIMEOUT))
+				.collect()
+		}
+		None => vec![]
+	};
+
+	Ok(HostScan {
+		open_ports,
+		filtered_ports,
+		banners,
+		confirmed,
+		scanned,
+		duration
+	})
+}
+
+// Sends the report to the output file, with the text version shown in the terminal alongside it, or to stdout when
+// there is no file.
+fn write_report(output: &mut Option<File>, rendered: String, text: String) -> Result<(), Error>
+{
+	match output
+	{
+		Some(file) =>
+		{
+			print!("{}", text);
+
+			file.write_all(rendered.as_bytes()).map_err(Error::Report)
 		}
+		None =>
+		{
+			print!("{}", rendered);
+			Ok(())
+		}
+	}
+}
 
-		port += number_of_threads;
+// Returns whether the probe port became reachable, or None when there was nothing to probe.
+fn knock(ip_address: IpAddr, sequence: &[u16], delay: Duration, probe: Option<u16>) -> Option<bool>
+{
+	for (index, port) in sequence.iter().enumerate()
+	{
+		println!("Knocking on {}", port);
+
+		// Only the connection attempt matters to the knock daemon, so whether the port answers is ignored.
+		let _ = TcpStream::connect_timeout(&SocketAddr::new(ip_address, *port), KNOCK_TIMEOUT);
+
+		if index + 1 < sequence.len()
+		{
+			thread::sleep(delay);
+		}
+	}
+
+	let port = probe?;
+
+	thread::sleep(delay);
+
+	let reachable = TcpStream::connect_timeout(&SocketAddr::new(ip_address, port), KNOCK_TIMEOUT).is_ok();
+
+	match reachable
+	{
+		true => println!("{} is reachable after knocking", port),
+		false => println!("{} is still unreachable after knocking", port)
 	}
+
+	Some(reachable)
 }
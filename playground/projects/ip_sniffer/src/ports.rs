@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, ops::RangeInclusive, str::FromStr};
+
+#[derive(Debug, PartialEq)]
+pub enum PortsError
+{
+	Invalid,
+	// A range written high to low, e.g. 1024-20.
+	ReversedRange
+}
+
+impl fmt::Display for PortsError
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		match self
+		{
+			PortsError::Invalid => write!(f, "expected ports from 1 to 65535 like 80,443 or 20-1024"),
+			PortsError::ReversedRange => write!(f, "a range has to go from the lower port to the higher one")
+		}
+	}
+}
+
+// The ports to scan, as a comma-separated list of single ports and ranges such as "22,80,8000-8100". Saved in config
+// files the way it is written on the command line.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Ports
+{
+	// In the order they were given. A single port is a range of one.
+	ranges: Vec<RangeInclusive<u16>>
+}
+
+impl Ports
+{
+	pub fn all() -> Ports
+	{
+		Ports {
+			ranges: vec![1..=u16::MAX]
+		}
+	}
+
+	// The given ports with each run of consecutive ports as a single range, so a long list can be written out
+	// compactly, e.g. 1-21,23-79.
+	pub fn collapse(ports: &[u16]) -> Ports
+	{
+		let mut ports = ports.to_vec();
+
+		ports.sort();
+		ports.dedup();
+
+		let mut ranges: Vec<RangeInclusive<u16>> = vec![];
+
+		for port in ports
+		{
+			match ranges.last_mut()
+			{
+				Some(range) if *range.end() + 1 == port => *range = *range.start()..=port,
+				_ => ranges.push(port..=port)
+			}
+		}
+
+		Ports { ranges }
+	}
+
+	// Every port in the set once, in ascending order, however the ranges overlap.
+	pub fn ports(&self) -> Vec<u16>
+	{
+		let mut ports: Vec<u16> = self.ranges.iter().cloned().flatten().collect();
+
+		ports.sort();
+		ports.dedup();
+		ports
+	}
+}
+
+impl FromStr for Ports
+{
+	type Err = PortsError;
+
+	fn from_str(ports: &str) -> Result<Ports, PortsError>
+	{
+		let port = |port: &str| {
+			match port.trim().parse::<u16>()
+			{
+				Ok(0) | Err(_) => Err(PortsError::Invalid),
+				Ok(port) => Ok(port)
+			}
+		};
+
+		let ranges = ports
+			.split(',')
+			.map(|range| {
+				match range.split_once('-')
+				{
+					Some((start, end)) =>
+					{
+						let (start, end) = (port(start)?, port(end)?);
+
+						if start > end
+						{
+							return Err(PortsError::ReversedRange);
+						}
+
+						Ok(start..=end)
+					}
+					None => port(range).map(|port| port..=port)
+				}
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+
+		Ok(Ports { ranges })
+	}
+}
+
+impl fmt::Display for Ports
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		let ranges: Vec<String> = self
+			.ranges
+			.iter()
+			.map(|range| {
+				match range.start() == range.end()
+				{
+					true => range.start().to_string(),
+					false => format!("{}-{}", range.start(), range.end())
+				}
+			})
+			.collect();
+
+		write!(f, "{}", ranges.join(","))
+	}
+}
+
+impl TryFrom<String> for Ports
+{
+	type Error = PortsError;
+
+	fn try_from(ports: String) -> Result<Ports, PortsError> { ports.parse() }
+}
+
+impl From<Ports> for String
+{
+	fn from(ports: Ports) -> String { ports.to_string() }
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn lists_and_ranges_are_parsed()
+	{
+		assert_eq!("80".parse::<Ports>().unwrap().ports(), [80]);
+		assert_eq!("20-23".parse::<Ports>().unwrap().ports(), [20, 21, 22, 23]);
+
+		// Overlaps are only scanned once, and everything comes out sorted.
+		let ports: Ports = "8080, 22-24,23,80".parse().unwrap();
+
+		assert_eq!(ports.ports(), [22, 23, 24, 80, 8080]);
+		assert_eq!(ports.to_string(), "8080,22-24,23,80");
+
+		assert_eq!(Ports::all().ports().len(), 65535);
+	}
+
+	#[test]
+	fn consecutive_ports_collapse_into_ranges()
+	{
+		assert_eq!(
+			Ports::collapse(&[80, 1, 2, 3, 22, 2, 65535]).to_string(),
+			"1-3,22,80,65535"
+		);
+		assert_eq!(Ports::collapse(&Ports::all().ports()), Ports::all());
+		assert_eq!(Ports::collapse(&[]).to_string(), "");
+	}
+
+	#[test]
+	fn bad_ports_are_rejected()
+	{
+		assert_eq!("1024-20".parse::<Ports>(), Err(PortsError::ReversedRange));
+
+		for ports in ["", "0", "65536", "80,", "http", "1-", "1-2-3"]
+		{
+			assert_eq!(ports.parse::<Ports>(), Err(PortsError::Invalid), "{}", ports);
+		}
+	}
+}
@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+use std::{
+	fmt,
+	io::{self, ErrorKind, Read, Write},
+	net::{SocketAddr, TcpStream},
+	str::FromStr,
+	time::{Duration, Instant}
+};
+
+// Checks that an open port is running a particular service by talking to it. Each check is handed a connected stream
+// and a deadline, and has to give up once the deadline passes.
+pub trait Probe
+{
+	fn check(&self, stream: &mut TcpStream, deadline: Instant) -> io::Result<bool>;
+}
+
+// A minimal HTTP/1.0 request, which any HTTP server answers with a status line starting with "HTTP/".
+pub struct HttpProbe;
+
+impl Probe for HttpProbe
+{
+	fn check(&self, stream: &mut TcpStream, deadline: Instant) -> io::Result<bool>
+	{
+		stream.write_all(b"GET / HTTP/1.0\r\n\r\n")?;
+
+		let mut response = [0; 5];
+		read_before(stream, &mut response, deadline)?;
+
+		Ok(&response == b"HTTP/")
+	}
+}
+
+// The probes that can be asked for by name on the command line.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeKind
+{
+	Http
+}
+
+impl ProbeKind
+{
+	pub fn probe(&self) -> Box<dyn Probe>
+	{
+		match self
+		{
+			ProbeKind::Http => Box::new(HttpProbe)
+		}
+	}
+}
+
+impl FromStr for ProbeKind
+{
+	type Err = ();
+
+	fn from_str(kind: &str) -> Result<ProbeKind, ()>
+	{
+		match kind
+		{
+			"http" => Ok(ProbeKind::Http),
+			_ => Err(())
+		}
+	}
+}
+
+impl fmt::Display for ProbeKind
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		match self
+		{
+			ProbeKind::Http => write!(f, "http")
+		}
+	}
+}
+
+// Connects and runs the probe, all within 'timeout'. Anything going wrong, including the service not answering in
+// time, counts as the service not being confirmed.
+pub fn confirm(socket_address: SocketAddr, probe: &dyn Probe, timeout: Duration) -> bool
+{
+	let deadline = Instant::now() + timeout;
+
+	let mut stream = match TcpStream::connect_timeout(&socket_address, timeout)
+	{
+		Ok(stream) => stream,
+		Err(_) => return false
+	};
+
+	if stream.set_write_timeout(Some(timeout)).is_err()
+	{
+		return false;
+	}
+
+	probe.check(&mut stream, deadline).unwrap_or(false)
+}
+
+// Fills the buffer, giving up at the deadline even if the peer keeps trickling in a byte at a time.
+fn read_before(stream: &mut TcpStream, buffer: &mut [u8], deadline: Instant) -> io::Result<()>
+{
+	let mut filled = 0;
+
+	while filled < buffer.len()
+	{
+		let remaining = deadline.saturating_duration_since(Instant::now());
+
+		if remaining.is_zero()
+		{
+			return Err(io::Error::from(ErrorKind::TimedOut));
+		}
+
+		stream.set_read_timeout(Some(remaining))?;
+
+		match stream.read(&mut buffer[filled..])?
+		{
+			0 => return Err(io::Error::from(ErrorKind::UnexpectedEof)),
+			read => filled += read
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::{net::TcpListener, thread};
+
+	const TIMEOUT: Duration = Duration::from_millis(500);
+
+	// A server that reads the request and answers every connection with the given bytes, or says nothing at all when
+	// there are none.
+	fn serve(answer: &'static [u8]) -> (SocketAddr, thread::JoinHandle<()>)
+	{
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let address = listener.local_addr().unwrap();
+
+		let server = thread::spawn(move || {
+			let (mut stream, _) = listener.accept().unwrap();
+			let mut request = [0; 18];
+
+			stream.read_exact(&mut request).unwrap();
+
+			match answer.is_empty()
+			{
+				true => thread::sleep(TIMEOUT * 2),
+				false => stream.write_all(answer).unwrap()
+			}
+		});
+
+		(address, server)
+	}
+
+	#[test]
+	fn http_is_confirmed_by_a_status_line()
+	{
+		let (address, server) = serve(b"HTTP/1.0 200 OK\r\n\r\n");
+
+		assert!(confirm(address, &HttpProbe, TIMEOUT));
+		server.join().unwrap();
+
+		let (address, server) = serve(b"SSH-2.0-OpenSSH_9.6\r\n");
+
+		assert!(!confirm(address, &HttpProbe, TIMEOUT));
+		server.join().unwrap();
+	}
+
+	#[test]
+	fn a_silent_service_gives_up_at_the_timeout()
+	{
+		let (address, server) = serve(b"");
+		let started = Instant::now();
+
+		assert!(!confirm(address, &HttpProbe, TIMEOUT));
+		assert!(started.elapsed() < TIMEOUT * 2);
+
+		server.join().unwrap();
+	}
+
+	#[test]
+	fn probe_kinds_are_parsed_by_name()
+	{
+		assert_eq!("http".parse(), Ok(ProbeKind::Http));
+		assert_eq!("tls".parse::<ProbeKind>(), Err(()));
+		assert_eq!(ProbeKind::Http.to_string(), "http");
+	}
+}
@@ -0,0 +1,564 @@
+use crate::{ports::Ports, probe::ProbeKind, services::service_name};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+	collections::HashMap,
+	fmt,
+	fs::{self, File},
+	io,
+	net::IpAddr,
+	path::Path,
+	str::FromStr,
+	time::Duration
+};
+
+// How the open ports found by a scan are written out.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format
+{
+	// One "<port> is open" line per port, as the sniffer has always printed.
+	Text,
+	// A single object with the target and the list of open ports.
+	Json,
+	// One object per open port on its own line, which is easy to append to and to stream through other tools.
+	Jsonl
+}
+
+impl FromStr for Format
+{
+	type Err = ();
+
+	fn from_str(format: &str) -> Result<Format, ()>
+	{
+		match format
+		{
+			"text" => Ok(Format::Text),
+			"json" => Ok(Format::Json),
+			"jsonl" => Ok(Format::Jsonl),
+			_ => Err(())
+		}
+	}
+}
+
+impl fmt::Display for Format
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		match self
+		{
+			Format::Text => write!(f, "text"),
+			Format::Json => write!(f, "json"),
+			Format::Jsonl => write!(f, "jsonl")
+		}
+	}
+}
+
+// How much of the host a finished scan got through, which the JSON report includes alongside the open ports.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Coverage<'a>
+{
+	pub scanned:  usize,
+	// The ports that let the connection time out, so the scan couldn't tell whether anything listens on them.
+	pub filtered: &'a [u16],
+	pub duration: Duration
+}
+
+// The text line for an open port, e.g. "22 is open (ssh)", with the service only shown for well-known ports and only
+// if 'service_names' is set. 'notes' go in the same parentheses, after the service, and the banner the port sent, if
+// any, at the end.
+pub fn open_port_line(port: u16, service_names: bool, notes: &[&str], banner: Option<&str>) -> String
+{
+	let service = service_names.then(|| service_name(port)).flatten();
+	let notes: Vec<&str> = service.into_iter().chain(notes.iter().copied()).collect();
+
+	let line = match notes.is_empty()
+	{
+		true => format!("{} is open", port),
+		false => format!("{} is open ({})", port, notes.join(", "))
+	};
+
+	match banner
+	{
+		Some(banner) => format!("{}: {}", line, banner),
+		None => line
+	}
+}
+
+// The text report's list of filtered ports under a heading of their own, or nothing when there were none. A firewall
+// can drop thousands of ports, so runs of them are written as ranges.
+fn filtered_section(filtered_ports: &[u16]) -> String
+{
+	match filtered_ports.is_empty()
+	{
+		true => String::new(),
+		false => format!("Filtered (no response):\n{}\n", Ports::collapse(filtered_ports))
+	}
+}
+
+// Renders the open ports of a finished scan, ending with a newline, along with the filtered ones from 'coverage' in
+// the text and JSON formats. JSON Lines stays one line per open port. The text format names the usual service on
+// well-known ports when 'service_names' is set. 'banners' is None unless they were read, in which case every format
+// includes them, ports that sent nothing as null in JSON.
+pub fn render(
+	format: Format,
+	ip_address: IpAddr,
+	open_ports: &[u16],
+	coverage: Coverage,
+	service_names: bool,
+	banners: Option<&HashMap<u16, String>>
+) -> String
+{
+	let banner = |port: &u16| banners.and_then(|banners| banners.get(port)).map(String::as_str);
+
+	let mut report = String::new();
+
+	match format
+	{
+		Format::Text =>
+		{
+			for port in open_ports
+			{
+				report.push_str(&format!(
+					"{}\n",
+					open_port_line(*port, service_names, &[], banner(port))
+				));
+			}
+
+			report.push_str(&filtered_section(coverage.filtered));
+		}
+		Format::Json =>
+		{
+			let mut scan = json!({
+				"ip": ip_address.to_string(),
+				"open_ports": open_ports,
+				"filtered_ports": coverage.filtered,
+				"scanned": coverage.scanned,
+				"duration_ms": coverage.duration.as_millis() as u64
+			});
+
+			if banners.is_some()
+			{
+				let banners: serde_json::Map<String, serde_json::Value> = open_ports
+					.iter()
+					.map(|port| (port.to_string(), json!(banner(port))))
+					.collect();
+
+				scan["banners"] = banners.into();
+			}
+
+			report.push_str(&format!("{:#}\n", scan));
+		}
+		Format::Jsonl =>
+		{
+			for port in open_ports
+			{
+				let mut line = json!({ "ip": ip_address.to_string(), "port": port });
+
+				if banners.is_some()
+				{
+					line["banner"] = json!(banner(port));
+				}
+
+				report.push_str(&format!("{}\n", line));
+			}
+		}
+	}
+
+	report
+}
+
+// Like 'render', but also says for each open port whether the probe confirmed the service. 'confirmed' holds the
+// ports where it did.
+pub fn render_probed(
+	format: Format,
+	ip_address: IpAddr,
+	open_ports: &[u16],
+	coverage: Coverage,
+	service_names: bool,
+	kind: ProbeKind,
+	confirmed: &[u16]
+) -> String
+{
+	let mut report = String::new();
+
+	match format
+	{
+		Format::Text =>
+		{
+			for port in open_ports
+			{
+				let outcome = match confirmed.contains(port)
+				{
+					true => format!("{} confirmed", kind),
+					false => format!("no {} response", kind)
+				};
+
+				report.push_str(&format!(
+					"{}\n",
+					open_port_line(*port, service_names, &[&outcome], None)
+				));
+			}
+
+			report.push_str(&filtered_section(coverage.filtered));
+		}
+		Format::Json =>
+		{
+			let scan = json!({
+				"ip": ip_address.to_string(),
+				"open_ports": open_ports,
+				"filtered_ports": coverage.filtered,
+				"scanned": coverage.scanned,
+				"duration_ms": coverage.duration.as_millis() as u64,
+				"probe": kind,
+				"confirmed": confirmed
+			});
+
+			report.push_str(&format!("{:#}\n", scan));
+		}
+		Format::Jsonl =>
+		{
+			for port in open_ports
+			{
+				let line = json!({
+					"ip": ip_address.to_string(),
+					"port": port,
+					"probe": kind,
+					"confirmed": confirmed.contains(port)
+				});
+
+				report.push_str(&format!("{}\n", line));
+			}
+		}
+	}
+
+	report
+}
+
+// Renders just the tallies of a finished scan, for when the individual ports don't matter. Every port that was tried
+// and neither accepted the connection nor let it time out counts as closed.
+pub fn render_counts(format: Format, ip_address: IpAddr, open: usize, filtered: usize, scanned: usize) -> String
+{
+	let closed = scanned.saturating_sub(open + filtered);
+
+	let counts = json!({
+		"ip": ip_address.to_string(),
+		"open": open,
+		"closed": closed,
+		"filtered": filtered,
+		"scanned": scanned
+	});
+
+	match format
+	{
+		Format::Text =>
+		{
+			format!(
+				"{} open, {} closed, {} filtered, {} scanned\n",
+				open, closed, filtered, scanned
+			)
+		}
+		Format::Json => format!("{:#}\n", counts),
+		Format::Jsonl => format!("{}\n", counts)
+	}
+}
+
+// How one host fared in a scan of a whole network.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HostResult
+{
+	pub ip_address: IpAddr,
+	// Whether the host answered the reachability check, or was scanned anyway because of --force.
+	pub up:         bool,
+	// None when the host was skipped for being down.
+	pub open:       Option<usize>
+}
+
+// Renders the table that ends a network scan, one row per host sorted by address, ending with a newline.
+pub fn render_summary(format: Format, results: &[HostResult]) -> String
+{
+	let mut results = results.to_vec();
+	results.sort_by_key(|result| result.ip_address);
+
+	let row =
+		|result: &HostResult| json!({ "ip": result.ip_address.to_string(), "up": result.up, "open": result.open });
+
+	match format
+	{
+		Format::Text =>
+		{
+			let width = results
+				.iter()
+				.map(|result| result.ip_address.to_string().len())
+				.chain(["HOST".len()])
+				.max()
+				.unwrap_or_default();
+
+			let mut table = format!("{:<width$}  {:<4}  {}\n", "HOST", "UP", "OPEN", width = width);
+
+			for result in &results
+			{
+				let open = result.open.map_or(String::from("-"), |open| open.to_string());
+				let up = if result.up { "yes" } else { "no" };
+
+				table.push_str(&format!(
+					"{:<width$}  {:<4}  {}\n",
+					result.ip_address.to_string(),
+					up,
+					open,
+					width = width
+				));
+			}
+
+			table
+		}
+		Format::Json =>
+		{
+			let hosts: Vec<serde_json::Value> = results.iter().map(row).collect();
+
+			format!("{:#}\n", json!({ "hosts": hosts }))
+		}
+		Format::Jsonl => results.iter().map(|result| format!("{}\n", row(result))).collect()
+	}
+}
+
+// Creates the report file, along with any missing parent directories. This is meant to run before the scan, so a path
+// that can't be written to is reported straight away instead of after a long scan.
+pub fn create_output(path: &Path) -> io::Result<File>
+{
+	if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty())
+	{
+		fs::create_dir_all(parent)?;
+	}
+
+	File::create(path)
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::net::Ipv4Addr;
+
+	const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+	const COVERAGE: Coverage = Coverage {
+		scanned:  65535,
+		filtered: &[],
+		duration: Duration::from_millis(1234)
+	};
+
+	fn filtered(ports: &[u16]) -> Coverage<'_>
+	{
+		Coverage {
+			filtered: ports,
+			..COVERAGE
+		}
+	}
+
+	#[test]
+	fn formats_are_parsed_by_name()
+	{
+		assert_eq!("text".parse(), Ok(Format::Text));
+		assert_eq!("json".parse(), Ok(Format::Json));
+		assert_eq!("jsonl".parse(), Ok(Format::Jsonl));
+		assert_eq!("xml".parse::<Format>(), Err(()));
+	}
+
+	#[test]
+	fn render_writes_each_format()
+	{
+		assert_eq!(
+			render(Format::Text, LOCALHOST, &[22, 80, 7000], COVERAGE, true, None),
+			"22 is open (ssh)\n80 is open (http)\n7000 is open\n"
+		);
+		assert_eq!(
+			render(Format::Text, LOCALHOST, &[22, 80], COVERAGE, false, None),
+			"22 is open\n80 is open\n"
+		);
+		assert_eq!(
+			render(Format::Text, LOCALHOST, &[22], filtered(&[23, 24, 25, 443]), true, None),
+			"22 is open (ssh)\nFiltered (no response):\n23-25,443\n"
+		);
+
+		let json: serde_json::Value = serde_json::from_str(&render(
+			Format::Json,
+			LOCALHOST,
+			&[22, 80],
+			filtered(&[443]),
+			true,
+			None
+		))
+		.unwrap();
+
+		assert_eq!(
+			json,
+			json!({
+				"ip": "127.0.0.1",
+				"open_ports": [22, 80],
+				"filtered_ports": [443],
+				"scanned": 65535,
+				"duration_ms": 1234
+			})
+		);
+
+		// One line per open port, as before, so the filtered ones are left out.
+		assert_eq!(
+			render(Format::Jsonl, LOCALHOST, &[22, 80], filtered(&[443]), true, None),
+			"{\"ip\":\"127.0.0.1\",\"port\":22}\n{\"ip\":\"127.0.0.1\",\"port\":80}\n"
+		);
+	}
+
+	#[test]
+	fn render_without_open_ports()
+	{
+		assert_eq!(render(Format::Text, LOCALHOST, &[], COVERAGE, true, None), "");
+		assert_eq!(render(Format::Jsonl, LOCALHOST, &[], COVERAGE, true, None), "");
+		assert!(render(Format::Json, LOCALHOST, &[], COVERAGE, true, None).contains("\"open_ports\": []"));
+	}
+
+	#[test]
+	fn render_includes_banners_once_they_were_read()
+	{
+		let banners = HashMap::from([(22, String::from("SSH-2.0-OpenSSH_9.6"))]);
+
+		assert_eq!(
+			render(Format::Text, LOCALHOST, &[22, 80], COVERAGE, true, Some(&banners)),
+			"22 is open (ssh): SSH-2.0-OpenSSH_9.6\n80 is open (http)\n"
+		);
+
+		let json: serde_json::Value = serde_json::from_str(&render(
+			Format::Json,
+			LOCALHOST,
+			&[22, 80],
+			COVERAGE,
+			true,
+			Some(&banners)
+		))
+		.unwrap();
+
+		assert_eq!(json["banners"], json!({ "22": "SSH-2.0-OpenSSH_9.6", "80": null }));
+
+		assert_eq!(
+			render(Format::Jsonl, LOCALHOST, &[80], COVERAGE, true, Some(&banners)),
+			"{\"banner\":null,\"ip\":\"127.0.0.1\",\"port\":80}\n"
+		);
+	}
+
+	#[test]
+	fn render_probed_marks_the_confirmed_ports()
+	{
+		assert_eq!(
+			render_probed(
+				Format::Text,
+				LOCALHOST,
+				&[80, 8080],
+				COVERAGE,
+				true,
+				ProbeKind::Http,
+				&[80]
+			),
+			"80 is open (http, http confirmed)\n8080 is open (http-alt, no http response)\n"
+		);
+
+		let json: serde_json::Value = serde_json::from_str(&render_probed(
+			Format::Json,
+			LOCALHOST,
+			&[80, 8080],
+			COVERAGE,
+			true,
+			ProbeKind::Http,
+			&[80]
+		))
+		.unwrap();
+
+		assert_eq!(
+			json,
+			json!({
+				"ip": "127.0.0.1",
+				"open_ports": [80, 8080],
+				"filtered_ports": [],
+				"scanned": 65535,
+				"duration_ms": 1234,
+				"probe": "http",
+				"confirmed": [80]
+			})
+		);
+
+		assert_eq!(
+			render_probed(Format::Jsonl, LOCALHOST, &[8080], COVERAGE, true, ProbeKind::Http, &[]),
+			"{\"confirmed\":false,\"ip\":\"127.0.0.1\",\"port\":8080,\"probe\":\"http\"}\n"
+		);
+	}
+
+	#[test]
+	fn render_counts_reports_the_tallies_in_each_format()
+	{
+		assert_eq!(
+			render_counts(Format::Text, LOCALHOST, 12, 3, 65535),
+			"12 open, 65520 closed, 3 filtered, 65535 scanned\n"
+		);
+
+		let expected = json!({ "ip": "127.0.0.1", "open": 12, "closed": 65520, "filtered": 3, "scanned": 65535 });
+
+		let json: serde_json::Value =
+			serde_json::from_str(&render_counts(Format::Json, LOCALHOST, 12, 3, 65535)).unwrap();
+		assert_eq!(json, expected);
+
+		let jsonl = render_counts(Format::Jsonl, LOCALHOST, 12, 3, 65535);
+		assert_eq!(jsonl.lines().count(), 1);
+		assert_eq!(serde_json::from_str::<serde_json::Value>(&jsonl).unwrap(), expected);
+	}
+
+	#[test]
+	fn render_summary_sorts_the_hosts_in_each_format()
+	{
+		let results = [
+			HostResult {
+				ip_address: IpAddr::from([10, 0, 0, 10]),
+				up:         true,
+				open:       Some(3)
+			},
+			HostResult {
+				ip_address: IpAddr::from([10, 0, 0, 2]),
+				up:         false,
+				open:       None
+			}
+		];
+
+		assert_eq!(
+			render_summary(Format::Text, &results),
+			"HOST       UP    OPEN\n10.0.0.2   no    -\n10.0.0.10  yes   3\n"
+		);
+
+		let json: serde_json::Value = serde_json::from_str(&render_summary(Format::Json, &results)).unwrap();
+
+		assert_eq!(
+			json,
+			json!({ "hosts": [
+				{ "ip": "10.0.0.2", "up": false, "open": null },
+				{ "ip": "10.0.0.10", "up": true, "open": 3 }
+			] })
+		);
+
+		assert_eq!(
+			render_summary(Format::Jsonl, &results),
+			"{\"ip\":\"10.0.0.2\",\"open\":null,\"up\":false}\n{\"ip\":\"10.0.0.10\",\"open\":3,\"up\":true}\n"
+		);
+	}
+
+	#[test]
+	fn create_output_makes_missing_directories()
+	{
+		let root = std::env::temp_dir().join(format!("ip_sniffer_report_{}", std::process::id()));
+		let path = root.join("nested").join("report.json");
+
+		create_output(&path).unwrap();
+		assert!(path.is_file());
+
+		// A directory can't be opened as the report file.
+		assert!(create_output(&root).is_err());
+
+		fs::remove_dir_all(&root).unwrap();
+	}
+}
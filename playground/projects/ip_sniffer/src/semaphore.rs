@@ -0,0 +1,49 @@
+use std::sync::{Condvar, Mutex};
+
+// A counting semaphore: at most 'permits' holders at a time, with everyone else blocking in 'acquire' until a permit
+// is handed back.
+pub struct Semaphore
+{
+	permits:   Mutex<usize>,
+	available: Condvar
+}
+
+// Returns its permit to the semaphore when dropped.
+pub struct Permit<'a>
+{
+	semaphore: &'a Semaphore
+}
+
+impl Semaphore
+{
+	pub fn new(permits: usize) -> Semaphore
+	{
+		Semaphore {
+			permits:   Mutex::new(permits),
+			available: Condvar::new()
+		}
+	}
+
+	pub fn acquire(&self) -> Permit<'_>
+	{
+		let mut permits = self.permits.lock().unwrap();
+
+		while *permits == 0
+		{
+			permits = self.available.wait(permits).unwrap();
+		}
+
+		*permits -= 1;
+
+		Permit { semaphore: self }
+	}
+}
+
+impl Drop for Permit<'_>
+{
+	fn drop(&mut self)
+	{
+		*self.semaphore.permits.lock().unwrap() += 1;
+		self.semaphore.available.notify_one();
+	}
+}
@@ -0,0 +1,95 @@
+// Well-known ports and the service usually found on them, sorted by port so they can be binary searched. Only a
+// guess: nothing stops a service from listening anywhere else.
+const SERVICES: [(u16, &str); 61] = [
+	(20, "ftp-data"),
+	(21, "ftp"),
+	(22, "ssh"),
+	(23, "telnet"),
+	(25, "smtp"),
+	(53, "dns"),
+	(67, "dhcp"),
+	(68, "dhcp"),
+	(69, "tftp"),
+	(80, "http"),
+	(88, "kerberos"),
+	(110, "pop3"),
+	(111, "rpcbind"),
+	(119, "nntp"),
+	(123, "ntp"),
+	(135, "msrpc"),
+	(137, "netbios-ns"),
+	(138, "netbios-dgm"),
+	(139, "netbios-ssn"),
+	(143, "imap"),
+	(161, "snmp"),
+	(162, "snmptrap"),
+	(179, "bgp"),
+	(389, "ldap"),
+	(443, "https"),
+	(445, "microsoft-ds"),
+	(465, "smtps"),
+	(500, "isakmp"),
+	(514, "syslog"),
+	(515, "printer"),
+	(587, "submission"),
+	(631, "ipp"),
+	(636, "ldaps"),
+	(873, "rsync"),
+	(993, "imaps"),
+	(995, "pop3s"),
+	(1080, "socks"),
+	(1194, "openvpn"),
+	(1433, "mssql"),
+	(1521, "oracle"),
+	(1723, "pptp"),
+	(1883, "mqtt"),
+	(2049, "nfs"),
+	(2375, "docker"),
+	(3306, "mysql"),
+	(3389, "rdp"),
+	(5060, "sip"),
+	(5432, "postgresql"),
+	(5672, "amqp"),
+	(5900, "vnc"),
+	(6379, "redis"),
+	(6443, "kubernetes"),
+	(8000, "http-alt"),
+	(8080, "http-alt"),
+	(8443, "https-alt"),
+	(9092, "kafka"),
+	(9200, "elasticsearch"),
+	(11211, "memcached"),
+	(27017, "mongodb"),
+	(50000, "db2"),
+	(51820, "wireguard")
+];
+
+// The service a port is usually assigned to, if it is a well-known one.
+pub fn service_name(port: u16) -> Option<&'static str>
+{
+	let index = SERVICES.binary_search_by_key(&port, |(port, _)| *port).ok()?;
+
+	Some(SERVICES[index].1)
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn well_known_ports_have_names()
+	{
+		assert_eq!(service_name(22), Some("ssh"));
+		assert_eq!(service_name(443), Some("https"));
+		assert_eq!(service_name(27017), Some("mongodb"));
+		assert_eq!(service_name(1), None);
+		assert_eq!(service_name(65535), None);
+	}
+
+	#[test]
+	fn the_table_is_sorted()
+	{
+		assert!(SERVICES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+	}
+}
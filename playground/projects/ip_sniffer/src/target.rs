@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+use std::{
+	fmt,
+	net::{IpAddr, Ipv4Addr, Ipv6Addr},
+	str::FromStr
+};
+
+// Networks are scanned host by host, each on every port, so anything much bigger than a /16 would never finish.
+pub const MAX_HOSTS: u128 = 65536;
+
+#[derive(Debug, PartialEq)]
+pub enum TargetError
+{
+	Invalid,
+	TooManyHosts
+}
+
+impl fmt::Display for TargetError
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		match self
+		{
+			TargetError::Invalid => write!(f, "expected an IP address or a network like 192.168.1.0/24"),
+			TargetError::TooManyHosts => write!(f, "networks are limited to {} hosts", MAX_HOSTS)
+		}
+	}
+}
+
+// What to scan: one host, or every host in a network given in CIDR notation. Saved in config files the way it is
+// written on the command line.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub enum Target
+{
+	Host(IpAddr),
+	// The address always has its host bits cleared.
+	Network(IpAddr, u8)
+}
+
+impl Target
+{
+	// The hosts to scan, in ascending order. An IPv4 network's own address and its broadcast address aren't hosts, so
+	// they are left out, except in a /31 or /32 where there is no room for them.
+	pub fn hosts(&self) -> Vec<IpAddr>
+	{
+		match *self
+		{
+			Target::Host(ip_address) => vec![ip_address],
+			Target::Network(IpAddr::V4(network), prefix) =>
+			{
+				let first = u32::from(network);
+				let last = first | (u32::MAX.checked_shr(prefix as u32).unwrap_or(0));
+
+				let hosts = match prefix
+				{
+					31 | 32 => first..=last,
+					_ => first + 1..=last - 1
+				};
+
+				hosts.map(|host| IpAddr::V4(Ipv4Addr::from(host))).collect()
+			}
+			Target::Network(IpAddr::V6(network), prefix) =>
+			{
+				let first = u128::from(network);
+				let last = first | (u128::MAX.checked_shr(prefix as u32).unwrap_or(0));
+
+				(first..=last).map(|host| IpAddr::V6(Ipv6Addr::from(host))).collect()
+			}
+		}
+	}
+
+	pub fn is_network(&self) -> bool { matches!(self, Target::Network(..)) }
+}
+
+impl FromStr for Target
+{
+	type Err = TargetError;
+
+	fn from_str(target: &str) -> Result<Target, TargetError>
+	{
+		let (address, prefix) = match target.split_once('/')
+		{
+			Some((address, prefix)) => (address, Some(prefix)),
+			None => (target, None)
+		};
+
+		let address = IpAddr::from_str(address).map_err(|_| TargetError::Invalid)?;
+
+		let prefix = match prefix
+		{
+			Some(prefix) => prefix.parse::<u8>().map_err(|_| TargetError::Invalid)?,
+			None => return Ok(Target::Host(address))
+		};
+
+		let bits = match address
+		{
+			IpAddr::V4(_) => 32,
+			IpAddr::V6(_) => 128
+		};
+
+		if prefix > bits
+		{
+			return Err(TargetError::Invalid);
+		}
+
+		if bits - prefix > MAX_HOSTS.trailing_zeros() as u8
+		{
+			return Err(TargetError::TooManyHosts);
+		}
+
+		let network = match address
+		{
+			IpAddr::V4(address) =>
+			{
+				IpAddr::V4(Ipv4Addr::from(
+					u32::from(address) & u32::MAX.checked_shl((bits - prefix) as u32).unwrap_or(0)
+				))
+			}
+			IpAddr::V6(address) =>
+			{
+				IpAddr::V6(Ipv6Addr::from(
+					u128::from(address) & u128::MAX.checked_shl((bits - prefix) as u32).unwrap_or(0)
+				))
+			}
+		};
+
+		Ok(Target::Network(network, prefix))
+	}
+}
+
+impl fmt::Display for Target
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		match self
+		{
+			Target::Host(ip_address) => write!(f, "{}", ip_address),
+			Target::Network(network, prefix) => write!(f, "{}/{}", network, prefix)
+		}
+	}
+}
+
+impl TryFrom<String> for Target
+{
+	type Error = TargetError;
+
+	fn try_from(target: String) -> Result<Target, TargetError> { target.parse() }
+}
+
+impl From<Target> for String
+{
+	fn from(target: Target) -> String { target.to_string() }
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn hosts(target: &str) -> Vec<String>
+	{
+		let target: Target = target.parse().unwrap();
+
+		target.hosts().iter().map(IpAddr::to_string).collect()
+	}
+
+	#[test]
+	fn networks_list_their_hosts()
+	{
+		assert_eq!(hosts("10.0.0.1"), vec!["10.0.0.1"]);
+		assert_eq!(hosts("10.0.0.0/30"), vec!["10.0.0.1", "10.0.0.2"]);
+		assert_eq!(hosts("10.0.0.0/31"), vec!["10.0.0.0", "10.0.0.1"]);
+		assert_eq!(hosts("10.0.0.7/32"), vec!["10.0.0.7"]);
+		assert_eq!(hosts("192.168.1.77/24").len(), 254);
+		assert_eq!(hosts("fe80::/126"), vec!["fe80::", "fe80::1", "fe80::2", "fe80::3"]);
+	}
+
+	#[test]
+	fn host_bits_are_cleared_and_kept_through_display()
+	{
+		let target: Target = "192.168.1.77/24".parse().unwrap();
+
+		assert_eq!(target, Target::Network(IpAddr::from([192, 168, 1, 0]), 24));
+		assert_eq!(target.to_string(), "192.168.1.0/24");
+		assert_eq!("10.0.0.1".parse::<Target>().unwrap().to_string(), "10.0.0.1");
+	}
+
+	#[test]
+	fn invalid_and_oversized_targets_are_rejected()
+	{
+		for invalid in [
+			"",
+			"10.0.0",
+			"10.0.0.0/",
+			"10.0.0.0/33",
+			"10.0.0.0/-1",
+			"::/129",
+			"example.com/24"
+		]
+		{
+			assert_eq!(invalid.parse::<Target>(), Err(TargetError::Invalid), "{}", invalid);
+		}
+
+		assert!("10.0.0.0/16".parse::<Target>().is_ok());
+		assert_eq!("10.0.0.0/15".parse::<Target>(), Err(TargetError::TooManyHosts));
+		assert_eq!("::/0".parse::<Target>(), Err(TargetError::TooManyHosts));
+	}
+}
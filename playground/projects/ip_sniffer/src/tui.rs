@@ -0,0 +1,216 @@
+use ip_sniffer::{report::open_port_line, scan_host_events, ScanEvent, ScanOptions};
+use ratatui::{
+	crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+	layout::{Constraint, Layout},
+	widgets::{Block, Gauge, List, Paragraph},
+	DefaultTerminal, Frame
+};
+use std::{
+	collections::HashMap,
+	io,
+	net::IpAddr,
+	sync::mpsc::{self, Receiver, TryRecvError},
+	thread,
+	time::{Duration, Instant}
+};
+
+// How often the screen is redrawn, which is also how long a key press can go unnoticed.
+const TICK: Duration = Duration::from_millis(100);
+
+// Everything the screen shows, updated as results arrive from the scanning thread. What the scan found is handed back
+// once the screen is closed.
+pub struct Scan
+{
+	target:             String,
+	total:              usize,
+	pub scanned:        usize,
+	pub open_ports:     Vec<u16>,
+	// Counted, but not listed on screen, where the open ports are what matters.
+	pub filtered_ports: Vec<u16>,
+	pub banners:        HashMap<u16, String>,
+	service_names:      bool,
+	started:            Instant,
+	// Set once the scan is over, which also freezes the elapsed time.
+	finished:           Option<Duration>
+}
+
+impl Scan
+{
+	fn new(target: String, total: usize, service_names: bool) -> Scan
+	{
+		Scan {
+			target,
+			total,
+			scanned: 0,
+			open_ports: vec![],
+			filtered_ports: vec![],
+			banners: HashMap::new(),
+			service_names,
+			started: Instant::now(),
+			finished: None
+		}
+	}
+
+	fn record(&mut self, event: ScanEvent)
+	{
+		self.scanned += 1;
+
+		match event
+		{
+			ScanEvent::Open(port, banner) =>
+			{
+				self.open_ports.push(port);
+				self.banners.extend(banner.map(|banner| (port, banner)));
+			}
+			ScanEvent::Filtered(port) => self.filtered_ports.push(port),
+			ScanEvent::Closed(_) => ()
+		}
+	}
+
+	fn elapsed(&self) -> Duration { self.finished.unwrap_or_else(|| self.started.elapsed()) }
+}
+
+// Runs the scan behind a live view of its progress and returns what it found: the open ports, with their banners if
+// the options asked for them, the filtered ports and how many ports were scanned. Pressing q stops the scan early, in
+// which case only the ports scanned so far are accounted for.
+pub fn run(
+	target: String,
+	ip_address: IpAddr,
+	ports: Vec<u16>,
+	options: ScanOptions,
+	service_names: bool
+) -> io::Result<Scan>
+{
+	let mut scan = Scan::new(target, ports.len(), service_names);
+
+	// The scan reports through a channel so the screen keeps refreshing even while no port has finished. Once the
+	// receiver is dropped the sends start failing, which is what stops the scan when the user quits.
+	let (tx, rx) = mpsc::channel();
+
+	thread::spawn(move || {
+		scan_host_events(ip_address, ports.into_iter(), &options, |event| tx.send(event).is_ok());
+	});
+
+	let mut terminal = ratatui::try_init()?;
+	let result = show(&mut terminal, &mut scan, &rx);
+
+	// The terminal has to be handed back even when drawing failed, or the shell is left in raw mode.
+	ratatui::restore();
+
+	result.map(|_| scan)
+}
+
+// Keeps the screen up to date until q is pressed, whether or not the scan has finished by then. The terminal is in raw
+// mode, so Ctrl-C arrives as a key rather than a signal, and it quits the same way.
+fn show(terminal: &mut DefaultTerminal, scan: &mut Scan, rx: &Receiver<ScanEvent>) -> io::Result<()>
+{
+	loop
+	{
+		loop
+		{
+			match rx.try_recv()
+			{
+				Ok(event) => scan.record(event),
+				Err(TryRecvError::Empty) => break,
+				Err(TryRecvError::Disconnected) =>
+				{
+					scan.finished.get_or_insert_with(|| scan.started.elapsed());
+					break;
+				}
+			}
+		}
+
+		terminal.draw(|frame| draw(frame, scan))?;
+
+		if event::poll(TICK)?
+		{
+			if let Event::Key(key) = event::read()?
+			{
+				let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+
+				if key.kind == KeyEventKind::Press && (key.code == KeyCode::Char('q') || ctrl_c)
+				{
+					return Ok(());
+				}
+			}
+		}
+	}
+}
+
+fn draw(frame: &mut Frame, scan: &Scan)
+{
+	let [progress_area, ports_area, status_area] =
+		Layout::vertical([Constraint::Length(3), Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+
+	let ratio = match scan.total
+	{
+		0 => 1.0,
+		total => scan.scanned as f64 / total as f64
+	};
+
+	frame.render_widget(
+		Gauge::default()
+			.block(Block::bordered().title(format!(" Scanning {} ", scan.target)))
+			.ratio(ratio)
+			.label(format!("{}/{} ports", scan.scanned, scan.total)),
+		progress_area
+	);
+
+	// Only the newest ports fit once the list outgrows its area, so it scrolls by dropping the oldest from view.
+	let visible = ports_area.height.saturating_sub(2) as usize;
+	let ports = scan
+		.open_ports
+		.iter()
+		.skip(scan.open_ports.len().saturating_sub(visible))
+		.map(|port| {
+			open_port_line(
+				*port,
+				scan.service_names,
+				&[],
+				scan.banners.get(port).map(String::as_str)
+			)
+		});
+
+	frame.render_widget(
+		List::new(ports).block(Block::bordered().title(format!(" Open ports ({}) ", scan.open_ports.len()))),
+		ports_area
+	);
+
+	let state = match scan.finished
+	{
+		Some(_) => "Finished",
+		None => "Scanning"
+	};
+
+	frame.render_widget(
+		Paragraph::new(format!(
+			"{} - {} filtered - elapsed {:.1}s - press q to quit",
+			state,
+			scan.filtered_ports.len(),
+			scan.elapsed().as_secs_f64()
+		)),
+		status_area
+	);
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn record_counts_every_port_and_keeps_the_open_ones()
+	{
+		let mut scan = Scan::new(String::from("127.0.0.1"), 4, true);
+
+		scan.record(ScanEvent::Closed(1));
+		scan.record(ScanEvent::Open(22, Some(String::from("SSH-2.0"))));
+		scan.record(ScanEvent::Closed(3));
+		scan.record(ScanEvent::Filtered(4));
+
+		assert_eq!(scan.scanned, 4);
+		assert_eq!(scan.open_ports, vec![22]);
+		assert_eq!(scan.filtered_ports, vec![4]);
+		assert_eq!(scan.banners.get(&22).map(String::as_str), Some("SSH-2.0"));
+	}
+}
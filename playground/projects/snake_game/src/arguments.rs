@@ -0,0 +1,143 @@
+use crate::{
+	draw::parse_hex_color,
+	game::{Colors, RestartOptions},
+	snake::Portal
+};
+use piston_window::types::Color;
+use rand::random;
+use std::{fmt, path::PathBuf};
+
+pub enum ArgumentError
+{
+	MissingValue(String),
+	InvalidColor(String, String),
+	InvalidSeed(String),
+	InvalidPortal(String),
+	UnknownArgument(String)
+}
+
+impl fmt::Display for ArgumentError
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		match self
+		{
+			ArgumentError::MissingValue(flag) => write!(f, "Missing value for {}", flag),
+			ArgumentError::InvalidColor(flag, value) =>
+			{
+				write!(
+					f,
+					"Invalid color '{}' for {}, expected a hex value like 00cc00",
+					value, flag
+				)
+			}
+			ArgumentError::InvalidSeed(value) => write!(f, "Invalid seed '{}', expected a whole number", value),
+			ArgumentError::InvalidPortal(value) =>
+			{
+				write!(
+					f,
+					"Invalid portal '{}', expected two different tiles like 5,5,20,20",
+					value
+				)
+			}
+			ArgumentError::UnknownArgument(argument) => write!(f, "Unknown argument {}", argument)
+		}
+	}
+}
+
+pub struct Arguments
+{
+	pub colors:          Colors,
+	pub restart_options: RestartOptions,
+	pub portals:         Vec<Portal>,
+	pub mute:            bool,
+	pub autopilot:       bool,
+	// Played instead of the built-in empty board when given.
+	pub level:           Option<PathBuf>
+}
+
+impl Arguments
+{
+	pub fn new(args: &[String]) -> Result<Arguments, ArgumentError>
+	{
+		let mut colors = Colors::default();
+		let mut restart_options = RestartOptions {
+			auto_restart: true,
+			repeat_food:  false,
+			seed:         random()
+		};
+
+		let mut portals = vec![];
+		let mut mute = false;
+		let mut autopilot = false;
+		let mut level = None;
+
+		let mut remaining = args.iter().skip(1);
+
+		while let Some(flag) = remaining.next()
+		{
+			match flag.as_str()
+			{
+				"--snake-color" => colors.snake = Arguments::color(flag, remaining.next())?,
+				"--food-color" => colors.food = Arguments::color(flag, remaining.next())?,
+				"--border-color" => colors.border = Arguments::color(flag, remaining.next())?,
+				"--no-auto-restart" => restart_options.auto_restart = false,
+				"--repeat-food" => restart_options.repeat_food = true,
+				"--mute" => mute = true,
+				"--autopilot" => autopilot = true,
+				"--portal" => portals.push(Arguments::portal(flag, remaining.next())?),
+				"--level" =>
+				{
+					let path = remaining
+						.next()
+						.ok_or_else(|| ArgumentError::MissingValue(flag.clone()))?;
+
+					level = Some(PathBuf::from(path));
+				}
+				"--seed" =>
+				{
+					let value = remaining
+						.next()
+						.ok_or_else(|| ArgumentError::MissingValue(flag.clone()))?;
+
+					restart_options.seed = value.parse().map_err(|_| ArgumentError::InvalidSeed(value.clone()))?;
+				}
+				_ => return Err(ArgumentError::UnknownArgument(flag.clone()))
+			}
+		}
+
+		Ok(Arguments {
+			colors,
+			restart_options,
+			portals,
+			mute,
+			autopilot,
+			level
+		})
+	}
+
+	// Parses "x1,y1,x2,y2" into the two ends of a portal.
+	fn portal(flag: &str, value: Option<&String>) -> Result<Portal, ArgumentError>
+	{
+		let value = value.ok_or_else(|| ArgumentError::MissingValue(flag.to_string()))?;
+
+		let coordinates = value
+			.split(',')
+			.map(|coordinate| coordinate.trim().parse::<i32>())
+			.collect::<Result<Vec<i32>, _>>()
+			.map_err(|_| ArgumentError::InvalidPortal(value.clone()))?;
+
+		match coordinates[..]
+		{
+			[x1, y1, x2, y2] if (x1, y1) != (x2, y2) => Ok(((x1, y1), (x2, y2))),
+			_ => Err(ArgumentError::InvalidPortal(value.clone()))
+		}
+	}
+
+	fn color(flag: &str, value: Option<&String>) -> Result<Color, ArgumentError>
+	{
+		let value = value.ok_or_else(|| ArgumentError::MissingValue(flag.to_string()))?;
+
+		parse_hex_color(value).ok_or_else(|| ArgumentError::InvalidColor(flag.to_string(), value.clone()))
+	}
+}
@@ -0,0 +1,165 @@
+use crate::snake::{step, Direction, Portal, Snake};
+use std::collections::{HashSet, VecDeque};
+
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+// The parts of the game the autopilot steers around. The border tiles are walls, as well as any in 'walls'.
+pub struct Board<'a>
+{
+	pub width:   i32,
+	pub height:  i32,
+	pub walls:   &'a HashSet<(i32, i32)>,
+	pub portals: &'a [Portal],
+	pub food:    Option<(i32, i32)>
+}
+
+impl Board<'_>
+{
+	// Whether the head could move onto the tile without dying. The body is treated as if it stays put, which is safe
+	// since it only ever frees up tiles as the snake moves.
+	fn is_free(&self, snake: &Snake, (x, y): (i32, i32)) -> bool
+	{
+		let inside = x > 0 && y > 0 && x < self.width - 1 && y < self.height - 1;
+
+		inside && !self.walls.contains(&(x, y)) && !snake.overlap_body(x, y)
+	}
+
+	// How many tiles can be reached from 'start', which is how much room the snake would have left there.
+	fn room_from(&self, snake: &Snake, start: (i32, i32)) -> usize
+	{
+		let mut seen = HashSet::from([start]);
+		let mut queue = VecDeque::from([start]);
+
+		while let Some(position) = queue.pop_front()
+		{
+			for direction in DIRECTIONS
+			{
+				let next = step(position, direction, self.portals);
+
+				if self.is_free(snake, next) && seen.insert(next)
+				{
+					queue.push_back(next);
+				}
+			}
+		}
+
+		seen.len()
+	}
+
+	// The first move of the shortest path from the head to the food, found with a breadth first search.
+	fn first_step_to_food(&self, snake: &Snake, moves: &[(Direction, (i32, i32))]) -> Option<Direction>
+	{
+		let food = self.food?;
+
+		let mut seen: HashSet<(i32, i32)> = moves.iter().map(|(_, position)| *position).collect();
+		let mut queue: VecDeque<(Direction, (i32, i32))> = moves.iter().copied().collect();
+
+		while let Some((first, position)) = queue.pop_front()
+		{
+			if position == food
+			{
+				return Some(first);
+			}
+
+			for direction in DIRECTIONS
+			{
+				let next = step(position, direction, self.portals);
+
+				if self.is_free(snake, next) && seen.insert(next)
+				{
+					queue.push_back((first, next));
+				}
+			}
+		}
+
+		None
+	}
+}
+
+// Picks the snake's next move: along the shortest path to the food unless that would leave it boxed into less room
+// than its own length, and otherwise towards whichever side has the most room. None when every move is fatal.
+pub fn choose_direction(snake: &Snake, board: &Board) -> Option<Direction>
+{
+	let moves: Vec<(Direction, (i32, i32))> = DIRECTIONS
+		.into_iter()
+		.filter(|direction| *direction != snake.head_direction().opposite())
+		.map(|direction| (direction, snake.next_head(Some(direction), board.portals)))
+		.filter(|(_, next)| board.is_free(snake, *next))
+		.collect();
+
+	let roomiest = moves
+		.iter()
+		.map(|(direction, next)| (*direction, board.room_from(snake, *next)))
+		.max_by_key(|(_, room)| *room);
+
+	let toward_food = board.first_step_to_food(snake, &moves).filter(|direction| {
+		let next = snake.next_head(Some(*direction), board.portals);
+
+		board.room_from(snake, next) >= snake.len()
+	});
+
+	toward_food.or(roomiest.map(|(direction, _)| direction))
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn heads_for_the_food()
+	{
+		// The head is at (4, 2), heading right.
+		let snake = Snake::new(2, 2);
+		let walls = HashSet::new();
+
+		let board = |food| {
+			Board {
+				width:   10,
+				height:  10,
+				walls:   &walls,
+				portals: &[],
+				food:    Some(food)
+			}
+		};
+
+		assert_eq!(choose_direction(&snake, &board((8, 2))), Some(Direction::Right));
+		assert_eq!(choose_direction(&snake, &board((4, 7))), Some(Direction::Down));
+
+		// Food behind the snake is reached by going around its body rather than turning back into it.
+		let direction = choose_direction(&snake, &board((1, 2)));
+		assert!(matches!(direction, Some(Direction::Up | Direction::Down)));
+	}
+
+	#[test]
+	fn avoids_walls_and_follows_portals()
+	{
+		// The head is at (8, 1), heading right, with the wall straight ahead and above.
+		let snake = Snake::new(6, 1);
+		let walls = HashSet::new();
+
+		let board = Board {
+			width:   10,
+			height:  10,
+			walls:   &walls,
+			portals: &[],
+			food:    None
+		};
+
+		assert_eq!(choose_direction(&snake, &board), Some(Direction::Down));
+
+		// Stepping down from (4, 2) lands on (4, 7) through the portal, right next to the food.
+		let snake = Snake::new(2, 2);
+		let portals = [((4, 3), (4, 7))];
+
+		let board = Board {
+			width:   20,
+			height:  20,
+			walls:   &walls,
+			portals: &portals,
+			food:    Some((4, 8))
+		};
+
+		assert_eq!(choose_direction(&snake, &board), Some(Direction::Down));
+	}
+}
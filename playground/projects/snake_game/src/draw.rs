@@ -31,3 +31,47 @@ pub fn draw_rectangle(color: Color, x: i32, y: i32, width: i32, height: i32, con
 		graphics
 	);
 }
+
+// Parses an "rrggbb" or "rrggbbaa" hex string (an optional leading '#' is allowed) into a piston color.
+pub fn parse_hex_color(hex: &str) -> Option<Color>
+{
+	let hex = hex.strip_prefix('#').unwrap_or(hex);
+
+	// from_str_radix accepts a leading '+', so "+fff00" would otherwise slip through as a color.
+	if (hex.len() != 6 && hex.len() != 8) || !hex.chars().all(|c| c.is_ascii_hexdigit())
+	{
+		return None;
+	}
+
+	let mut color: Color = [0.0, 0.0, 0.0, 1.0];
+
+	for (index, channel) in color.iter_mut().enumerate().take(hex.len() / 2)
+	{
+		let value = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).ok()?;
+		*channel = value as f32 / 255.0;
+	}
+
+	Some(color)
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn parse_hex_color_accepts_rgb_and_rgba()
+	{
+		assert_eq!(parse_hex_color("#ff0000"), Some([1.0, 0.0, 0.0, 1.0]));
+		assert_eq!(parse_hex_color("00ff0000"), Some([0.0, 1.0, 0.0, 0.0]));
+	}
+
+	#[test]
+	fn parse_hex_color_rejects_non_hex_digits()
+	{
+		assert_eq!(parse_hex_color("+fff00"), None);
+		assert_eq!(parse_hex_color("#+f+f+f"), None);
+		assert_eq!(parse_hex_color("gg0000"), None);
+		assert_eq!(parse_hex_color("ff00"), None);
+	}
+}
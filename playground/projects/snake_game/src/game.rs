@@ -1,13 +1,57 @@
 use crate::{
+	autopilot::{self, Board},
 	draw::{draw_block, draw_rectangle},
-	snake::{Direction, Snake}
+	hud::draw_text,
+	level::{Level, LevelError},
+	snake::{Direction, Portal, Snake, SNAKE_COLOR},
+	sound::Sound,
+	streak::Streak
 };
 use piston_window::{types::Color, *};
-use rand::{thread_rng, Rng};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::path::Path;
 
 const FOOD_COLOR: Color = [0.80, 0.00, 0.00, 1.0];
 const BORDER_COLOR: Color = [0.00, 0.00, 0.00, 1.0];
+const PORTAL_COLOR: Color = [0.55, 0.20, 0.85, 1.0];
 const GAMEOVER_COLOR: Color = [0.90, 0.00, 0.00, 0.5];
+const GAMEOVER_TEXT_COLOR: Color = [1.00, 1.00, 1.00, 1.0];
+const GAMEOVER_FONT_SIZE: u32 = 32;
+const HUD_TEXT_COLOR: Color = [1.00, 1.00, 1.00, 1.0];
+const HUD_FONT_SIZE: u32 = 16;
+
+// Points for one food, before the streak multiplier.
+const FOOD_POINTS: u32 = 10;
+
+#[derive(Clone, Copy)]
+pub struct Colors
+{
+	pub snake:  Color,
+	pub food:   Color,
+	pub border: Color
+}
+
+impl Default for Colors
+{
+	fn default() -> Colors
+	{
+		Colors {
+			snake:  SNAKE_COLOR,
+			food:   FOOD_COLOR,
+			border: BORDER_COLOR
+		}
+	}
+}
+
+#[derive(Clone, Copy)]
+pub struct RestartOptions
+{
+	// Restart on its own after RESTART_TIME, rather than waiting for a key press.
+	pub auto_restart: bool,
+	// Start each round from 'seed' again, so food appears in the same places every time.
+	pub repeat_food:  bool,
+	pub seed:         u64
+}
 
 const MOVING_PERIOD: f64 = 0.1;
 const RESTART_TIME: f64 = 1.0;
@@ -20,33 +64,116 @@ pub struct Game
 	food_x:      i32,
 	food_y:      i32,
 
-	width:  i32,
-	height: i32,
+	level: Level,
+
+	portals: Vec<Portal>,
 
 	game_is_over: bool,
-	waiting_time: f64
+	waiting_time: f64,
+
+	score:  u32,
+	streak: Streak,
+
+	colors:          Colors,
+	restart_options: RestartOptions,
+	rng:             StdRng,
+	// None when muted or when there is no audio device.
+	sound:           Option<Sound>,
+	// Steers the snake on every move. Key presses still take effect, so a player can take over at any time.
+	autopilot:       bool
 }
 
 impl Game
 {
-	pub fn new(width: i32, height: i32) -> Game
+	pub fn new(
+		width: i32,
+		height: i32,
+		portals: Vec<Portal>,
+		colors: Colors,
+		restart_options: RestartOptions,
+		sound: Option<Sound>,
+		autopilot: bool
+	) -> Game
+	{
+		Game::with_level(
+			Level::empty(width, height),
+			portals,
+			colors,
+			restart_options,
+			sound,
+			autopilot
+		)
+	}
+
+	// Plays on a level loaded from a text file, see 'Level::parse' for the format.
+	pub fn from_level(
+		path: &Path,
+		portals: Vec<Portal>,
+		colors: Colors,
+		restart_options: RestartOptions,
+		sound: Option<Sound>,
+		autopilot: bool
+	) -> Result<Game, LevelError>
 	{
-		Game {
-			snake: Snake::new(2, 2),
+		let level = Level::load(path)?;
+
+		Ok(Game::with_level(
+			level,
+			portals,
+			colors,
+			restart_options,
+			sound,
+			autopilot
+		))
+	}
+
+	fn with_level(
+		level: Level,
+		portals: Vec<Portal>,
+		colors: Colors,
+		restart_options: RestartOptions,
+		sound: Option<Sound>,
+		autopilot: bool
+	) -> Game
+	{
+		let (start_x, start_y) = level.snake_start;
+		let (food_x, food_y) = level.food_start.unwrap_or_default();
+
+		let mut game = Game {
+			snake: Snake::new(start_x, start_y),
 			waiting_time: 0.0,
-			food_exists: true,
-			food_x: 6,
-			food_y: 4,
-			width,
-			height,
-			game_is_over: false
-		}
+			food_exists: level.food_start.is_some(),
+			food_x,
+			food_y,
+			level,
+			portals,
+			game_is_over: false,
+			score: 0,
+			streak: Streak::new(),
+			colors,
+			restart_options,
+			rng: StdRng::seed_from_u64(restart_options.seed),
+			sound,
+			autopilot
+		};
+
+		// The first food has a fixed spot, so a new one is placed instead if a portal sits there.
+		game.food_exists = game.food_exists && !game.is_portal(game.food_x, game.food_y);
+		game
 	}
 
+	pub fn size(&self) -> (i32, i32) { (self.level.width, self.level.height) }
+
 	pub fn key_pressed(&mut self, key: Key)
 	{
 		if self.game_is_over
 		{
+			// The delay still applies, so a key that was held down when the snake died doesn't skip the game over.
+			if !self.restart_options.auto_restart && self.waiting_time > RESTART_TIME
+			{
+				self.restart();
+			}
+
 			return;
 		}
 
@@ -70,23 +197,66 @@ impl Game
 		self.update_snake(direction);
 	}
 
-	pub fn draw(&self, context: &Context, graphics: &mut G2d)
+	// Text is only drawn when a font could be loaded.
+	pub fn draw(&self, mut glyphs: Option<&mut Glyphs>, context: &Context, graphics: &mut G2d)
 	{
-		self.snake.draw(context, graphics);
+		// Portals go underneath, so the snake stays visible while it passes through one.
+		for ((entrance_x, entrance_y), (exit_x, exit_y)) in &self.portals
+		{
+			draw_block(PORTAL_COLOR, *entrance_x, *entrance_y, context, graphics);
+			draw_block(PORTAL_COLOR, *exit_x, *exit_y, context, graphics);
+		}
+
+		for (x, y) in &self.level.walls
+		{
+			draw_block(self.colors.border, *x, *y, context, graphics);
+		}
+
+		self.snake.draw(self.colors.snake, context, graphics);
 
 		if self.food_exists
 		{
-			draw_block(FOOD_COLOR, self.food_x, self.food_y, context, graphics);
+			draw_block(self.colors.food, self.food_x, self.food_y, context, graphics);
 		}
 
-		draw_rectangle(BORDER_COLOR, 0, 0, self.width, 1, context, graphics);
-		draw_rectangle(BORDER_COLOR, 0, self.height - 1, self.width, 1, context, graphics);
-		draw_rectangle(BORDER_COLOR, 0, 0, 1, self.height, context, graphics);
-		draw_rectangle(BORDER_COLOR, self.width - 1, 0, 1, self.height, context, graphics);
+		let border_color = self.colors.border;
+		let (width, height) = self.size();
+
+		draw_rectangle(border_color, 0, 0, width, 1, context, graphics);
+		draw_rectangle(border_color, 0, height - 1, width, 1, context, graphics);
+		draw_rectangle(border_color, 0, 0, 1, height, context, graphics);
+		draw_rectangle(border_color, width - 1, 0, 1, height, context, graphics);
+
+		// Sits on the top border, where it never covers the snake or the food.
+		if let Some(glyphs) = glyphs.as_deref_mut()
+		{
+			draw_text(
+				glyphs,
+				&format!("Score: {}  x{}", self.score, self.streak.get_multiplier()),
+				(1, 1),
+				HUD_FONT_SIZE,
+				HUD_TEXT_COLOR,
+				context,
+				graphics
+			);
+		}
 
 		if self.game_is_over
 		{
-			draw_rectangle(GAMEOVER_COLOR, 0, 0, self.width, self.height, context, graphics);
+			draw_rectangle(GAMEOVER_COLOR, 0, 0, width, height, context, graphics);
+
+			if let Some(glyphs) = glyphs
+			{
+				draw_text(
+					glyphs,
+					&format!("Length: {}  Score: {}", self.snake.len(), self.score),
+					(2, height / 2),
+					GAMEOVER_FONT_SIZE,
+					GAMEOVER_TEXT_COLOR,
+					context,
+					graphics
+				);
+			}
 		}
 	}
 
@@ -96,7 +266,7 @@ impl Game
 
 		if self.game_is_over
 		{
-			if self.waiting_time > RESTART_TIME
+			if self.restart_options.auto_restart && self.waiting_time > RESTART_TIME
 			{
 				self.restart();
 			}
@@ -104,6 +274,8 @@ impl Game
 			return;
 		}
 
+		self.streak.update(delta_time);
+
 		if !self.food_exists
 		{
 			self.add_food();
@@ -111,7 +283,13 @@ impl Game
 
 		if self.waiting_time > MOVING_PERIOD
 		{
-			self.update_snake(None);
+			let direction = match self.autopilot
+			{
+				true => self.autopilot_direction(),
+				false => None
+			};
+
+			self.update_snake(direction);
 		}
 	}
 
@@ -124,35 +302,42 @@ impl Game
 			self.food_exists = false;
 
 			self.snake.increase_body_length();
+			self.score += FOOD_POINTS * self.streak.eat();
+
+			if let Some(sound) = &self.sound
+			{
+				sound.eat();
+			}
 		}
 	}
 
 	fn is_snake_alive(&self, direction: Option<Direction>) -> bool
 	{
-		let (next_x, next_y): (i32, i32) = self.snake.next_head(direction);
+		let (next_x, next_y): (i32, i32) = self.snake.next_head(direction, &self.portals);
 
 		if self.snake.overlap_body(next_x, next_y)
 		{
 			return false;
 		}
 
-		let snake_is_within_area_bounds: bool =
-			next_x > 0 && next_y > 0 && next_x < self.width - 1 && next_y < self.height - 1;
-
-		snake_is_within_area_bounds
+		// The border counts as a wall.
+		!self.level.is_wall((next_x, next_y))
 	}
 
 	fn add_food(&mut self)
 	{
-		let mut rng = thread_rng();
+		let (width, height) = self.size();
 
-		let mut new_x = rng.gen_range(1..self.width - 1);
-		let mut new_y = rng.gen_range(1..self.height - 1);
+		let mut new_x = self.rng.gen_range(1..width - 1);
+		let mut new_y = self.rng.gen_range(1..height - 1);
 
+		// Food on a portal could never be eaten, since the head always jumps past it.
 		while self.snake.overlap_body(new_x, new_y)
+			|| self.is_portal(new_x, new_y)
+			|| self.level.is_wall((new_x, new_y))
 		{
-			new_x = rng.gen_range(1..self.width - 1);
-			new_y = rng.gen_range(1..self.height - 1);
+			new_x = self.rng.gen_range(1..width - 1);
+			new_y = self.rng.gen_range(1..height - 1);
 		}
 
 		self.food_x = new_x;
@@ -160,16 +345,41 @@ impl Game
 		self.food_exists = true;
 	}
 
+	fn autopilot_direction(&self) -> Option<Direction>
+	{
+		let board = Board {
+			width:   self.level.width,
+			height:  self.level.height,
+			walls:   &self.level.walls,
+			portals: &self.portals,
+			food:    self.food_exists.then_some((self.food_x, self.food_y))
+		};
+
+		autopilot::choose_direction(&self.snake, &board)
+	}
+
+	fn is_portal(&self, x: i32, y: i32) -> bool
+	{
+		self.portals
+			.iter()
+			.any(|(entrance, exit)| *entrance == (x, y) || *exit == (x, y))
+	}
+
 	fn update_snake(&mut self, direction: Option<Direction>)
 	{
 		if self.is_snake_alive(direction)
 		{
-			self.snake.move_forward(direction);
+			self.snake.move_forward(direction, &self.portals);
 			self.check_eating();
 		}
 		else
 		{
 			self.game_is_over = true;
+
+			if let Some(sound) = &self.sound
+			{
+				sound.game_over();
+			}
 		}
 
 		self.waiting_time = 0.0;
@@ -177,11 +387,71 @@ impl Game
 
 	fn restart(&mut self)
 	{
-		self.snake = Snake::new(2, 2);
+		let (start_x, start_y) = self.level.snake_start;
+
+		self.snake = Snake::new(start_x, start_y);
 		self.waiting_time = 0.0;
-		self.food_exists = true;
-		self.food_x = 6;
-		self.food_y = 4;
+		(self.food_x, self.food_y) = self.level.food_start.unwrap_or_default();
+		self.food_exists = self.level.food_start.is_some() && !self.is_portal(self.food_x, self.food_y);
 		self.game_is_over = false;
+		self.score = 0;
+		self.streak = Streak::new();
+
+		if self.restart_options.repeat_food
+		{
+			self.rng = StdRng::seed_from_u64(self.restart_options.seed);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn autopilot_eats_without_crashing()
+	{
+		let restart_options = RestartOptions {
+			auto_restart: false,
+			repeat_food:  false,
+			seed:         7
+		};
+
+		let mut game = Game::new(20, 20, vec![], Colors::default(), restart_options, None, true);
+
+		for _ in 0..1000
+		{
+			game.update(MOVING_PERIOD * 2.0);
+		}
+
+		assert!(!game.game_is_over);
+		assert!(
+			game.snake.len() >= 20,
+			"the snake only reached a length of {}",
+			game.snake.len()
+		);
+	}
+
+	#[test]
+	fn levels_place_the_snake_and_food_and_walls_kill()
+	{
+		let restart_options = RestartOptions {
+			auto_restart: false,
+			repeat_food:  false,
+			seed:         7
+		};
+
+		let level = Level::parse("#######\n#.....#\n#S..#F#\n#######").ok().unwrap();
+		let mut game = Game::with_level(level, vec![], Colors::default(), restart_options, None, false);
+
+		assert_eq!(game.size(), (7, 4));
+		assert_eq!(game.snake.head_position(), (3, 2));
+		assert!(game.food_exists);
+		assert_eq!((game.food_x, game.food_y), (5, 2));
+
+		// The wall is right in front of the head.
+		game.update(MOVING_PERIOD * 2.0);
+		assert!(game.game_is_over);
 	}
 }
@@ -0,0 +1,31 @@
+use crate::draw::to_coordinate;
+use piston_window::{text, types::Color, Context, G2d, Glyphs, PistonWindow, Transformed};
+
+const FONT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/DejaVuSans.ttf");
+
+// Loads the bundled font once for the whole game. A missing or broken font only costs the on-screen text, so it is
+// reported and the game goes on without it.
+pub fn load_font(window: &mut PistonWindow) -> Option<Glyphs>
+{
+	window
+		.load_font(FONT_PATH)
+		.map_err(|error| eprintln!("Failed to load {}, text won't be shown: {}", FONT_PATH, error))
+		.ok()
+}
+
+// Draws text with its baseline starting at the given game coordinates.
+pub fn draw_text(
+	glyphs: &mut Glyphs,
+	text: &str,
+	(x, y): (i32, i32),
+	size: u32,
+	color: Color,
+	context: &Context,
+	graphics: &mut G2d
+)
+{
+	let transform = context.transform.trans(to_coordinate(x), to_coordinate(y));
+
+	// A glyph that fails to render only costs the text, so the game keeps going.
+	let _ = text::Text::new_color(color, size).draw(text, glyphs, &context.draw_state, transform, graphics);
+}
@@ -0,0 +1,276 @@
+use std::{collections::HashSet, fmt, fs, io, path::Path};
+
+pub enum LevelError
+{
+	Io(io::Error),
+	Empty,
+	NotRectangular
+	{
+		line:     usize,
+		expected: usize,
+		found:    usize
+	},
+	UnknownTile
+	{
+		line:   usize,
+		column: usize,
+		tile:   char
+	},
+	NoSnakeStart,
+	MultipleSnakeStarts,
+	MultipleFoodStarts,
+	SnakeStartBlocked,
+	FoodStartOnBorder
+}
+
+impl fmt::Display for LevelError
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		match self
+		{
+			LevelError::Io(error) => write!(f, "{}", error),
+			LevelError::Empty => write!(f, "The level is empty"),
+			LevelError::NotRectangular { line, expected, found } =>
+			{
+				write!(
+					f,
+					"Line {} is {} tiles wide, but the first line is {}, so the level isn't rectangular",
+					line, found, expected
+				)
+			}
+			LevelError::UnknownTile { line, column, tile } =>
+			{
+				write!(
+					f,
+					"Unknown tile '{}' at line {}, column {}, expected one of # . S F",
+					tile, line, column
+				)
+			}
+			LevelError::NoSnakeStart => write!(f, "The level has no snake start, mark one with S"),
+			LevelError::MultipleSnakeStarts => write!(f, "The level has more than one snake start"),
+			LevelError::MultipleFoodStarts => write!(f, "The level has more than one food start"),
+			LevelError::SnakeStartBlocked =>
+			{
+				write!(
+					f,
+					"The snake starts three tiles long heading right, so S needs two empty tiles to its right inside the \
+					 border"
+				)
+			}
+			LevelError::FoodStartOnBorder => write!(f, "The food start is on the border, where it can't be reached")
+		}
+	}
+}
+
+// The board a game is played on. The outermost tiles are always walls, drawn as the border, whether or not the level
+// marks them.
+pub struct Level
+{
+	pub width:       i32,
+	pub height:      i32,
+	pub walls:       HashSet<(i32, i32)>,
+	// Where the snake's tail starts. The snake is three tiles long and heads right, so its head is two tiles further.
+	pub snake_start: (i32, i32),
+	// None leaves the first food to be placed at random.
+	pub food_start:  Option<(i32, i32)>
+}
+
+impl Level
+{
+	// The built-in board: nothing but the border.
+	pub fn empty(width: i32, height: i32) -> Level
+	{
+		Level {
+			width,
+			height,
+			walls: HashSet::new(),
+			snake_start: (2, 2),
+			food_start: Some((6, 4))
+		}
+	}
+
+	pub fn load(path: &Path) -> Result<Level, LevelError>
+	{
+		Level::parse(&fs::read_to_string(path).map_err(LevelError::Io)?)
+	}
+
+	// Reads a level drawn as text, one line per row: '#' is a wall, '.' is empty, 'S' is the snake start and 'F' the
+	// food start.
+	pub fn parse(text: &str) -> Result<Level, LevelError>
+	{
+		let rows: Vec<&str> = text.trim_end().lines().collect();
+
+		let width = match rows.first()
+		{
+			Some(row) if !row.is_empty() => row.chars().count(),
+			_ => return Err(LevelError::Empty)
+		};
+
+		let mut walls = HashSet::new();
+		let mut snake_start = None;
+		let mut food_start = None;
+
+		for (y, row) in rows.iter().enumerate()
+		{
+			let found = row.chars().count();
+
+			if found != width
+			{
+				return Err(LevelError::NotRectangular {
+					line: y + 1,
+					expected: width,
+					found
+				});
+			}
+
+			for (x, tile) in row.chars().enumerate()
+			{
+				let position = (x as i32, y as i32);
+
+				match tile
+				{
+					'#' =>
+					{
+						walls.insert(position);
+					}
+					'.' => (),
+					'S' if snake_start.is_some() => return Err(LevelError::MultipleSnakeStarts),
+					'S' => snake_start = Some(position),
+					'F' if food_start.is_some() => return Err(LevelError::MultipleFoodStarts),
+					'F' => food_start = Some(position),
+					tile =>
+					{
+						return Err(LevelError::UnknownTile {
+							line: y + 1,
+							column: x + 1,
+							tile
+						})
+					}
+				}
+			}
+		}
+
+		let level = Level {
+			width: width as i32,
+			height: rows.len() as i32,
+			walls,
+			snake_start: snake_start.ok_or(LevelError::NoSnakeStart)?,
+			food_start
+		};
+
+		let (x, y) = level.snake_start;
+
+		// The food can't start underneath the snake either, or it would be eaten by the tail rather than the head.
+		let body_is_free = (0..3).all(|offset| {
+			let tile = (x + offset, y);
+
+			level.is_inside(tile) && !level.walls.contains(&tile) && level.food_start != Some(tile)
+		});
+
+		if !body_is_free
+		{
+			return Err(LevelError::SnakeStartBlocked);
+		}
+
+		if level.food_start.is_some_and(|food| !level.is_inside(food))
+		{
+			return Err(LevelError::FoodStartOnBorder);
+		}
+
+		Ok(level)
+	}
+
+	// Whether the tile is within the border.
+	pub fn is_inside(&self, (x, y): (i32, i32)) -> bool { x > 0 && y > 0 && x < self.width - 1 && y < self.height - 1 }
+
+	pub fn is_wall(&self, position: (i32, i32)) -> bool { !self.is_inside(position) || self.walls.contains(&position) }
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	const LEVEL: &str = "\
+########
+#......#
+#.S..#.#
+#....#F#
+########
+";
+
+	fn error(text: &str) -> LevelError
+	{
+		match Level::parse(text)
+		{
+			Ok(_) => panic!("{:?} was accepted", text),
+			Err(error) => error
+		}
+	}
+
+	#[test]
+	fn parses_walls_and_starts()
+	{
+		let level = Level::parse(LEVEL).ok().unwrap();
+
+		assert_eq!((level.width, level.height), (8, 5));
+		assert_eq!(level.snake_start, (2, 2));
+		assert_eq!(level.food_start, Some((6, 3)));
+
+		assert!(level.is_wall((5, 2)));
+		assert!(level.is_wall((0, 1)));
+		assert!(!level.is_wall((4, 1)));
+
+		// The border is always a wall, even where the level leaves it open.
+		let level = Level::parse(".....\n.S...\n.....").ok().unwrap();
+
+		assert!(level.walls.is_empty());
+		assert!(level.is_wall((0, 0)));
+		assert_eq!(level.food_start, None);
+	}
+
+	#[test]
+	fn rejects_invalid_levels()
+	{
+		assert!(matches!(error(""), LevelError::Empty));
+		assert!(matches!(error("\n\n"), LevelError::Empty));
+		assert!(matches!(
+			error("#####\n#S..#\n###\n"),
+			LevelError::NotRectangular {
+				line:     3,
+				expected: 5,
+				found:    3
+			}
+		));
+		assert!(matches!(
+			error("#####\n#S.x#\n#####"),
+			LevelError::UnknownTile {
+				line:   2,
+				column: 4,
+				tile:   'x'
+			}
+		));
+		assert!(matches!(error("#####\n#...#\n#####"), LevelError::NoSnakeStart));
+		assert!(matches!(
+			error("######\n#S.S.#\n######"),
+			LevelError::MultipleSnakeStarts
+		));
+		assert!(matches!(
+			error("#####\n#S..#\n#FF.#\n#####"),
+			LevelError::MultipleFoodStarts
+		));
+		assert!(matches!(error("#####\n#S.##\n#####"), LevelError::SnakeStartBlocked));
+		assert!(matches!(error("#####\n#.S.#\n#####"), LevelError::SnakeStartBlocked));
+		assert!(matches!(error("######\n#SF..#\n######"), LevelError::SnakeStartBlocked));
+		assert!(matches!(error("##F###\n#S...#\n######"), LevelError::FoodStartOnBorder));
+	}
+
+	#[test]
+	fn the_sample_level_loads()
+	{
+		let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("levels/rooms.txt");
+
+		assert!(Level::load(&path).is_ok());
+	}
+}
@@ -1,25 +1,77 @@
 extern crate piston_window;
 extern crate rand;
 
+mod arguments;
+mod autopilot;
 mod draw;
 mod game;
+mod hud;
+mod level;
 mod snake;
+mod sound;
+mod streak;
 
-use crate::{draw::to_coordinate_u32, game::Game};
+use crate::{arguments::Arguments, draw::to_coordinate_u32, game::Game, sound::Sound};
 use piston_window::{types::Color, *};
+use std::{env, process};
 
 const BACK_COLOR: Color = [0.5, 0.5, 0.5, 1.0];
 
 fn main()
 {
-	let (width, height) = (30, 30);
+	let args: Vec<String> = env::args().collect();
+
+	let arguments = Arguments::new(&args).unwrap_or_else(|error| {
+		eprintln!("{}: {}", args[0], error);
+		process::exit(1);
+	});
+
+	let sound = match arguments.mute
+	{
+		true => None,
+		false => Sound::new()
+	};
+
+	let mut game = match &arguments.level
+	{
+		Some(path) =>
+		{
+			Game::from_level(
+				path,
+				arguments.portals,
+				arguments.colors,
+				arguments.restart_options,
+				sound,
+				arguments.autopilot
+			)
+			.unwrap_or_else(|error| {
+				eprintln!("{}: can't load level {}: {}", args[0], path.display(), error);
+				process::exit(1);
+			})
+		}
+		None =>
+		{
+			Game::new(
+				30,
+				30,
+				arguments.portals,
+				arguments.colors,
+				arguments.restart_options,
+				sound,
+				arguments.autopilot
+			)
+		}
+	};
+
+	// The window is sized to the board, which a level decides.
+	let (width, height) = game.size();
 
 	let mut window: PistonWindow = WindowSettings::new("Snake", [to_coordinate_u32(width), to_coordinate_u32(height)])
 		.exit_on_esc(true)
 		.build()
 		.unwrap();
 
-	let mut game = Game::new(width, height);
+	let mut glyphs = hud::load_font(&mut window);
 
 	while let Some(event) = window.next()
 	{
@@ -28,9 +80,14 @@ fn main()
 			game.key_pressed(key);
 		}
 
-		window.draw_2d(&event, |c, g, _| {
+		window.draw_2d(&event, |c, g, device| {
 			clear(BACK_COLOR, g);
-			game.draw(&c, g);
+			game.draw(glyphs.as_mut(), &c, g);
+
+			if let Some(glyphs) = glyphs.as_mut()
+			{
+				glyphs.factory.encoder.flush(device);
+			}
 		});
 
 		event.update(|arg| {
@@ -2,9 +2,12 @@ use crate::draw::draw_block;
 use piston_window::{types::Color, Context, G2d};
 use std::collections::LinkedList;
 
-const SNAKE_COLOR: Color = [0.00, 0.80, 0.00, 1.0];
+pub const SNAKE_COLOR: Color = [0.00, 0.80, 0.00, 1.0];
 
-#[derive(Copy, Clone, PartialEq)]
+// A pair of linked tiles. Moving onto either one puts the head on the other instead.
+pub type Portal = ((i32, i32), (i32, i32));
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Direction
 {
 	Up,
@@ -53,16 +56,16 @@ impl Snake
 
 		Snake {
 			direction: Direction::Right,
-			body:      body,
-			tail:      None
+			body,
+			tail: None
 		}
 	}
 
-	pub fn draw(&self, context: &Context, graphics: &mut G2d)
+	pub fn draw(&self, color: Color, context: &Context, graphics: &mut G2d)
 	{
 		for block in &self.body
 		{
-			draw_block(SNAKE_COLOR, block.x, block.y, context, graphics)
+			draw_block(color, block.x, block.y, context, graphics)
 		}
 	}
 
@@ -73,74 +76,37 @@ impl Snake
 		(head_block.x, head_block.y)
 	}
 
-	pub fn move_forward(&mut self, direction: Option<Direction>)
+	pub fn move_forward(&mut self, direction: Option<Direction>, portals: &[Portal])
 	{
-		match direction
+		if let Some(direction) = direction
 		{
-			Some(direction) => self.direction = direction,
-			None => ()
+			self.direction = direction;
 		}
 
-		let (last_x, last_y): (i32, i32) = self.head_position();
-
-		let new_block = match self.direction
-		{
-			Direction::Up =>
-			{
-				Block {
-					x: last_x,
-					y: last_y - 1
-				}
-			}
-			Direction::Down =>
-			{
-				Block {
-					x: last_x,
-					y: last_y + 1
-				}
-			}
-			Direction::Left =>
-			{
-				Block {
-					x: last_x - 1,
-					y: last_y
-				}
-			}
-			Direction::Right =>
-			{
-				Block {
-					x: last_x + 1,
-					y: last_y
-				}
-			}
-		};
+		let (x, y): (i32, i32) = self.next_head(None, portals);
 
-		self.body.push_front(new_block);
+		self.body.push_front(Block { x, y });
 		let removed_body = self.body.pop_back().unwrap();
 		self.tail = Some(removed_body);
 	}
 
 	pub fn head_direction(&self) -> Direction { self.direction }
 
-	pub fn next_head(&self, direction: Option<Direction>) -> (i32, i32)
+	pub fn len(&self) -> usize { self.body.len() }
+
+	// Where the head ends up after the next move, including any jump through a portal.
+	pub fn next_head(&self, direction: Option<Direction>, portals: &[Portal]) -> (i32, i32)
 	{
 		let (head_x, head_y): (i32, i32) = self.head_position();
 
 		let mut moving_direction = self.direction;
 
-		match direction
+		if let Some(direction) = direction
 		{
-			Some(direction) => moving_direction = direction,
-			None => ()
+			moving_direction = direction;
 		}
 
-		match moving_direction
-		{
-			Direction::Up => (head_x, head_y - 1),
-			Direction::Down => (head_x, head_y + 1),
-			Direction::Left => (head_x - 1, head_y),
-			Direction::Right => (head_x + 1, head_y)
-		}
+		step((head_x, head_y), moving_direction, portals)
 	}
 
 	pub fn increase_body_length(&mut self)
@@ -171,6 +137,76 @@ impl Snake
 			}
 		}
 
-		return false;
+		false
+	}
+}
+
+// The tile reached by moving one tile from 'position', including any jump through a portal.
+pub fn step((x, y): (i32, i32), direction: Direction, portals: &[Portal]) -> (i32, i32)
+{
+	let next = match direction
+	{
+		Direction::Up => (x, y - 1),
+		Direction::Down => (x, y + 1),
+		Direction::Left => (x - 1, y),
+		Direction::Right => (x + 1, y)
+	};
+
+	for (entrance, exit) in portals
+	{
+		if next == *entrance
+		{
+			return *exit;
+		}
+
+		if next == *exit
+		{
+			return *entrance;
+		}
+	}
+
+	next
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn eating_grows_the_snake_by_one()
+	{
+		let mut snake = Snake::new(2, 2);
+
+		assert_eq!(snake.len(), 3);
+
+		for eaten in 1..=5
+		{
+			// Growing re-attaches the tail left behind by the last move, the way check_eating does after a move.
+			snake.move_forward(None, &[]);
+			snake.increase_body_length();
+
+			assert_eq!(snake.len(), 3 + eaten);
+		}
+	}
+
+	#[test]
+	fn portals_move_the_head_to_the_other_end()
+	{
+		// The head starts at (4, 2) heading right.
+		let portals = [((5, 2), (10, 7)), ((11, 7), (20, 20))];
+		let mut snake = Snake::new(2, 2);
+
+		assert_eq!(snake.next_head(None, &portals), (10, 7));
+
+		snake.move_forward(None, &portals);
+
+		assert_eq!(snake.head_position(), (10, 7));
+		assert_eq!(snake.len(), 3);
+
+		// Landing on an exit sends the head back to its entrance, but only one hop is taken per move.
+		assert_eq!(snake.next_head(None, &portals), (20, 20));
+		assert_eq!(snake.next_head(Some(Direction::Up), &portals), (10, 6));
+		assert_eq!(Snake::new(7, 7).next_head(None, &portals), (5, 2));
 	}
 }
@@ -0,0 +1,74 @@
+// Sound needs the "sound" feature, which is on by default. Without it the game is always silent, but builds without
+// the audio libraries rodio needs.
+#[cfg(feature = "sound")]
+pub use self::device::Sound;
+#[cfg(not(feature = "sound"))]
+pub use self::silent::Sound;
+
+#[cfg(feature = "sound")]
+mod device
+{
+	use rodio::{source::SineWave, DeviceSinkBuilder, MixerDeviceSink, Player, Source};
+	use std::time::Duration;
+
+	const EAT_FREQUENCY: f32 = 880.0;
+	const EAT_DURATION: Duration = Duration::from_millis(60);
+	const GAME_OVER_FREQUENCY: f32 = 220.0;
+	const GAME_OVER_DURATION: Duration = Duration::from_millis(400);
+	const VOLUME: f32 = 0.2;
+
+	// Plays the game's effects through one player that is kept for the whole game, rather than opening the device
+	// again for every sound.
+	pub struct Sound
+	{
+		// Playback stops as soon as the device is dropped, so it has to live as long as the player.
+		_device: MixerDeviceSink,
+		player:  Player
+	}
+
+	impl Sound
+	{
+		// Returns None when there is no audio device to play on, so the game can carry on without sound.
+		pub fn new() -> Option<Sound>
+		{
+			let mut device = DeviceSinkBuilder::open_default_sink()
+				.map_err(|error| eprintln!("No audio output available, sound is disabled: {}", error))
+				.ok()?;
+
+			device.log_on_drop(false);
+
+			let player = Player::connect_new(device.mixer());
+
+			Some(Sound {
+				_device: device,
+				player
+			})
+		}
+
+		pub fn eat(&self) { self.tone(EAT_FREQUENCY, EAT_DURATION) }
+
+		pub fn game_over(&self) { self.tone(GAME_OVER_FREQUENCY, GAME_OVER_DURATION) }
+
+		fn tone(&self, frequency: f32, duration: Duration)
+		{
+			self.player
+				.append(SineWave::new(frequency).take_duration(duration).amplify(VOLUME));
+		}
+	}
+}
+
+#[cfg(not(feature = "sound"))]
+mod silent
+{
+	pub struct Sound;
+
+	impl Sound
+	{
+		// There is never anything to play on, just as when no audio device is found.
+		pub fn new() -> Option<Sound> { None }
+
+		pub fn eat(&self) {}
+
+		pub fn game_over(&self) {}
+	}
+}
@@ -0,0 +1,86 @@
+// Food eaten within this many seconds of the previous one continues the streak.
+pub const STREAK_WINDOW: f64 = 3.0;
+pub const MAX_MULTIPLIER: u32 = 5;
+
+// Tracks how quickly food is being eaten in a row. Each food eaten while the streak is going raises the multiplier for
+// the next one, up to MAX_MULTIPLIER, and taking longer than STREAK_WINDOW drops it back to 1.
+pub struct Streak
+{
+	// Seconds since the last food, or None before the first one.
+	since_last_food: Option<f64>,
+	multiplier:      u32
+}
+
+impl Streak
+{
+	pub fn new() -> Streak
+	{
+		Streak {
+			since_last_food: None,
+			multiplier:      1
+		}
+	}
+
+	pub fn update(&mut self, delta_time: f64)
+	{
+		if let Some(elapsed) = &mut self.since_last_food
+		{
+			*elapsed += delta_time;
+
+			if *elapsed > STREAK_WINDOW
+			{
+				self.since_last_food = None;
+				self.multiplier = 1;
+			}
+		}
+	}
+
+	// Returns the multiplier this food is worth and starts the window for the next one.
+	pub fn eat(&mut self) -> u32
+	{
+		let multiplier = self.multiplier;
+
+		self.since_last_food = Some(0.0);
+		self.multiplier = (self.multiplier + 1).min(MAX_MULTIPLIER);
+
+		multiplier
+	}
+
+	// What the next food would be worth if it were eaten now.
+	pub fn get_multiplier(&self) -> u32 { self.multiplier }
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn quick_eating_raises_the_multiplier_until_the_window_runs_out()
+	{
+		let mut streak = Streak::new();
+
+		// Waiting before the first food doesn't matter.
+		streak.update(STREAK_WINDOW * 10.0);
+		assert_eq!(streak.eat(), 1);
+
+		streak.update(STREAK_WINDOW - 0.5);
+		assert_eq!(streak.eat(), 2);
+
+		// The window starts over with every food, rather than running from the start of the streak.
+		streak.update(STREAK_WINDOW - 0.5);
+		assert_eq!(streak.get_multiplier(), 3);
+		assert_eq!(streak.eat(), 3);
+
+		for _ in 0..10
+		{
+			streak.eat();
+		}
+
+		assert_eq!(streak.eat(), MAX_MULTIPLIER);
+
+		streak.update(STREAK_WINDOW + 0.1);
+		assert_eq!(streak.get_multiplier(), 1);
+		assert_eq!(streak.eat(), 1);
+	}
+}
@@ -1,16 +1,575 @@
-use crate::models::Post;
+use crate::{
+	events::{Change, Event},
+	models::{Post, PostError}
+};
+use std::{collections::HashSet, error::Error, fmt};
+use uuid::Uuid;
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
+pub enum ImportError
+{
+	InvalidJson(serde_json::Error),
+	MalformedPost(usize, serde_json::Error),
+	InvalidPost(usize, PostError),
+	DuplicateId(usize, Uuid),
+	// The post replies to or retweets this id, which is neither an existing post nor earlier in the same array.
+	UnknownParent(usize, Uuid)
+}
+
+impl fmt::Display for ImportError
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		match self
+		{
+			ImportError::InvalidJson(error) => write!(f, "Expected a JSON array of posts: {}", error),
+			ImportError::MalformedPost(index, error) => write!(f, "Post {} is malformed: {}", index, error),
+			ImportError::InvalidPost(index, error) => write!(f, "Post {} is invalid: {}", index, error),
+			ImportError::DuplicateId(index, uuid) => write!(f, "Post {} reuses the id {}", index, uuid),
+			ImportError::UnknownParent(index, uuid) => write!(f, "Post {} points at a missing post {}", index, uuid)
+		}
+	}
+}
+
+impl Error for ImportError {}
+
+#[derive(Debug)]
+pub enum AddError
+{
+	DuplicateId(Uuid),
+	// The post replies to or retweets a post that doesn't exist.
+	UnknownParent(Uuid)
+}
+
+impl fmt::Display for AddError
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		match self
+		{
+			AddError::DuplicateId(uuid) => write!(f, "There is already a post with the id {}", uuid),
+			AddError::UnknownParent(uuid) => write!(f, "There is no post with the id {} to reply to or retweet", uuid)
+		}
+	}
+}
+
+impl Error for AddError {}
+
+// What happens to replies and retweets when the post they point at is deleted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeletePolicy
+{
+	// Keep the post while anything still points at it.
+	Refuse,
+	// Delete everything that points at it too, including replies to those replies.
+	Cascade
+}
+
+#[derive(Debug)]
+pub enum DeleteError
+{
+	NotFound(Uuid),
+	// The ids of the replies and retweets still pointing at the post.
+	Referenced(Vec<Uuid>)
+}
+
+impl fmt::Display for DeleteError
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		match self
+		{
+			DeleteError::NotFound(uuid) => write!(f, "There is no post with the id {}", uuid),
+			DeleteError::Referenced(uuids) =>
+			{
+				let uuids: Vec<String> = uuids.iter().map(Uuid::to_string).collect();
+
+				write!(f, "The post is still replied to or retweeted by {}", uuids.join(", "))
+			}
+		}
+	}
+}
+
+impl Error for DeleteError {}
+
+#[derive(Clone, Debug, Default)]
 pub struct Database
 {
-	posts: Vec<Post>
+	posts:  Vec<Post>,
+	// Every change ever made, oldest first. Only ever appended to.
+	events: Vec<Event>
 }
 
 impl Database
 {
-	pub fn new() -> Database { Database { posts: vec![] } }
+	pub fn new() -> Database
+	{
+		Database {
+			posts:  vec![],
+			events: vec![]
+		}
+	}
+
+	// Rebuilds a database by applying each event in order. The log carries over too, so the result can keep recording
+	// where the original left off.
+	pub fn replay(events: &[Event]) -> Database
+	{
+		let mut database = Database::new();
+
+		for event in events
+		{
+			database.record(event.clone());
+		}
+
+		database
+	}
+
+	pub fn events(&self) -> &[Event] { &self.events }
+
+	// Every change goes through here, so the posts and the log can't get out of step.
+	fn record(&mut self, event: Event)
+	{
+		match event.get_change()
+		{
+			Change::Added(post) => self.posts.push(post.clone()),
+			Change::Deleted => self.posts.retain(|post| post.get_uuid() != event.get_uuid())
+		}
+
+		self.events.push(event);
+	}
 
-	pub fn add_post(&mut self, new_post: Post) { self.posts.push(new_post) }
+	// Adds the post unless its id is taken or it replies to or retweets a post that doesn't exist. Either would break
+	// 'delete_post': a deleted id would take every post sharing it along, and a reply to nothing can't be kept from
+	// pointing at a missing post.
+	pub fn add_post(&mut self, new_post: Post) -> Result<(), AddError>
+	{
+		let ids: HashSet<Uuid> = self.posts.iter().map(|post| *post.get_uuid()).collect();
+
+		if ids.contains(new_post.get_uuid())
+		{
+			return Err(AddError::DuplicateId(*new_post.get_uuid()));
+		}
+
+		if let Some(parent) = Database::missing_parent(&new_post, &ids)
+		{
+			return Err(AddError::UnknownParent(parent));
+		}
+
+		self.record(Event::new(*new_post.get_uuid(), Change::Added(new_post)));
+		Ok(())
+	}
+
+	// The first post the given one replies to or retweets that isn't among the ids.
+	fn missing_parent(post: &Post, ids: &HashSet<Uuid>) -> Option<Uuid>
+	{
+		[post.get_in_reply_to(), post.get_retweet_of()]
+			.into_iter()
+			.flatten()
+			.find(|parent| !ids.contains(parent))
+			.copied()
+	}
 
 	pub fn get_posts(&self) -> &Vec<Post> { &self.posts }
+
+	pub fn posts_with_media(&self) -> Vec<&Post> { self.posts.iter().filter(|post| post.has_media()).collect() }
+
+	// Deletes the post and returns the ids of every post removed, the post itself first. References to it are dealt
+	// with according to the policy, so no reply or retweet is ever left pointing at a post that doesn't exist.
+	pub fn delete_post(&mut self, uuid: Uuid, policy: DeletePolicy) -> Result<Vec<Uuid>, DeleteError>
+	{
+		if !self.posts.iter().any(|post| *post.get_uuid() == uuid)
+		{
+			return Err(DeleteError::NotFound(uuid));
+		}
+
+		let mut removed = vec![uuid];
+
+		if policy == DeletePolicy::Refuse
+		{
+			let references = self.dependents_of(&uuid);
+
+			if !references.is_empty()
+			{
+				return Err(DeleteError::Referenced(references));
+			}
+		}
+
+		// Each round picks up the posts pointing at the ones found in the round before.
+		let mut index = 0;
+
+		while index < removed.len()
+		{
+			for dependent in self.dependents_of(&removed[index])
+			{
+				if !removed.contains(&dependent)
+				{
+					removed.push(dependent);
+				}
+			}
+
+			index += 1;
+		}
+
+		for uuid in &removed
+		{
+			self.record(Event::new(*uuid, Change::Deleted));
+		}
+
+		Ok(removed)
+	}
+
+	fn dependents_of(&self, uuid: &Uuid) -> Vec<Uuid>
+	{
+		self.posts
+			.iter()
+			.filter(|post| post.depends_on(uuid))
+			.map(|post| *post.get_uuid())
+			.collect()
+	}
+
+	// Returns up to 'limit' posts following the one with the cursor id, in the order they were added, along with the
+	// cursor for the next page, which is None once there is nothing left. A None cursor starts from the beginning.
+	// Unlike an offset, the cursor keeps pointing at the same place when posts are added between fetches. A cursor
+	// that doesn't match any post gives an empty last page.
+	pub fn get_posts_after(&self, cursor: Option<Uuid>, limit: usize) -> (Vec<&Post>, Option<Uuid>)
+	{
+		let start = match cursor
+		{
+			Some(uuid) =>
+			{
+				match self.posts.iter().position(|post| *post.get_uuid() == uuid)
+				{
+					Some(index) => index + 1,
+					None => return (vec![], None)
+				}
+			}
+			None => 0
+		};
+
+		let page: Vec<&Post> = self.posts.iter().skip(start).take(limit).collect();
+
+		let next = match start + page.len() < self.posts.len()
+		{
+			true => page.last().map(|post| *post.get_uuid()),
+			false => None
+		};
+
+		(page, next)
+	}
+
+	// Appends every post in a JSON array and returns how many were added. The import is all or nothing: the first
+	// malformed or invalid entry, one whose id is already taken (by an existing post or earlier in the same array), or
+	// one pointing at a post that is neither, is reported by its index and nothing is added.
+	pub fn import_json(&mut self, json: &str) -> Result<usize, ImportError>
+	{
+		let entries: Vec<serde_json::Value> = serde_json::from_str(json).map_err(ImportError::InvalidJson)?;
+
+		let mut ids: HashSet<Uuid> = self.posts.iter().map(|post| *post.get_uuid()).collect();
+		let mut imported = Vec::with_capacity(entries.len());
+
+		for (index, entry) in entries.into_iter().enumerate()
+		{
+			let post: Post = serde_json::from_value(entry).map_err(|error| ImportError::MalformedPost(index, error))?;
+
+			post.validate()
+				.map_err(|error| ImportError::InvalidPost(index, error))?;
+
+			// Checked before the post's own id goes in, so a post can't reply to or retweet itself.
+			if let Some(parent) = Database::missing_parent(&post, &ids)
+			{
+				return Err(ImportError::UnknownParent(index, parent));
+			}
+
+			if !ids.insert(*post.get_uuid())
+			{
+				return Err(ImportError::DuplicateId(index, *post.get_uuid()));
+			}
+
+			imported.push(post);
+		}
+
+		let count = imported.len();
+
+		// Every post was checked above, against the ones before it in the array too, which 'add_post' couldn't see
+		// yet.
+		for post in imported
+		{
+			self.record(Event::new(*post.get_uuid(), Change::Added(post)));
+		}
+
+		Ok(count)
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	const FIRST_ID: &str = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+	const SECOND_ID: &str = "1b4e28ba-2fa1-11d2-883f-0016d3cca427";
+
+	fn post_json(uuid: &str, media: &str) -> String
+	{
+		format!(
+			r#"{{"title":"Title","body":"Body","author":"Author","datetime":"2024-01-01T00:00:00Z","uuid":"{}","media":[{}]}}"#,
+			uuid, media
+		)
+	}
+
+	fn database_with_posts(count: usize) -> (Database, Vec<Uuid>)
+	{
+		let mut database = Database::new();
+		let mut ids = vec![];
+
+		for index in 0..count
+		{
+			let uuid = Uuid::new_v4();
+			let title = format!("Post {}", index);
+
+			database
+				.add_post(Post::new(&title, "Body", "Author", chrono::Utc::now(), uuid, vec![]).unwrap())
+				.unwrap();
+			ids.push(uuid);
+		}
+
+		(database, ids)
+	}
+
+	fn post(uuid: Uuid) -> Post { Post::new("Title", "Body", "Author", chrono::Utc::now(), uuid, vec![]).unwrap() }
+
+	fn page_ids(page: &[&Post]) -> Vec<Uuid> { page.iter().map(|post| *post.get_uuid()).collect() }
+
+	#[test]
+	fn get_posts_after_pages_through_every_post()
+	{
+		let (mut database, ids) = database_with_posts(5);
+
+		let (first, cursor) = database.get_posts_after(None, 2);
+
+		assert_eq!(page_ids(&first), ids[0..2]);
+		assert_eq!(cursor, Some(ids[1]));
+
+		// A post added between fetches lands at the end instead of shifting the pages still to come.
+		let (mut added, _) = database_with_posts(1);
+		let added_id = *added.get_posts()[0].get_uuid();
+
+		database.add_post(added.posts.remove(0)).unwrap();
+
+		let (middle, cursor) = database.get_posts_after(cursor, 2);
+
+		assert_eq!(page_ids(&middle), ids[2..4]);
+		assert_eq!(cursor, Some(ids[3]));
+
+		let (last, cursor) = database.get_posts_after(cursor, 2);
+
+		assert_eq!(page_ids(&last), vec![ids[4], added_id]);
+		assert_eq!(cursor, None);
+	}
+
+	#[test]
+	fn get_posts_after_an_unknown_cursor_is_empty()
+	{
+		let (database, _) = database_with_posts(2);
+
+		let (page, cursor) = database.get_posts_after(Some(Uuid::new_v4()), 10);
+
+		assert!(page.is_empty());
+		assert_eq!(cursor, None);
+
+		let empty = Database::new();
+		let (page, cursor) = empty.get_posts_after(None, 10);
+
+		assert!(page.is_empty());
+		assert_eq!(cursor, None);
+	}
+
+	#[test]
+	fn delete_refuses_while_a_post_is_referenced()
+	{
+		let (mut database, ids) = database_with_posts(2);
+		let reply = Uuid::new_v4();
+		let retweet = Uuid::new_v4();
+
+		database.add_post(post(reply).replying_to(ids[0])).unwrap();
+		database.add_post(post(retweet).retweeting(ids[0])).unwrap();
+
+		match database.delete_post(ids[0], DeletePolicy::Refuse)
+		{
+			Err(DeleteError::Referenced(references)) => assert_eq!(references, vec![reply, retweet]),
+			other => panic!("expected Referenced, got {:?}", other)
+		}
+
+		assert_eq!(database.get_posts().len(), 4);
+
+		// Nothing points at the second post, and a reply itself can go once nothing points at it.
+		assert_eq!(
+			database.delete_post(ids[1], DeletePolicy::Refuse).unwrap(),
+			vec![ids[1]]
+		);
+		assert_eq!(database.delete_post(reply, DeletePolicy::Refuse).unwrap(), vec![reply]);
+
+		assert!(matches!(
+			database.delete_post(reply, DeletePolicy::Refuse),
+			Err(DeleteError::NotFound(_))
+		));
+	}
+
+	#[test]
+	fn add_post_refuses_taken_ids_and_missing_parents()
+	{
+		let (mut database, ids) = database_with_posts(1);
+		let missing = Uuid::new_v4();
+		let reply = Uuid::new_v4();
+
+		assert!(matches!(
+			database.add_post(post(ids[0])),
+			Err(AddError::DuplicateId(uuid)) if uuid == ids[0]
+		));
+		assert!(matches!(
+			database.add_post(post(reply).replying_to(missing)),
+			Err(AddError::UnknownParent(uuid)) if uuid == missing
+		));
+		assert!(matches!(
+			database.add_post(post(reply).retweeting(missing)),
+			Err(AddError::UnknownParent(uuid)) if uuid == missing
+		));
+		assert!(matches!(
+			database.add_post(post(reply).replying_to(reply)),
+			Err(AddError::UnknownParent(_))
+		));
+
+		assert_eq!(database.get_posts().len(), 1);
+		assert_eq!(database.events().len(), 1);
+
+		database.add_post(post(reply).replying_to(ids[0])).unwrap();
+		database.add_post(post(Uuid::new_v4()).retweeting(reply)).unwrap();
+
+		assert_eq!(database.get_posts().len(), 3);
+	}
+
+	#[test]
+	fn delete_cascades_to_replies_and_retweets()
+	{
+		let (mut database, ids) = database_with_posts(2);
+		let reply = Uuid::new_v4();
+		let reply_to_reply = Uuid::new_v4();
+		let retweet = Uuid::new_v4();
+
+		database.add_post(post(reply).replying_to(ids[0])).unwrap();
+		database.add_post(post(reply_to_reply).replying_to(reply)).unwrap();
+		database.add_post(post(retweet).retweeting(ids[0])).unwrap();
+
+		let removed = database.delete_post(ids[0], DeletePolicy::Cascade).unwrap();
+
+		assert_eq!(removed, vec![ids[0], reply, retweet, reply_to_reply]);
+		assert_eq!(page_ids(&database.get_posts().iter().collect::<Vec<_>>()), vec![ids[1]]);
+	}
+
+	fn post_jsons(database: &Database) -> Vec<String> { database.get_posts().iter().map(Post::to_json).collect() }
+
+	#[test]
+	fn replaying_the_event_log_rebuilds_the_same_posts()
+	{
+		let (mut database, ids) = database_with_posts(3);
+		let reply = Uuid::new_v4();
+
+		database.add_post(post(reply).replying_to(ids[0])).unwrap();
+		database.import_json(&format!("[{}]", post_json(FIRST_ID, ""))).unwrap();
+		database.delete_post(ids[0], DeletePolicy::Cascade).unwrap();
+		database.delete_post(ids[2], DeletePolicy::Refuse).unwrap();
+
+		// Four adds, the import, then the cascade's two deletes and the last delete.
+		let changes: Vec<(Uuid, bool)> = database
+			.events()
+			.iter()
+			.map(|event| (*event.get_uuid(), matches!(event.get_change(), Change::Added(_))))
+			.collect();
+
+		assert_eq!(changes.len(), 8);
+		assert_eq!(changes[5..], [(ids[0], false), (reply, false), (ids[2], false)]);
+		assert!(database
+			.events()
+			.windows(2)
+			.all(|pair| pair[0].get_timestamp() <= pair[1].get_timestamp()));
+
+		let replayed = Database::replay(database.events());
+
+		assert_eq!(post_jsons(&replayed), post_jsons(&database));
+		assert_eq!(replayed.events().len(), database.events().len());
+
+		// A log cut short gives the state as it was at that point.
+		assert_eq!(Database::replay(&database.events()[..5]).get_posts().len(), 5);
+	}
+
+	#[test]
+	fn import_json_appends_every_post()
+	{
+		let mut database = Database::new();
+		let json = format!(
+			"[{}, {}]",
+			post_json(FIRST_ID, ""),
+			post_json(SECOND_ID, r#""https://example.com/a.png""#)
+		);
+
+		assert_eq!(database.import_json(&json).unwrap(), 2);
+		assert_eq!(database.get_posts().len(), 2);
+		assert_eq!(database.posts_with_media().len(), 1);
+	}
+
+	#[test]
+	fn import_json_is_all_or_nothing()
+	{
+		let mut database = Database::new();
+		let json = format!("[{}, {{\"title\": 5}}]", post_json(FIRST_ID, ""));
+
+		match database.import_json(&json)
+		{
+			Err(ImportError::MalformedPost(index, _)) => assert_eq!(index, 1),
+			other => panic!("expected MalformedPost, got {:?}", other)
+		}
+
+		assert!(database.get_posts().is_empty());
+
+		let json = format!(
+			"[{}, {}]",
+			post_json(FIRST_ID, ""),
+			post_json(SECOND_ID, r#""ftp://example.com""#)
+		);
+
+		match database.import_json(&json)
+		{
+			Err(ImportError::InvalidPost(index, PostError::InvalidMedia(_))) => assert_eq!(index, 1),
+			other => panic!("expected InvalidPost, got {:?}", other)
+		}
+
+		let json = format!("[{}, {}]", post_json(FIRST_ID, ""), post_json(FIRST_ID, ""));
+
+		match database.import_json(&json)
+		{
+			Err(ImportError::DuplicateId(index, _)) => assert_eq!(index, 1),
+			other => panic!("expected DuplicateId, got {:?}", other)
+		}
+
+		assert!(database.get_posts().is_empty());
+
+		// A reply may point at a post earlier in the same array, but not at one later on or nowhere at all.
+		let reply = |uuid: &str, parent: &str| {
+			post_json(uuid, "").replace(r#""media""#, &format!(r#""in_reply_to":"{}","media""#, parent))
+		};
+
+		let json = format!("[{}, {}]", reply(SECOND_ID, FIRST_ID), post_json(FIRST_ID, ""));
+
+		match database.import_json(&json)
+		{
+			Err(ImportError::UnknownParent(index, _)) => assert_eq!(index, 0),
+			other => panic!("expected UnknownParent, got {:?}", other)
+		}
+
+		assert!(database.get_posts().is_empty());
+
+		let json = format!("[{}, {}]", post_json(FIRST_ID, ""), reply(SECOND_ID, FIRST_ID));
+
+		assert_eq!(database.import_json(&json).unwrap(), 2);
+	}
 }
@@ -0,0 +1,38 @@
+use crate::models::Post;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+#[derive(Clone, Debug)]
+pub enum Change
+{
+	Added(Post),
+	Deleted
+}
+
+// One change made to the database, in the order they happened. A cascading delete is recorded as one event per post
+// it removed, so replaying never has to know which policy was used.
+#[derive(Clone, Debug)]
+pub struct Event
+{
+	timestamp: DateTime<Utc>,
+	uuid:      Uuid,
+	change:    Change
+}
+
+impl Event
+{
+	pub fn new(uuid: Uuid, change: Change) -> Event
+	{
+		Event {
+			timestamp: Utc::now(),
+			uuid,
+			change
+		}
+	}
+
+	pub fn get_timestamp(&self) -> &DateTime<Utc> { &self.timestamp }
+
+	pub fn get_uuid(&self) -> &Uuid { &self.uuid }
+
+	pub fn get_change(&self) -> &Change { &self.change }
+}
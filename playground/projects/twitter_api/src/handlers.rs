@@ -1,4 +1,7 @@
-use crate::{database::Database, models::Post};
+use crate::{
+	database::{AddError, Database, DeleteError, DeletePolicy},
+	models::Post
+};
 use iron::{headers::ContentType, status, AfterMiddleware, Handler, IronResult, Request, Response};
 use router::Router;
 use std::{
@@ -49,9 +52,10 @@ macro_rules! get_http_param {
 
 pub struct Handlers
 {
-	pub post_feed: PostFeedHandler,
-	pub post_post: PostPostHandler,
-	pub post:      PostHandler
+	pub post_feed:   PostFeedHandler,
+	pub post_post:   PostPostHandler,
+	pub post:        PostHandler,
+	pub delete_post: DeletePostHandler
 }
 
 impl Handlers
@@ -60,9 +64,10 @@ impl Handlers
 	{
 		let database = Arc::new(Mutex::new(db));
 		Handlers {
-			post_feed: PostFeedHandler::new(database.clone()),
-			post_post: PostPostHandler::new(database.clone()),
-			post:      PostHandler::new(database.clone())
+			post_feed:   PostFeedHandler::new(database.clone()),
+			post_post:   PostPostHandler::new(database.clone()),
+			post:        PostHandler::new(database.clone()),
+			delete_post: DeletePostHandler::new(database.clone())
 		}
 	}
 }
@@ -103,10 +108,14 @@ impl Handler for PostPostHandler
 		let mut payload = String::new();
 		try_handler!(req.body.read_to_string(&mut payload));
 
-		let post = try_handler!(serde_json::from_str(payload.as_str()), status::BadRequest);
+		let post = try_handler!(Post::from_json(&payload), status::BadRequest);
 
-		lock!(self.database).add_post(post);
-		Ok(Response::with((status::Created, payload)))
+		match lock!(self.database).add_post(post)
+		{
+			Ok(()) => Ok(Response::with((status::Created, payload))),
+			Err(error @ AddError::DuplicateId(_)) => Ok(Response::with((status::Conflict, error.to_string()))),
+			Err(error @ AddError::UnknownParent(_)) => Ok(Response::with((status::BadRequest, error.to_string())))
+		}
 	}
 }
 
@@ -123,7 +132,7 @@ impl PostHandler
 	{
 		let locked = lock!(self.database);
 		let mut iterator = locked.get_posts().iter();
-		iterator.find(|p| p.get_uuid() == id).map(|p| p.clone())
+		iterator.find(|p| p.get_uuid() == id).cloned()
 	}
 }
 
@@ -131,14 +140,13 @@ impl Handler for PostHandler
 {
 	fn handle(&self, req: &mut Request) -> IronResult<Response>
 	{
-		let ref post_id = get_http_param!(req, "id");
+		let post_id = get_http_param!(req, "id");
 
 		let id = try_handler!(Uuid::parse_str(post_id), status::BadRequest);
 
 		if let Some(post) = self.find_post(&id)
 		{
-			let payload = try_handler!(serde_json::to_string(&post), status::InternalServerError);
-			Ok(Response::with((status::Ok, payload)))
+			Ok(Response::with((status::Ok, post.to_json())))
 		}
 		else
 		{
@@ -147,6 +155,53 @@ impl Handler for PostHandler
 	}
 }
 
+pub struct DeletePostHandler
+{
+	database: Arc<Mutex<Database>>
+}
+
+impl DeletePostHandler
+{
+	fn new(database: Arc<Mutex<Database>>) -> DeletePostHandler { DeletePostHandler { database } }
+}
+
+impl Handler for DeletePostHandler
+{
+	fn handle(&self, req: &mut Request) -> IronResult<Response>
+	{
+		let post_id = get_http_param!(req, "id");
+
+		let id = try_handler!(Uuid::parse_str(post_id), status::BadRequest);
+
+		let policy = delete_policy(req.url.query());
+
+		match lock!(self.database).delete_post(id, policy)
+		{
+			Ok(removed) =>
+			{
+				let payload = try_handler!(serde_json::to_string(&removed));
+				Ok(Response::with((status::Ok, payload)))
+			}
+			Err(DeleteError::NotFound(_)) => Ok(Response::with(status::NotFound)),
+			Err(error) => Ok(Response::with((status::Conflict, error.to_string())))
+		}
+	}
+}
+
+// Deleting replies and retweets along with the post has to be asked for explicitly, with "cascade=true" anywhere in the
+// query.
+fn delete_policy(query: Option<&str>) -> DeletePolicy
+{
+	let cascade = url::form_urlencoded::parse(query.unwrap_or_default().as_bytes())
+		.any(|(key, value)| key == "cascade" && value == "true");
+
+	match cascade
+	{
+		true => DeletePolicy::Cascade,
+		false => DeletePolicy::Refuse
+	}
+}
+
 pub struct JsonAfterMiddleware;
 
 impl AfterMiddleware for JsonAfterMiddleware
@@ -157,3 +212,29 @@ impl AfterMiddleware for JsonAfterMiddleware
 		Ok(res)
 	}
 }
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn cascade_is_found_anywhere_in_the_query()
+	{
+		for query in ["cascade=true", "cascade=true&x=1", "x=1&cascade=true"]
+		{
+			assert_eq!(delete_policy(Some(query)), DeletePolicy::Cascade, "{}", query);
+		}
+
+		for query in [
+			None,
+			Some(""),
+			Some("cascade=false"),
+			Some("cascade"),
+			Some("x=cascade=true")
+		]
+		{
+			assert_eq!(delete_policy(query), DeletePolicy::Refuse, "{:?}", query);
+		}
+	}
+}
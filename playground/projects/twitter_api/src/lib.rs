@@ -0,0 +1,4 @@
+pub mod database;
+pub mod events;
+pub mod handlers;
+pub mod models;
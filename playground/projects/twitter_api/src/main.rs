@@ -1,40 +1,38 @@
-mod database;
-mod handlers;
-mod models;
-
-use database::Database;
-use handlers::*;
 use iron::{prelude::Chain, Iron};
 use logger::Logger;
-use models::*;
 use router::Router;
+use twitter_api::{database::Database, handlers::*, models::*};
 use uuid::Uuid;
 
 fn main()
 {
 	env_logger::init();
-	
+
 	let (logger_before, logger_after) = Logger::new(None);
 
 	let mut db = Database::new();
-	
+
 	let p = Post::new(
 		"The First Post",
 		"This is the first post in our API",
 		"Tensor",
 		chrono::offset::Utc::now(),
-		Uuid::new_v4()
-	);
-	db.add_post(p);
+		Uuid::new_v4(),
+		vec![]
+	)
+	.expect("Seed post should be valid");
+	db.add_post(p).expect("Seed posts should be accepted");
 
 	let p2 = Post::new(
 		"The next post is better",
 		"Iron is really cool and Rust is awesome too!",
 		"Metalman",
 		chrono::offset::Utc::now(),
-		Uuid::new_v4()
-	);
-	db.add_post(p2);
+		Uuid::new_v4(),
+		vec![]
+	)
+	.expect("Seed post should be valid");
+	db.add_post(p2).expect("Seed posts should be accepted");
 
 	let handlers = Handlers::new(db);
 	let json_content_middleware = JsonAfterMiddleware;
@@ -43,11 +41,12 @@ fn main()
 	router.get("/post_feed", handlers.post_feed, "post_feed");
 	router.post("/post", handlers.post_post, "post_post");
 	router.get("/post/:id", handlers.post, "post");
+	router.delete("/post/:id", handlers.delete_post, "delete_post");
 
 	let mut chain = Chain::new(router);
 	chain.link_before(logger_before);
 	chain.link_after(json_content_middleware);
 	chain.link_after(logger_after);
 
-	Iron::new(chain).http("localhost:8000");
-}
\ No newline at end of file
+	Iron::new(chain).http("localhost:8000").expect("Failed to start server");
+}
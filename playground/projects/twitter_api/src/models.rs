@@ -1,29 +1,280 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt};
+use url::Url;
 use uuid::Uuid;
 
+const MAX_MEDIA: usize = 4;
+
+#[derive(Debug)]
+pub enum PostError
+{
+	TooManyMedia(usize),
+	InvalidMedia(String),
+	MalformedJson(serde_json::Error)
+}
+
+impl fmt::Display for PostError
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+	{
+		match self
+		{
+			PostError::TooManyMedia(count) =>
+			{
+				write!(
+					f,
+					"A post can have at most {} media attachments, got {}",
+					MAX_MEDIA, count
+				)
+			}
+			PostError::InvalidMedia(media) => write!(f, "Invalid media URL: {}", media),
+			PostError::MalformedJson(error) => write!(f, "Malformed post JSON: {}", error)
+		}
+	}
+}
+
+impl Error for PostError {}
+
+// The JSON field names are part of the API described in the README, so they are spelled out rather than taken from the
+// Rust names, which keeps them stable if a field is ever renamed.
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Post
 {
-	title:    String,
-	body:     String,
-	author:   String,
-	datetime: DateTime<Utc>,
-	uuid:     Uuid
+	#[serde(rename = "title")]
+	title:       String,
+	#[serde(rename = "body")]
+	body:        String,
+	#[serde(rename = "author")]
+	author:      String,
+	#[serde(rename = "datetime")]
+	datetime:    DateTime<Utc>,
+	#[serde(rename = "uuid")]
+	uuid:        Uuid,
+	#[serde(rename = "media", default)]
+	media:       Vec<String>,
+	#[serde(rename = "in_reply_to", default, skip_serializing_if = "Option::is_none")]
+	in_reply_to: Option<Uuid>,
+	#[serde(rename = "retweet_of", default, skip_serializing_if = "Option::is_none")]
+	retweet_of:  Option<Uuid>
 }
 
 impl Post
 {
-	pub fn new(title: &str, body: &str, author: &str, datetime: DateTime<Utc>, uuid: Uuid) -> Post
+	pub fn new(
+		title: &str,
+		body: &str,
+		author: &str,
+		datetime: DateTime<Utc>,
+		uuid: Uuid,
+		media: Vec<String>
+	) -> Result<Post, PostError>
 	{
-		Post {
+		let post = Post {
 			title: title.to_string(),
 			body: body.to_string(),
 			author: author.to_string(),
 			datetime,
-			uuid
+			uuid,
+			media,
+			in_reply_to: None,
+			retweet_of: None
+		};
+
+		post.validate()?;
+		Ok(post)
+	}
+
+	// Posts can also arrive through deserialization, which skips `new`, so the checks live here to be reusable.
+	pub fn validate(&self) -> Result<(), PostError>
+	{
+		if self.media.len() > MAX_MEDIA
+		{
+			return Err(PostError::TooManyMedia(self.media.len()));
 		}
+
+		// Anything else that parses as a URL, such as "javascript:" or "file:", isn't something a client should fetch.
+		for media in &self.media
+		{
+			match Url::parse(media)
+			{
+				Ok(url) if url.scheme() == "http" || url.scheme() == "https" => (),
+				_ => return Err(PostError::InvalidMedia(media.clone()))
+			}
+		}
+
+		Ok(())
+	}
+
+	// Parses a post in the API's JSON format, applying the same checks as 'new'.
+	pub fn from_json(json: &str) -> Result<Post, PostError>
+	{
+		let post: Post = serde_json::from_str(json).map_err(PostError::MalformedJson)?;
+
+		post.validate()?;
+		Ok(post)
+	}
+
+	pub fn to_json(&self) -> String
+	{
+		// Every field is a string or a list of strings once serialized, so this can't fail.
+		serde_json::to_string(self).unwrap()
 	}
 
 	pub fn get_uuid(&self) -> &Uuid { &self.uuid }
+
+	pub fn replying_to(mut self, parent: Uuid) -> Post
+	{
+		self.in_reply_to = Some(parent);
+		self
+	}
+
+	pub fn retweeting(mut self, original: Uuid) -> Post
+	{
+		self.retweet_of = Some(original);
+		self
+	}
+
+	pub fn get_in_reply_to(&self) -> Option<&Uuid> { self.in_reply_to.as_ref() }
+
+	pub fn get_retweet_of(&self) -> Option<&Uuid> { self.retweet_of.as_ref() }
+
+	// Whether this post is a reply to or a retweet of the given one.
+	pub fn depends_on(&self, uuid: &Uuid) -> bool
+	{
+		self.in_reply_to.as_ref() == Some(uuid) || self.retweet_of.as_ref() == Some(uuid)
+	}
+
+	// The body cut down to at most 'max' characters for previews, ending in an ellipsis when anything was removed.
+	// Characters are Unicode scalar values, so multibyte characters such as emoji are never split.
+	pub fn display_text(&self, max: usize) -> String
+	{
+		if self.body.chars().count() <= max
+		{
+			return self.body.clone();
+		}
+
+		if max == 0
+		{
+			return String::new();
+		}
+
+		let mut text: String = self.body.chars().take(max - 1).collect();
+		text.push('…');
+		text
+	}
+
+	pub fn has_media(&self) -> bool { !self.media.is_empty() }
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn post_with_media(media: Vec<String>) -> Result<Post, PostError>
+	{
+		Post::new("Title", "Body", "Author", Utc::now(), Uuid::new_v4(), media)
+	}
+
+	fn media(count: usize) -> Vec<String>
+	{
+		(0..count)
+			.map(|index| format!("https://example.com/{}.png", index))
+			.collect()
+	}
+
+	#[test]
+	fn media_count_is_capped()
+	{
+		assert!(post_with_media(media(MAX_MEDIA)).unwrap().has_media());
+
+		match post_with_media(media(MAX_MEDIA + 1))
+		{
+			Err(PostError::TooManyMedia(count)) => assert_eq!(count, MAX_MEDIA + 1),
+			other => panic!("expected TooManyMedia, got {:?}", other)
+		}
+	}
+
+	#[test]
+	fn malformed_or_non_http_media_is_rejected()
+	{
+		for url in ["not a url", "javascript:alert(1)", "file:///etc/passwd"]
+		{
+			match post_with_media(vec![String::from(url)])
+			{
+				Err(PostError::InvalidMedia(media)) => assert_eq!(media, url),
+				other => panic!("expected InvalidMedia for {}, got {:?}", url, other)
+			}
+		}
+
+		assert!(post_with_media(vec![String::from("http://example.com/clip.mp4")]).is_ok());
+	}
+
+	fn post_with_body(body: &str) -> Post
+	{
+		Post::new("Title", body, "Author", Utc::now(), Uuid::new_v4(), vec![]).unwrap()
+	}
+
+	#[test]
+	fn json_round_trips_with_the_documented_field_names()
+	{
+		let post = post_with_media(media(2)).unwrap();
+		let json = post.to_json();
+
+		let fields: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&json).unwrap();
+		let mut names: Vec<&str> = fields.keys().map(String::as_str).collect();
+
+		names.sort();
+		assert_eq!(names, ["author", "body", "datetime", "media", "title", "uuid"]);
+
+		let parsed = Post::from_json(&json).unwrap();
+
+		assert_eq!(parsed.to_json(), json);
+		assert_eq!(parsed.get_uuid(), post.get_uuid());
+	}
+
+	#[test]
+	fn from_json_rejects_malformed_and_invalid_posts()
+	{
+		assert!(matches!(
+			Post::from_json(r#"{"title": 5}"#),
+			Err(PostError::MalformedJson(_))
+		));
+
+		let mut json: serde_json::Value = serde_json::from_str(&post_with_media(vec![]).unwrap().to_json()).unwrap();
+
+		json["media"] = serde_json::json!(["ftp://example.com"]);
+
+		assert!(matches!(
+			Post::from_json(&json.to_string()),
+			Err(PostError::InvalidMedia(_))
+		));
+
+		// Media is optional and defaults to none.
+		json.as_object_mut().unwrap().remove("media");
+
+		assert!(!Post::from_json(&json.to_string()).unwrap().has_media());
+	}
+
+	#[test]
+	fn display_text_counts_characters_not_bytes()
+	{
+		let post = post_with_body("héllo 👋🌍!");
+
+		assert_eq!(post.display_text(9), "héllo 👋🌍!");
+		assert_eq!(post.display_text(100), "héllo 👋🌍!");
+		assert_eq!(post.display_text(0), "");
+		assert_eq!(post.display_text(3), "hé…");
+	}
+
+	#[test]
+	fn display_text_never_splits_an_emoji()
+	{
+		let post = post_with_body("ab👋🌍cd");
+
+		// The cut lands right after each emoji in turn, so a byte-based cut would land inside one.
+		assert_eq!(post.display_text(4), "ab👋…");
+		assert_eq!(post.display_text(5), "ab👋🌍…");
+	}
 }